@@ -6,6 +6,11 @@
 #include <iomanip>
 #include <iostream>
 #include <fstream>
+#include <string>
+
+#ifdef G2048_RUST
+#include "g2048.h"
+#endif
 
 static std::random_device rd;
 static std::independent_bits_engine<std::random_device,
@@ -18,7 +23,8 @@ typedef std::chrono::steady_clock default_clock;
 
 /* free functions */
 
-score_t shift_line(std::array<cell_value_t*, board_size> &line)
+score_t shift_line(std::array<cell_value_t*, board_size> &line,
+                   std::array<bool, board_size> *merged_cells)
 {
     score_t result = 0;
     for (size_t i = 0; i < line.size(); /* nothing */) {
@@ -30,6 +36,9 @@ score_t shift_line(std::array<cell_value_t*, board_size> &line)
             *line[i-1] += 1;
             *line[i] = 0;
             merged = true;
+            if (merged_cells) {
+                (*merged_cells)[i-1] = true;
+            }
             // result += round(pow(SCORE_MERGE_BASE, *line[i-1]));
             result += round((*line[i-1]) * SCORE_MERGE_FACTOR);
         }
@@ -504,8 +513,101 @@ void read_state(std::istream &is, uint8_t &state)
     is.read((std::istream::char_type*)&state, 1);
 }
 
-int main()
+/**
+ * Conformance driver for testdata/shift_vectors.txt: read lines of
+ * board_size cells from stdin, shift each of them and print the result
+ * as "<cells> | <merge flags>".
+ */
+int shift_lines_main()
+{
+    std::array<int, board_size> input;
+    while (true) {
+        for (auto &cell: input) {
+            std::cin >> cell;
+        }
+        if (!std::cin) {
+            return 0;
+        }
+
+        std::array<cell_value_t, board_size> cells;
+        std::array<cell_value_t*, board_size> line;
+        std::array<bool, board_size> merged{};
+        for (size_t i = 0; i < board_size; i++) {
+            cells[i] = input[i];
+            line[i] = &cells[i];
+        }
+        shift_line(line, &merged);
+
+        for (auto cell: cells) {
+            std::cout << (int)cell << " ";
+        }
+        std::cout << "|";
+        for (auto flag: merged) {
+            std::cout << " " << (int)flag;
+        }
+        std::cout << std::endl;
+    }
+}
+
+#ifdef G2048_RUST
+/**
+ * Serve the pipe protocol with the searcher of the Rust library,
+ * configured with the AI options in argv, instead of the AI above.
+ */
+int rust_main(int argc, char **argv)
 {
+    if (g2048_abi_version() != NATIVE_ABI_VERSION) {
+        std::cerr << "ai: Rust library has ABI version "
+                  << g2048_abi_version() << ", expected "
+                  << NATIVE_ABI_VERSION << std::endl;
+        return 4;
+    }
+    std::unique_ptr<g2048_searcher, void (*)(g2048_searcher*)> searcher(
+        g2048_searcher_configure(argc, argv), g2048_searcher_free);
+    if (!searcher) {
+        std::cerr << "ai: invalid options for the Rust searcher" << std::endl;
+        return 4;
+    }
+
+    RawBoard board;
+    uint8_t state;
+    while (true) {
+        read_board(std::cin, board);
+        read_state(std::cin, state);
+        if (!std::cin) {
+            return 0;
+        }
+
+        uint8_t cells[board_size * board_size];
+        for (size_t y = 0; y < board_size; y++) {
+            for (size_t x = 0; x < board_size; x++) {
+                cells[y * board_size + x] = board[y][x];
+            }
+        }
+        int move = g2048_searcher_move(searcher.get(), cells);
+        if (move == NATIVE_NO_MOVE) {
+            std::cerr << "ai: no further options. terminating." << std::endl;
+            return 1;
+        } else if (move < 0) {
+            std::cerr << "ai: Rust searcher failed with " << move << std::endl;
+            return 5;
+        }
+        std::cout << (uint8_t)move << std::flush;
+    }
+}
+#endif
+
+int main(int argc, char **argv)
+{
+    if (argc > 1 && std::string(argv[1]) == "--shift-lines") {
+        return shift_lines_main();
+    }
+#ifdef G2048_RUST
+    if (argc > 1 && std::string(argv[1]) == "--rust") {
+        return rust_main(argc - 2, argv + 2);
+    }
+#endif
+
     logfile.open("ai++.log", std::ios_base::out | std::ios_base::trunc);
     if (!logfile.is_open()) {
         std::cerr << "ai: cannot open log. terminating." << std::endl;
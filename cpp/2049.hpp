@@ -53,7 +53,8 @@ struct PartialAction {
 
 typedef std::array<std::array<cell_value_t, board_size>, board_size> RawBoard;
 
-score_t shift_line(std::array<cell_value_t*, board_size> &line);
+score_t shift_line(std::array<cell_value_t*, board_size> &line,
+                   std::array<bool, board_size> *merged = nullptr);
 
 class GameBoard {
 public:
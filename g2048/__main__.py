@@ -18,6 +18,17 @@ parser.add_argument(
     nargs="+",
     default=None,
     help="Use the stdio AI provided instead of builtin")
+parser.add_argument(
+    "--ai-native",
+    default=False,
+    action="store_true",
+    help="Use the Rust searcher in-process (build it with make native in"
+    " rust/)")
+parser.add_argument(
+    "--board-size",
+    type=int,
+    default=4,
+    help="Width and height of the board (3 to 8 with the Rust AI)")
 parser.add_argument(
     "--profile",
     default=False,
@@ -34,12 +45,15 @@ logging.basicConfig(
     level=logging.DEBUG,
     filename="ai.log")
 
-game = g2048.logic.Game()
+game = g2048.logic.Game(board_size=args.board_size)
 game.new_game()
 frontend = g2048.urwid_frontend.UrwidFrontend(game)
 if args.ai:
     if args.ai_command:
         ai = g2048.pipe_ai.SubprocessAI(args.ai_command)
+    elif args.ai_native:
+        import g2048.native
+        ai = g2048.native.NativeAI()
     else:
         ai = g2048.ai.AI()
 
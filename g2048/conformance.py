@@ -0,0 +1,39 @@
+"""
+Conformance driver for the golden shift vectors in
+testdata/shift_vectors.txt.
+
+Reads one line of four space separated cells per line from stdin, shifts
+it with :meth:`GameBoard.shift_array` and prints the result in the form
+``<cells> | <merge flags>``. The vectors are checked by the ``conformance``
+tool of the Rust AI::
+
+    rust/conformance testdata/shift_vectors.txt python3 -m g2048.conformance
+"""
+import sys
+
+import numpy
+
+import g2048.logic as logic
+
+
+def shift_line(cells):
+    arr = numpy.asarray(cells, logic.GameBoard.dtype)
+    merged = [0] * len(arr)
+    for action, _, dest in logic.GameBoard.shift_array(arr):
+        if action == "merge":
+            merged[dest] = 1
+    return list(arr), merged
+
+
+def main():
+    for line in sys.stdin:
+        line = line.strip()
+        if not line:
+            continue
+        cells, merged = shift_line([int(cell) for cell in line.split()])
+        print("{} | {}".format(" ".join(map(str, cells)),
+                               " ".join(map(str, merged))))
+    sys.stdout.flush()
+
+if __name__ == "__main__":
+    main()
@@ -55,7 +55,7 @@ class GameBoard:
         return self._board_size
 
     def cleared(self):
-        return GameBoard()
+        return GameBoard(board_size=self._board_size)
 
     def free_fields(self):
         return zip(*numpy.where(self._board == 0))
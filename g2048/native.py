@@ -0,0 +1,336 @@
+"""
+ctypes bindings for the engine and the searcher of the Rust AI, as built by
+``make native`` in the ``rust`` directory.
+
+Boards are numpy arrays of tile exponents. The functions accept a single
+board of shape (height, width) or a batch of shape (count, height, width)
+and return results of the matching shape, so that many boards are handled
+in one call.
+"""
+import ctypes
+import logging
+import math
+import os
+
+import numpy
+
+import g2048.logic as logic
+
+logger = logging.getLogger(__name__)
+
+LIBRARY_ENV = "G2048_NATIVE_LIB"
+DEFAULT_LIBRARY = os.path.join(
+    os.path.dirname(os.path.dirname(os.path.abspath(__file__))),
+    "rust", "libg2048native.so")
+
+# return codes of the library, see rust/native.rs
+NATIVE_OK = 0
+NATIVE_ERRORS = {
+    -1: "invalid argument",
+    -2: "invalid board",
+    -3: "search failed",
+}
+NATIVE_INVALID_MOVE = -2**63
+# the tile exponent of blockers in observations, see rust/env.rs
+RAW_BLOCKER = -1.0
+NATIVE_ABI_VERSION = 1
+
+# directions in the order of their codes in the AI protocol
+DIRECTIONS = [
+    logic.DIR_UP,
+    logic.DIR_DOWN,
+    logic.DIR_LEFT,
+    logic.DIR_RIGHT,
+]
+
+class NativeError(Exception):
+    pass
+
+_lib = None
+
+def load(path=None):
+    """
+    Load the library from *path*, the path in the G2048_NATIVE_LIB
+    environment variable or the default build location, in that order. The
+    library is loaded once; later calls return it.
+    """
+    global _lib
+    if _lib is not None:
+        return _lib
+    path = path or os.environ.get(LIBRARY_ENV, DEFAULT_LIBRARY)
+    lib = ctypes.CDLL(path)
+    lib.g2048_abi_version.argtypes = []
+    lib.g2048_abi_version.restype = ctypes.c_uint
+    if lib.g2048_abi_version() != NATIVE_ABI_VERSION:
+        raise NativeError("{} has ABI version {}, expected {}".format(
+            path, lib.g2048_abi_version(), NATIVE_ABI_VERSION))
+
+    boards_args = [ctypes.c_uint, ctypes.c_uint, ctypes.c_uint,
+                   ctypes.c_void_p]
+    lib.g2048_shift.argtypes = boards_args + [
+        ctypes.c_uint, ctypes.c_void_p, ctypes.c_void_p]
+    lib.g2048_spawn.argtypes = boards_args + [ctypes.c_uint32]
+    lib.g2048_game_over.argtypes = boards_args + [ctypes.c_void_p]
+    lib.g2048_searcher_new.argtypes = [ctypes.c_uint, ctypes.c_uint]
+    lib.g2048_searcher_new.restype = ctypes.c_void_p
+    lib.g2048_searcher_free.argtypes = [ctypes.c_void_p]
+    lib.g2048_searcher_free.restype = None
+    lib.g2048_searcher_eval.argtypes = [ctypes.c_void_p] + boards_args + [
+        ctypes.c_void_p, ctypes.c_void_p]
+    lib.g2048_searcher_hint.argtypes = [ctypes.c_void_p] + boards_args + [
+        ctypes.c_void_p] * 4
+    lib.g2048_env_new.argtypes = [ctypes.c_uint, ctypes.c_uint,
+                                  ctypes.c_uint, ctypes.c_int]
+    lib.g2048_env_new.restype = ctypes.c_void_p
+    lib.g2048_env_configure.argtypes = [
+        ctypes.c_uint, ctypes.c_uint, ctypes.c_uint, ctypes.c_int,
+        ctypes.c_int, ctypes.POINTER(ctypes.c_char_p)]
+    lib.g2048_env_configure.restype = ctypes.c_void_p
+    lib.g2048_env_free.argtypes = [ctypes.c_void_p]
+    lib.g2048_env_free.restype = None
+    lib.g2048_env_observation_size.argtypes = [ctypes.c_void_p]
+    lib.g2048_env_observation_size.restype = ctypes.c_uint
+    lib.g2048_env_reset.argtypes = [ctypes.c_void_p, ctypes.c_uint32,
+                                    ctypes.c_void_p]
+    lib.g2048_env_step.argtypes = [ctypes.c_void_p] + [ctypes.c_void_p] * 5
+    lib.g2048_env_action_masks.argtypes = [ctypes.c_void_p, ctypes.c_void_p]
+    for func in (lib.g2048_shift, lib.g2048_spawn, lib.g2048_game_over,
+                 lib.g2048_searcher_eval, lib.g2048_searcher_hint,
+                 lib.g2048_env_reset,
+                 lib.g2048_env_step, lib.g2048_env_action_masks):
+        func.restype = ctypes.c_int
+
+    _lib = lib
+    return lib
+
+def _check(code):
+    if code != NATIVE_OK:
+        raise NativeError(NATIVE_ERRORS.get(code, "error {}".format(code)))
+
+def _batch(boards):
+    """
+    Return *boards* as a contiguous batch and whether a single board was
+    passed.
+    """
+    boards = numpy.ascontiguousarray(boards, dtype=numpy.uint8)
+    if boards.ndim == 2:
+        return boards[numpy.newaxis], True
+    if boards.ndim != 3:
+        raise ValueError("expected a board or a batch of boards")
+    return boards, False
+
+def _ptr(array):
+    return array.ctypes.data_as(ctypes.c_void_p)
+
+def _call(func, boards, *args):
+    count, height, width = boards.shape
+    _check(func(width, height, count, _ptr(boards), *args))
+
+def direction_code(direction):
+    return DIRECTIONS.index(direction)
+
+def shift(boards, direction):
+    """
+    Shift the boards towards *direction*, one of the logic.DIR_*
+    constants. Returns the shifted boards and the points gained by the
+    merges.
+    """
+    batch, single = _batch(boards)
+    out = numpy.empty_like(batch)
+    points = numpy.empty(len(batch), numpy.uint64)
+    _call(load().g2048_shift, batch, direction_code(direction),
+          _ptr(out), _ptr(points))
+    if single:
+        return out[0], int(points[0])
+    return out, points
+
+def spawn(boards, seed):
+    """
+    Return copies of the boards with a tile spawned on a random empty
+    cell, drawn from a generator seeded with *seed*.
+    """
+    batch, single = _batch(boards)
+    out = batch.copy()
+    _call(load().g2048_spawn, out, seed & 0xffffffff)
+    return out[0] if single else out
+
+def game_over(boards):
+    """
+    Whether the boards have no valid move left.
+    """
+    batch, single = _batch(boards)
+    out = numpy.empty(len(batch), numpy.uint8)
+    _call(load().g2048_game_over, batch, _ptr(out))
+    out = out.astype(bool)
+    return bool(out[0]) if single else out
+
+class Searcher:
+    """
+    The expectimax searcher of the Rust AI. With a *depth* of None, the
+    depth is picked per board like the AI does.
+    """
+
+    def __init__(self, depth=None, threads=1):
+        self._lib = load()
+        self._handle = self._lib.g2048_searcher_new(depth or 0, threads)
+        if not self._handle:
+            raise NativeError("invalid searcher parameters")
+
+    def close(self):
+        if self._handle:
+            self._lib.g2048_searcher_free(self._handle)
+            self._handle = None
+
+    def __del__(self):
+        self.close()
+
+    def evaluate(self, boards):
+        """
+        Search the boards. Returns the best move of each board (None if
+        there is none) and the scores of the four moves, in protocol order,
+        with None for moves which do not change the board.
+        """
+        batch, single = _batch(boards)
+        moves = numpy.empty(len(batch), numpy.int8)
+        scores = numpy.empty((len(batch), 4), numpy.int64)
+        _call(self._lib.g2048_searcher_eval, batch, _ptr(moves),
+              _ptr(scores))
+        results = [
+            (DIRECTIONS[move] if move >= 0 else None,
+             [int(score) if score != NATIVE_INVALID_MOVE else None
+              for score in row])
+            for move, row in zip(moves, scores)]
+        return results[0] if single else results
+
+    def hint(self, boards):
+        """
+        Advise on the boards like the hints of rust/play. Returns the
+        recommended move and the scores as evaluate does, the loss of each
+        move and the valid moves, best first. The loss is the share by which
+        a move is expected to score less than the recommended one: 0.0 for
+        the recommended move, 0.25 for a move a quarter worse, None for moves
+        which do not change the board.
+        """
+        batch, single = _batch(boards)
+        moves = numpy.empty(len(batch), numpy.int8)
+        scores = numpy.empty((len(batch), 4), numpy.int64)
+        losses = numpy.empty((len(batch), 4), numpy.float64)
+        ranking = numpy.empty((len(batch), 4), numpy.int8)
+        _call(self._lib.g2048_searcher_hint, batch, _ptr(moves),
+              _ptr(scores), _ptr(losses), _ptr(ranking))
+        results = [
+            (DIRECTIONS[move] if move >= 0 else None,
+             [int(score) if score != NATIVE_INVALID_MOVE else None
+              for score in score_row],
+             [None if math.isnan(loss) else float(loss)
+              for loss in loss_row],
+             [DIRECTIONS[code] for code in rank_row if code >= 0])
+            for move, score_row, loss_row, rank_row
+            in zip(moves, scores, losses, ranking)]
+        return results[0] if single else results
+
+class VecEnv:
+    """
+    *count* games of 2048 as environments for reinforcement learning,
+    stepped together. Actions are direction codes in protocol order (see
+    DIRECTIONS). Observations are float32 arrays of shape (count, height,
+    width) with the tile exponents, or (count, planes, height, width) with
+    one-hot planes of the exponents 0 to the largest tile if *one_hot* is
+    set.
+
+    The games follow the rules of the original game, or those given by the
+    game variant options of the Rust AI in *options*, e.g.
+    ``["--blocker", "1,1"]``. Blockers are RAW_BLOCKER in the tile
+    exponents, and have one more plane after those of the tiles in the
+    one-hot encoding.
+
+    Games which end are started over right away: step reports the end and
+    the final score, but returns the observation of the new game.
+    """
+
+    def __init__(self, count, board_size=4, one_hot=False, options=None):
+        self._lib = load()
+        if options is None:
+            self._handle = self._lib.g2048_env_new(
+                count, board_size, board_size, 1 if one_hot else 0)
+        else:
+            argv = (ctypes.c_char_p * len(options))(
+                *[option.encode() for option in options])
+            self._handle = self._lib.g2048_env_configure(
+                count, board_size, board_size, 1 if one_hot else 0,
+                len(options), argv)
+        if not self._handle:
+            raise NativeError("invalid environment parameters")
+        self.count = count
+        size = self._lib.g2048_env_observation_size(self._handle)
+        cells = board_size * board_size
+        if one_hot:
+            self.observation_shape = (size // cells, board_size, board_size)
+        else:
+            self.observation_shape = (board_size, board_size)
+
+    def close(self):
+        if self._handle:
+            self._lib.g2048_env_free(self._handle)
+            self._handle = None
+
+    def __del__(self):
+        self.close()
+
+    def _observations(self):
+        return numpy.empty((self.count,) + self.observation_shape,
+                           numpy.float32)
+
+    def reset(self, seed):
+        """
+        Start new games, the i-th with *seed* + i, and return their
+        observations.
+        """
+        obs = self._observations()
+        _check(self._lib.g2048_env_reset(self._handle, seed & 0xffffffff,
+                                         _ptr(obs)))
+        return obs
+
+    def step(self, actions):
+        """
+        Play one action per game. Returns the observations, the rewards
+        (the points gained), the done flags and a list with an info dict per
+        game.
+        """
+        actions = numpy.ascontiguousarray(actions, numpy.uint8)
+        if actions.shape != (self.count,):
+            raise ValueError("expected {} actions".format(self.count))
+        obs = self._observations()
+        rewards = numpy.empty(self.count, numpy.float64)
+        dones = numpy.empty(self.count, numpy.uint8)
+        scores = numpy.empty(self.count, numpy.uint64)
+        _check(self._lib.g2048_env_step(
+            self._handle, _ptr(actions), _ptr(obs), _ptr(rewards),
+            _ptr(dones), _ptr(scores)))
+        infos = [{"score": int(score)} for score in scores]
+        return obs, rewards, dones.astype(bool), infos
+
+    def action_masks(self):
+        """
+        A boolean array of shape (count, 4), true for the actions which
+        change the board.
+        """
+        masks = numpy.empty((self.count, 4), numpy.uint8)
+        _check(self._lib.g2048_env_action_masks(self._handle, _ptr(masks)))
+        return masks.astype(bool)
+
+class NativeAI:
+    """
+    Plays with the Rust searcher in-process, as a replacement for
+    SubprocessAI.
+    """
+
+    def __init__(self, depth=None, threads=1):
+        self._searcher = Searcher(depth=depth, threads=threads)
+
+    def actuate(self, game):
+        move, scores = self._searcher.evaluate(game.board.board)
+        logger.info("native search: move=%s scores=%s", move, scores)
+        if move is None:
+            logger.info("no valid move left")
+        return move
@@ -6,22 +6,54 @@ import g2048.logic as logic
 
 logger = logging.getLogger(__name__)
 
+# extension byte announcing a board which is not 4x4, see the README
+EXT_SIZED_BOARD = 1
+
+# exit statuses of AIs speaking the pipe protocol, see the README
+EXIT_RESIGNED = 1
+EXIT_STATUS_MESSAGES = {
+    0: "closed after end of input",
+    EXIT_RESIGNED: "resigned, no valid move left",
+    2: "protocol error",
+    3: "I/O error",
+    4: "configuration error",
+    5: "search failure",
+}
+
 class SubprocessAI:
     def __init__(self, cmd):
         super().__init__()
-        self._send_struct = struct.Struct("B"*17)
         self._process = subprocess.Popen(
             cmd,
             shell=False,
             stdin=subprocess.PIPE,
             stdout=subprocess.PIPE)
 
+    def _log_exit(self, returncode):
+        try:
+            message = EXIT_STATUS_MESSAGES[returncode]
+        except KeyError:
+            logger.error("AI process crashed. exit code=%d", returncode)
+            return
+        if returncode == EXIT_RESIGNED:
+            logger.info("AI process has terminated: %s", message)
+        else:
+            logger.error("AI process has terminated: %s (exit code=%d)",
+                         message, returncode)
+
+    @staticmethod
+    def _encode_request(board):
+        cells = [int(cell) for cell in board.flatten()]
+        height, width = board.shape
+        if (width, height) == (4, 4):
+            return cells + [0]
+        return [0]*16 + [EXT_SIZED_BOARD, width, height] + cells
+
     def actuate(self, game):
         if not self._process:
             return
-        board_and_state = list(game.board.board.flatten())
-        board_and_state.append(0)
-        buf = self._send_struct.pack(*board_and_state)
+        board_and_state = self._encode_request(game.board.board)
+        buf = struct.pack("B"*len(board_and_state), *board_and_state)
         logger.info("sending board to child AI (status=%s, state=%s, buffer=%s)",
                     self._process.poll(),
                     board_and_state,
@@ -30,8 +62,7 @@ class SubprocessAI:
         self._process.stdin.flush()
         read = self._process.stdout.read(1)
         if not read:
-            logger.warn("AI process has terminated. exit code=%d",
-                        self._process.returncode)
+            self._log_exit(self._process.wait())
             self._process = None
             return
 
@@ -0,0 +1,83 @@
+"""
+Serve the Python AI over the pipe protocol on stdin/stdout, so that it can be
+played by the hosts of the Rust AI like any other AI::
+
+    rust/host -- python3 -m g2048.pipe_server
+
+Exits with the statuses the Rust AI uses, see the README.
+"""
+import logging
+import sys
+
+import numpy
+
+import g2048.ai as ai
+import g2048.logic as logic
+import g2048.pipe_ai as pipe_ai
+
+logger = logging.getLogger(__name__)
+
+EXIT_INPUT_CLOSED = 0
+EXIT_PROTOCOL = 2
+
+# directions in the order of their codes in the protocol
+DIRECTIONS = [
+    logic.DIR_UP,
+    logic.DIR_DOWN,
+    logic.DIR_LEFT,
+    logic.DIR_RIGHT,
+]
+
+class ProtocolError(Exception):
+    pass
+
+def _read(stream, count):
+    data = stream.read(count)
+    if len(data) < count:
+        raise ProtocolError("input ended in the middle of a request")
+    return data
+
+def read_request(stream):
+    """
+    Read one request and return the board, or None if the input ended
+    before it.
+    """
+    request = stream.read(17)
+    if not request:
+        return None
+    if len(request) < 17:
+        raise ProtocolError("input ended in the middle of a request")
+    extension = request[16]
+    if extension == 0:
+        width, height, cells = 4, 4, request[:16]
+    elif extension == pipe_ai.EXT_SIZED_BOARD:
+        width, height = _read(stream, 2)
+        cells = _read(stream, width * height)
+    else:
+        raise ProtocolError("unsupported extension byte {}".format(extension))
+    if width != height:
+        raise ProtocolError("the Python AI only plays square boards")
+    board = numpy.frombuffer(cells, logic.GameBoard.dtype)
+    return logic.GameBoard(board.reshape((height, width)))
+
+def main():
+    logging.basicConfig(level=logging.WARNING, stream=sys.stderr)
+    player = ai.AI()
+    stdin = sys.stdin.buffer
+    stdout = sys.stdout.buffer
+    while True:
+        try:
+            board = read_request(stdin)
+        except ProtocolError as exc:
+            logger.error("%s", exc)
+            return EXIT_PROTOCOL
+        if board is None:
+            return EXIT_INPUT_CLOSED
+        _, move, _ = player.analyze(board)
+        if move is None:
+            return pipe_ai.EXIT_RESIGNED
+        stdout.write(bytes([DIRECTIONS.index(move)]))
+        stdout.flush()
+
+if __name__ == "__main__":
+    sys.exit(main())
@@ -0,0 +1,119 @@
+import os
+import unittest
+
+import numpy
+import numpy.testing
+
+import g2048.logic as logic
+import g2048.native as native
+
+def _library_available():
+    path = os.environ.get(native.LIBRARY_ENV, native.DEFAULT_LIBRARY)
+    return os.path.isfile(path)
+
+@unittest.skipUnless(_library_available(),
+                     "native library not built, run make native in rust/")
+class TestNative(unittest.TestCase):
+    def test_shift_matches_python_engine(self):
+        boards = numpy.asarray(
+            [
+                [[1, 1, 0, 2],
+                 [0, 2, 2, 2],
+                 [3, 0, 3, 1],
+                 [1, 1, 1, 1]],
+                [[0, 0, 0, 0],
+                 [0, 1, 0, 0],
+                 [0, 0, 0, 0],
+                 [2, 0, 0, 2]],
+            ], numpy.uint8)
+        for direction in native.DIRECTIONS:
+            shifted, points = native.shift(boards, direction)
+            self.assertEqual(len(points), 2)
+            for board, result in zip(boards, shifted):
+                expected = logic.GameBoard(board).shifted(direction)
+                numpy.testing.assert_array_equal(expected.board, result)
+
+        shifted, points = native.shift(boards[1], logic.DIR_LEFT)
+        self.assertEqual(points, 8)
+        self.assertEqual(shifted[3, 0], 3)
+
+    def test_spawn_and_game_over(self):
+        boards = numpy.zeros((3, 4, 4), numpy.uint8)
+        spawned = native.spawn(boards, 42)
+        self.assertTrue((numpy.count_nonzero(spawned, axis=(1, 2)) == 1).all())
+        numpy.testing.assert_array_equal(spawned, native.spawn(boards, 42))
+        self.assertFalse(boards.any())
+
+        full = numpy.asarray([[1, 2, 1], [2, 1, 2], [1, 2, 1]], numpy.uint8)
+        self.assertTrue(native.game_over(full))
+        numpy.testing.assert_array_equal(
+            native.game_over(numpy.stack([full, numpy.zeros_like(full)])),
+            [True, False])
+
+    def test_invalid_board(self):
+        with self.assertRaises(native.NativeError):
+            native.game_over(numpy.full((3, 3), 11, numpy.uint8))
+        with self.assertRaises(native.NativeError):
+            native.game_over(numpy.zeros((9, 9), numpy.uint8))
+
+    def test_searcher(self):
+        searcher = native.Searcher(depth=2)
+        board = numpy.asarray(
+            [[1, 0, 0, 0],
+             [1, 0, 0, 0],
+             [0, 0, 0, 0],
+             [0, 0, 0, 0]], numpy.uint8)
+        move, scores = searcher.evaluate(board)
+        self.assertIn(move, native.DIRECTIONS)
+        self.assertIsNone(scores[native.direction_code(logic.DIR_LEFT)])
+        results = searcher.evaluate(numpy.stack([board, board]))
+        self.assertEqual(results[0], results[1])
+
+        move, scores, losses, ranking = searcher.hint(board)
+        self.assertEqual((move, scores), searcher.evaluate(board))
+        self.assertEqual(losses[native.direction_code(move)], 0.0)
+        self.assertIsNone(losses[native.direction_code(logic.DIR_LEFT)])
+        self.assertEqual(ranking[0], move)
+        self.assertNotIn(logic.DIR_LEFT, ranking)
+        ranked_losses = [losses[native.direction_code(d)] for d in ranking]
+        self.assertEqual(ranked_losses, sorted(ranked_losses))
+        full = numpy.asarray(
+            [[1, 2, 1, 2],
+             [2, 1, 2, 1],
+             [1, 2, 1, 2],
+             [2, 1, 2, 1]], numpy.uint8)
+        results = searcher.hint(numpy.stack([board, full]))
+        self.assertEqual(results[1], (None, [None] * 4, [None] * 4, []))
+        searcher.close()
+
+    def test_vec_env(self):
+        envs = native.VecEnv(4, board_size=3, one_hot=True)
+        obs = envs.reset(7)
+        self.assertEqual(obs.shape, (4, 11, 3, 3))
+        numpy.testing.assert_array_equal(obs.sum(axis=1), 1)
+        numpy.testing.assert_array_equal(obs, envs.reset(7))
+
+        finished = 0
+        for _ in range(500):
+            masks = envs.action_masks()
+            actions = masks.argmax(axis=1)
+            obs, rewards, dones, infos = envs.step(actions)
+            self.assertTrue((rewards >= 0).all())
+            finished += dones.sum()
+        self.assertGreater(finished, 0)
+        with self.assertRaises(ValueError):
+            envs.step([0])
+
+    def test_vec_env_blockers(self):
+        envs = native.VecEnv(2, board_size=3, options=["--blocker", "1,1"])
+        obs = envs.reset(3)
+        self.assertEqual(obs.shape, (2, 3, 3))
+        self.assertTrue((obs[:, 1, 1] == native.RAW_BLOCKER).all())
+        envs = native.VecEnv(2, board_size=3, one_hot=True,
+                             options=["--blocker", "1,1"])
+        obs = envs.reset(3)
+        self.assertEqual(obs.shape, (2, 12, 3, 3))
+        numpy.testing.assert_array_equal(obs.sum(axis=1), 1)
+        self.assertTrue((obs[:, 11, 1, 1] == 1).all())
+        with self.assertRaises(native.NativeError):
+            native.VecEnv(2, board_size=3, options=["--blocker", "3,3"])
@@ -114,7 +114,7 @@ class UrwidFrontend:
 
     def __init__(self, game):
         self._game = game
-        self._board = Board()
+        self._board = Board(board_size=game.board.board_size)
         self._score_text = urwid.BigText(
             self.get_score_text(0),
             urwid.font.HalfBlock5x4Font())
@@ -0,0 +1,177 @@
+use std::fmt;
+
+use board::{Board, Direction};
+use config::Config;
+use error::{AiError, ConfigFailure, SearchFailure};
+use hint::Hint;
+use record::GameRecord;
+
+/// How bad a move is, by how much less it is expected to score than
+/// the best one.
+#[deriving(Eq, Ord, Clone, Show)]
+pub enum Severity {
+    Inaccuracy,
+    Mistake,
+    Blunder
+}
+
+impl Severity {
+    /// The annotation of chess notation for moves of this severity.
+    pub fn symbol(&self) -> &'static str {
+        match *self {
+            Inaccuracy => "?!",
+            Mistake => "?",
+            Blunder => "??"
+        }
+    }
+}
+
+/// The losses, as shares of the score of the best move, from which on
+/// moves count as inaccuracies, mistakes and blunders.
+#[deriving(Clone)]
+pub struct Thresholds {
+    pub inaccuracy: f64,
+    pub mistake: f64,
+    pub blunder: f64
+}
+
+impl Thresholds {
+    pub fn default() -> Thresholds {
+        Thresholds { inaccuracy: 0.05, mistake: 0.15, blunder: 0.3 }
+    }
+
+    /// The severity of a move with *loss*, None for good moves.
+    pub fn classify(&self, loss: f64) -> Option<Severity> {
+        if loss >= self.blunder {
+            Some(Blunder)
+        } else if loss >= self.mistake {
+            Some(Mistake)
+        } else if loss >= self.inaccuracy {
+            Some(Inaccuracy)
+        } else {
+            None
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), AiError> {
+        if !(0.0 < self.inaccuracy && self.inaccuracy <= self.mistake &&
+             self.mistake <= self.blunder) {
+            return Err(ConfigFailure(format!(
+                "the thresholds must grow from inaccuracy over mistake to blunder")));
+        }
+        Ok(())
+    }
+}
+
+/// The verdict on one move of a game.
+pub struct MoveAnalysis {
+    /// The number of the move, counted from one.
+    pub number: uint,
+    /// The board the move was played on.
+    pub board: Board,
+    pub played: Direction,
+    /// The advice of the searcher on the board.
+    pub hint: Hint,
+    /// How much less the move is expected to score than the best one,
+    /// see Hint::loss.
+    pub loss: f64,
+    pub severity: Option<Severity>
+}
+
+impl fmt::Show for MoveAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f.buf, "move {} {}", self.number, self.played));
+        match self.severity {
+            Some(severity) => try!(write!(f.buf, "{} {}", severity.symbol(), severity)),
+            None => ()
+        }
+        match self.hint.best {
+            Some(best) if best as uint != self.played as uint =>
+                try!(write!(f.buf, ", loss {:.1f}%, best {}", self.loss * 100.0, best)),
+            _ => ()
+        }
+        Ok(())
+    }
+}
+
+/// Replay *record* under the rules of *config* and judge every move
+/// by the searcher of *config*. Fails like GameRecord::replay if a
+/// move is invalid under these rules.
+pub fn analyze(record: &GameRecord, config: &Config,
+               thresholds: &Thresholds) -> Result<Vec<MoveAnalysis>, AiError> {
+    let mut ctx = config.context();
+    let mut game = try!(config.new_game(record.board.width(), record.board.height(),
+                                        record.seed));
+    let mut analysis = Vec::with_capacity(record.moves.len());
+    for (i, dir) in record.moves.iter().enumerate() {
+        let board = game.board().clone();
+        let hint = try!(Hint::search(&mut ctx, &config.policy, &board).map_err(
+            |e| SearchFailure(e)));
+        let loss = match hint.loss(*dir) {
+            Some(loss) if game.play(*dir).is_some() => loss,
+            _ => return Err(ConfigFailure(format!(
+                "move {} of the record is invalid, was it played under \
+                 other rules?", i + 1)))
+        };
+        analysis.push(MoveAnalysis { number: i + 1,
+                                     board: board,
+                                     played: *dir,
+                                     hint: hint,
+                                     loss: loss,
+                                     severity: thresholds.classify(loss) });
+    }
+    Ok(analysis)
+}
+
+#[cfg(test)]
+mod test {
+    use board::{Up, Down, Left, Right};
+    use config::Config;
+    use record::GameRecord;
+    use super::{analyze, Thresholds, Inaccuracy, Mistake, Blunder};
+
+    #[test]
+    fn test_classify() {
+        let thresholds = Thresholds::default();
+        assert!(thresholds.classify(0.0).is_none());
+        assert!(thresholds.classify(0.1) == Some(Inaccuracy));
+        assert!(thresholds.classify(0.2) == Some(Mistake));
+        assert!(thresholds.classify(0.5) == Some(Blunder));
+        assert!(Blunder > Inaccuracy);
+        assert!(thresholds.validate().is_ok());
+        let mut reversed = thresholds.clone();
+        reversed.blunder = 0.1;
+        assert!(reversed.validate().is_err());
+    }
+
+    #[test]
+    fn test_analyze() {
+        let mut config = Config::default();
+        config.policy.min_depth = 1;
+        config.policy.default_depth = 2;
+        config.policy.max_depth = 2;
+        config.threads = 1;
+        let mut game = config.new_game(4, 4, 11).ok().unwrap();
+        let mut record = GameRecord::new("test", 11, &game);
+        let mut i = 0u;
+        while record.moves.len() < 10 {
+            let dir = [Up, Left, Down, Right][i % 4];
+            if game.play(dir).is_some() {
+                record.push(dir, 0, &game);
+            } else {
+                i += 1;
+            }
+        }
+        let analysis = analyze(&record, &config, &Thresholds::default()).ok().unwrap();
+        assert_eq!(analysis.len(), 10);
+        for (i, verdict) in analysis.iter().enumerate() {
+            assert_eq!(verdict.number, i + 1);
+            assert!(verdict.loss >= 0.0);
+            match verdict.hint.best {
+                Some(best) if best as uint == verdict.played as uint =>
+                    assert!(verdict.severity.is_none()),
+                _ => ()
+            }
+        }
+    }
+}
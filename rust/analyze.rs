@@ -0,0 +1,148 @@
+extern crate getopts;
+extern crate g2048;
+
+use std::io;
+use std::os;
+
+use g2048::analysis::{analyze, Thresholds, Inaccuracy, Mistake, Blunder};
+use g2048::board::{Up, Down, Left, Right};
+use g2048::config;
+use g2048::config::Config;
+use g2048::error::{AiError, IoFailure, ConfigFailure, EXIT_CONFIG};
+use g2048::record::GameRecord;
+
+fn options() -> Vec<getopts::OptGroup> {
+    let mut opts = config::options();
+    opts.push(getopts::optopt(
+        "", "game", "only analyze the game on line N of each file", "N"));
+    opts.push(getopts::optopt(
+        "", "inaccuracy", "loss from which on a move is an inaccuracy (5)", "PERCENT"));
+    opts.push(getopts::optopt(
+        "", "mistake", "loss from which on a move is a mistake (15)", "PERCENT"));
+    opts.push(getopts::optopt(
+        "", "blunder", "loss from which on a move is a blunder (30)", "PERCENT"));
+    opts.push(getopts::optflag(
+        "", "all", "list every move, not only the bad ones"));
+    opts.push(getopts::optflag(
+        "", "boards", "show the board before each listed move"));
+    opts
+}
+
+/// Read the records of *path*, - for stdin, with their line numbers.
+fn read_records(path: &str) -> Result<Vec<(uint, GameRecord)>, AiError> {
+    let data = if path == "-" {
+        io::stdin().read_to_str()
+    } else {
+        io::File::open(&Path::new(path)).and_then(|mut file| file.read_to_str())
+    };
+    let data = try!(data.map_err(|e| IoFailure(e)));
+    let mut records = Vec::new();
+    for (i, line) in data.as_slice().lines().enumerate() {
+        if line.trim().len() == 0 {
+            continue;
+        }
+        let record = try!(GameRecord::from_json(line).map_err(
+            |e| ConfigFailure(format!("{}, line {}: {}", path, i + 1, e))));
+        records.push((i + 1, record));
+    }
+    Ok(records)
+}
+
+fn print_report(name: &str, record: &GameRecord, config: &Config,
+                thresholds: &Thresholds, all: bool, boards: bool) -> Result<(), AiError> {
+    println!("{}: ai={} seed={} moves={} score={} max_tile={} end={}",
+             name, record.ai, record.seed, record.moves.len(), record.score,
+             1u64 << record.max_tile() as uint, record.end.name());
+    let analysis = try!(analyze(record, config, thresholds));
+    let mut counts = [0u, ..3];
+    let mut total_loss = 0.0;
+    for verdict in analysis.iter() {
+        total_loss += verdict.loss;
+        match verdict.severity {
+            Some(Inaccuracy) => counts[0] += 1,
+            Some(Mistake) => counts[1] += 1,
+            Some(Blunder) => counts[2] += 1,
+            None if !all => continue,
+            None => ()
+        }
+        println!("  {}", verdict);
+        if boards {
+            for row in format!("{}", verdict.board).as_slice().lines() {
+                println!("      {}", row);
+            }
+        }
+        let scores = [Up, Down, Left, Right].iter().map(|dir| {
+            match verdict.hint.score(*dir) {
+                Some(score) => format!("{}={}", *dir, score),
+                None => format!("{}=-", *dir)
+            }
+        }).collect::<Vec<String>>();
+        println!("    depth={} {}", verdict.hint.depth, scores.connect(" "));
+    }
+    println!("  inaccuracies={} mistakes={} blunders={} mean_loss={:.2f}%",
+             counts[0], counts[1], counts[2],
+             total_loss / (if analysis.len() > 0 { analysis.len() } else { 1 }) as f64 * 100.0);
+    Ok(())
+}
+
+fn run(args: &[String]) -> Result<(), AiError> {
+    let opts = options();
+    let matches = try!(getopts::getopts(args, opts.as_slice()).map_err(
+        |e| ConfigFailure(e.to_err_msg())));
+    if matches.opt_present("help") {
+        println!("{}", getopts::usage(
+            "Usage: analyze [OPTIONS] RECORDS...\n\n\
+             Judge every move of recorded games, as written by --record of host, \
+             tournament\nand play, with the searcher configured by the search options; \
+             - reads from stdin.\nThe game variant options must match the rules the \
+             games were played with.",
+            opts.as_slice()));
+        return Ok(());
+    }
+    if matches.free.len() == 0 {
+        return Err(ConfigFailure(format!("no record file given")));
+    }
+    let mut config = Config::default();
+    try!(config.update(&matches));
+    let defaults = Thresholds::default();
+    let thresholds = Thresholds {
+        inaccuracy: try!(config::parse_or(&matches, "inaccuracy",
+                                          defaults.inaccuracy * 100.0)) / 100.0,
+        mistake: try!(config::parse_or(&matches, "mistake",
+                                       defaults.mistake * 100.0)) / 100.0,
+        blunder: try!(config::parse_or(&matches, "blunder",
+                                       defaults.blunder * 100.0)) / 100.0
+    };
+    try!(thresholds.validate());
+    let only = match matches.opt_str("game") {
+        Some(_) => Some(try!(config::parse_or(&matches, "game", 0u))),
+        None => None
+    };
+
+    for path in matches.free.iter() {
+        let records = try!(read_records(path.as_slice()));
+        for &(line, ref record) in records.iter() {
+            if only.map_or(false, |n| n != line) {
+                continue;
+            }
+            let name = format!("{}:{}", path, line);
+            try!(print_report(name.as_slice(), record, &config, &thresholds,
+                              matches.opt_present("all"), matches.opt_present("boards")));
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = os::args();
+    match run(args.tail()) {
+        Ok(()) => (),
+        Err(e) => {
+            let _ = writeln!(&mut io::stderr(), "analyze: {}", e);
+            if e.exit_code() == EXIT_CONFIG {
+                let _ = writeln!(&mut io::stderr(), "analyze: try --help");
+            }
+            os::set_exit_status(e.exit_code());
+        }
+    }
+}
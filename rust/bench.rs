@@ -0,0 +1,121 @@
+extern crate g2048;
+extern crate test;
+
+use test::Bencher;
+
+use g2048::board::{Board, Line, Left, Up, shift_line};
+use g2048::search::EvalContext;
+
+// The boards every benchmark runs on. They are picked to cover the
+// phases of a game, as the cost of the search depends mostly on the
+// number of empty cells.
+
+fn opening() -> Board {
+    Board::from_raw(&vec![0u8, 0, 0, 0,
+                          0, 1, 0, 0,
+                          0, 0, 0, 0,
+                          0, 0, 1, 0]).unwrap()
+}
+
+fn midgame() -> Board {
+    Board::from_raw(&vec![7u8, 6, 4, 2,
+                          3, 4, 2, 0,
+                          1, 2, 0, 0,
+                          1, 0, 0, 0]).unwrap()
+}
+
+fn endgame() -> Board {
+    Board::from_raw(&vec![10u8, 9, 8, 7,
+                          3, 4, 5, 6,
+                          2, 3, 1, 2,
+                          1, 0, 2, 1]).unwrap()
+}
+
+/// Search *board* to *depth* on the calling thread. The search is
+/// deterministic, so each iteration expands the same nodes; their
+/// number is passed as the byte count, so that the throughput column
+/// (MB/s) reads as millions of nodes per second.
+fn bench_eval(b: &mut Bencher, board: Board, depth: uint) {
+    let ctx = EvalContext::new(depth, 1.0, 0.6, 2);
+    b.bytes = ctx.eval(&board).unwrap().stats.nodes() as u64;
+    b.iter(|| ctx.eval(&board).unwrap());
+}
+
+#[bench]
+fn shift_line_mixed(b: &mut Bencher) {
+    let lines: [Line, ..8] = [[0, 0, 0, 0], [1, 1, 1, 1], [1, 0, 0, 1],
+                              [2, 1, 1, 0], [0, 3, 3, 3], [1, 2, 3, 4],
+                              [4, 0, 4, 0], [0, 0, 0, 5]];
+    b.bytes = lines.len() as u64;
+    b.iter(|| {
+        let mut score = 0;
+        for line in lines.iter() {
+            let mut copy = *line;
+            score += shift_line(&mut copy);
+        }
+        score
+    });
+}
+
+#[bench]
+fn shifted_board_opening(b: &mut Bencher) {
+    let board = opening();
+    b.iter(|| board.shifted_board(Left));
+}
+
+#[bench]
+fn shifted_board_midgame(b: &mut Bencher) {
+    let board = midgame();
+    b.iter(|| board.shifted_board(Up));
+}
+
+#[bench]
+fn shifted_board_endgame(b: &mut Bencher) {
+    let board = endgame();
+    b.iter(|| board.shifted_board(Left));
+}
+
+#[bench]
+fn gradient_score_opening(b: &mut Bencher) {
+    let board = opening();
+    b.iter(|| board.gradient_score());
+}
+
+#[bench]
+fn gradient_score_midgame(b: &mut Bencher) {
+    let board = midgame();
+    b.iter(|| board.gradient_score());
+}
+
+#[bench]
+fn gradient_score_endgame(b: &mut Bencher) {
+    let board = endgame();
+    b.iter(|| board.gradient_score());
+}
+
+#[bench]
+fn eval_opening_depth_1(b: &mut Bencher) { bench_eval(b, opening(), 1) }
+#[bench]
+fn eval_opening_depth_2(b: &mut Bencher) { bench_eval(b, opening(), 2) }
+#[bench]
+fn eval_opening_depth_3(b: &mut Bencher) { bench_eval(b, opening(), 3) }
+#[bench]
+fn eval_opening_depth_4(b: &mut Bencher) { bench_eval(b, opening(), 4) }
+
+#[bench]
+fn eval_midgame_depth_1(b: &mut Bencher) { bench_eval(b, midgame(), 1) }
+#[bench]
+fn eval_midgame_depth_2(b: &mut Bencher) { bench_eval(b, midgame(), 2) }
+#[bench]
+fn eval_midgame_depth_3(b: &mut Bencher) { bench_eval(b, midgame(), 3) }
+#[bench]
+fn eval_midgame_depth_4(b: &mut Bencher) { bench_eval(b, midgame(), 4) }
+
+#[bench]
+fn eval_endgame_depth_1(b: &mut Bencher) { bench_eval(b, endgame(), 1) }
+#[bench]
+fn eval_endgame_depth_2(b: &mut Bencher) { bench_eval(b, endgame(), 2) }
+#[bench]
+fn eval_endgame_depth_3(b: &mut Bencher) { bench_eval(b, endgame(), 3) }
+#[bench]
+fn eval_endgame_depth_4(b: &mut Bencher) { bench_eval(b, endgame(), 4) }
@@ -0,0 +1,1199 @@
+use std::cmp;
+use std::fmt;
+use std::num;
+
+pub type Score = i64;
+pub type LineView<'r> = [&'r mut u8, ..4];
+pub type Line = [u8, ..4];
+
+pub static SCORE_MERGE_FACTOR: f32 = 1.2f32;
+
+/// The range of supported widths and heights.
+pub static MIN_SIZE: uint = 3;
+pub static MAX_SIZE: uint = 8;
+pub static MAX_CELLS: uint = MAX_SIZE * MAX_SIZE;
+
+/// The largest tile exponent accepted on any board. Tile values and
+/// the scores built from them stay far below the range of Score up to
+/// here, and the bound of the alpha-beta window of the search is
+/// derived from it.
+pub static MAX_EXPONENT: u8 = 40;
+
+/// The largest tile exponent which can be built on a board of the
+/// given size: each cell can hold one tile of a chain 2^n, 2^(n-1),
+/// ..., and the spawn of a 4 adds one to the end. Capped at
+/// MAX_EXPONENT for the large boards.
+pub fn max_tile(width: uint, height: uint) -> u8 {
+    cmp::min(width * height + 1, MAX_EXPONENT as uint) as u8
+}
+
+#[deriving(Show, Clone)]
+pub enum Direction {
+    Up = 0,
+    Down = 1,
+    Left = 2,
+    Right = 3
+}
+
+/// A 2048 game board, consisting of width times height squares.
+/// Each slot in the array holds the log2 of the actual value of the
+/// square. Slots outside of the board are always zero.
+pub struct Board {
+    cols: [[u8, ..MAX_SIZE], ..MAX_SIZE],
+    width: uint,
+    height: uint,
+    /// Bit ``y * MAX_SIZE + x`` is set for each blocker cell. Blockers
+    /// never hold a tile, and tiles do not move across them.
+    blockers: u64
+}
+
+/// Reasons for rejecting raw board data.
+#[deriving(Eq, Clone)]
+pub enum BoardError {
+    /// The data did not hold exactly one byte per cell; carries the
+    /// expected and the found number of bytes.
+    WrongSize(uint, uint),
+    /// The cell at the given index (in the raw, row-major order)
+    /// holds an exponent above max_tile for the board size.
+    TileOutOfRange(uint, u8),
+    /// Width or height are outside of MIN_SIZE to MAX_SIZE.
+    UnsupportedSize(uint, uint)
+}
+
+impl fmt::Show for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WrongSize(expected, len) =>
+                write!(f.buf, "expected {} cells, got {}", expected, len),
+            TileOutOfRange(idx, tile) =>
+                write!(f.buf, "tile exponent {} at cell {} is too large \
+                               for the board", tile, idx),
+            UnsupportedSize(width, height) =>
+                write!(f.buf, "unsupported board size {}x{}, sizes must be \
+                               between {} and {}",
+                       width, height, MIN_SIZE, MAX_SIZE)
+        }
+    }
+}
+
+/// Check that a board of the given size is supported.
+pub fn check_size(width: uint, height: uint) -> Result<(), BoardError> {
+    if width < MIN_SIZE || width > MAX_SIZE ||
+        height < MIN_SIZE || height > MAX_SIZE
+    {
+        return Err(UnsupportedSize(width, height));
+    }
+    Ok(())
+}
+
+/// Shift a 2048 line (either horizontal or vertical) towards lower
+/// indicies. Takes a mutable line and returns the merging score
+/// obtained by this move.
+///
+/// Goes through the Line from low to high indicies. On each slot, it
+/// is checked whether the previous slot has the same number as the
+/// current one. If so, the current one is merged with the previous
+/// slot and the current slot is set to zero.
+///
+/// Then, if the current slot is zero, the subsequent slots are
+/// searched for non-zero slots. The first non-zero slot encountered
+/// will be shifted into the current position. If no slots were
+/// shifted or a merge has taken place, the next iteration will deal
+/// with the next slot, otherwise the same slot is inspected again.
+///
+/// Each merge scores with the resulting number times the
+/// SCORE_MERGE_FACTOR.
+pub fn shift_line(line: &mut [u8]) -> Score {
+    let mut merged = [false, ..MAX_SIZE];
+    let len = line.len();
+    shift_line_tracked(line, merged.mut_slice_to(len))
+}
+
+/// Shift a line exactly like shift_line does and additionally flag
+/// each slot which received a merge in *merged*. Slots which did not
+/// receive a merge are left untouched.
+pub fn shift_line_tracked(line: &mut [u8], merged: &mut [bool]) -> Score {
+    let mut result: Score = 0;
+    let mut i = 0;
+    while i < line.len() {
+        let mut did_merge = false;
+        if line[i] != 0 &&
+           i > 0 &&
+           line[i-1] == line[i]
+        {
+            line[i-1] += 1;
+            line[i] = 0;
+            did_merge = true;
+            merged[i-1] = true;
+            result += line[i-1] as Score;
+        }
+        if line[i] == 0 {
+            let mut shifted = false;
+            let mut j = i+1;
+            while j < line.len() {
+                if line[j] != 0 {
+                    line[i] = line[j];
+                    line[j] = 0;
+                    shifted = true;
+                    break;
+                }
+                j += 1;
+            }
+            if !shifted || did_merge {
+                i += 1;
+            }
+        } else {
+            i += 1;
+        }
+    }
+
+    (result as f32 * SCORE_MERGE_FACTOR).round() as Score
+}
+
+/// Shift a line exactly like shift_line does and additionally trace
+/// where the tiles come from: afterwards, bit k of ``origins[i]`` is
+/// set if the tile in slot i was built from the tile in slot k of the
+/// original line. Slow compared to shift_line, so meant for display.
+pub fn shift_line_traced(line: &mut [u8], origins: &mut [u16]) {
+    for i in range(0u, line.len()) {
+        origins[i] = if line[i] != 0 { 1 << i } else { 0 };
+    }
+    let mut i = 0;
+    while i < line.len() {
+        let mut did_merge = false;
+        if line[i] != 0 &&
+           i > 0 &&
+           line[i-1] == line[i]
+        {
+            line[i-1] += 1;
+            line[i] = 0;
+            origins[i-1] |= origins[i];
+            origins[i] = 0;
+            did_merge = true;
+        }
+        if line[i] == 0 {
+            let mut shifted = false;
+            let mut j = i+1;
+            while j < line.len() {
+                if line[j] != 0 {
+                    line[i] = line[j];
+                    line[j] = 0;
+                    origins[i] = origins[j];
+                    origins[j] = 0;
+                    shifted = true;
+                    break;
+                }
+                j += 1;
+            }
+            if !shifted || did_merge {
+                i += 1;
+            }
+        } else {
+            i += 1;
+        }
+    }
+}
+
+/// Where a tile goes when the board is shifted.
+#[deriving(Clone, Show)]
+pub struct Motion {
+    pub from: (uint, uint),
+    pub to: (uint, uint),
+    /// The tile exponent before the shift.
+    pub tile: u8,
+    /// Whether the tile merges with another one at *to*.
+    pub merged: bool
+}
+
+/// Implementation of the 2048 board
+impl Board {
+
+    /// Create an empty board of the given size.
+    pub fn new(width: uint, height: uint) -> Board {
+        assert!(width >= MIN_SIZE && width <= MAX_SIZE);
+        assert!(height >= MIN_SIZE && height <= MAX_SIZE);
+        Board { cols: [[0, ..MAX_SIZE], ..MAX_SIZE],
+                width: width,
+                height: height,
+                blockers: 0 }
+    }
+
+    /// Create a 4x4 board from a vector of bytes. Each byte is taken
+    /// as the literal value. The bytes are supposed to be in
+    /// columns-first order (that is, x increments before y
+    /// increments).
+    ///
+    /// The data is validated, as it usually comes from the outside:
+    /// it must hold exactly 16 cells, none of them above max_tile(4, 4).
+    pub fn from_raw(src: &Vec<u8>) -> Result<Board, BoardError> {
+        Board::from_raw_sized(4, 4, src)
+    }
+
+    /// Create a board of the given size from a vector of bytes, in
+    /// the same order as from_raw. Besides the cells, the size is
+    /// validated as well.
+    pub fn from_raw_sized(width: uint, height: uint,
+                          src: &Vec<u8>) -> Result<Board, BoardError> {
+        try!(check_size(width, height));
+        if src.len() != width * height {
+            return Err(WrongSize(width * height, src.len()));
+        }
+        let max = max_tile(width, height);
+        for (idx, item) in src.iter().enumerate() {
+            if *item > max {
+                return Err(TileOutOfRange(idx, *item));
+            }
+        }
+
+        let mut result = Board::new(width, height);
+        for (idx, item) in src.iter().enumerate() {
+            result.cols[idx % width][idx / width] = *item;
+        }
+        Ok(result)
+    }
+
+    /// Construct a 4x4 board from a vector of four lines, taken as
+    /// one column each.
+    pub fn from_cols(lines: Vec<Line>) -> Board {
+        assert!(lines.len() == 4);
+        let mut result = Board::new(4, 4);
+        let mut x = 0;
+        for col in lines.iter() {
+            let mut y = 0;
+            for cell in col.iter() {
+                result.cols[x][y] = *cell;
+                y += 1;
+            }
+            x += 1;
+        }
+        result
+    }
+
+    /// Construct a 4x4 board from a vector of four lines, taken as
+    /// one row each.
+    pub fn from_rows(lines: Vec<Line>) -> Board {
+        assert!(lines.len() == 4);
+        let mut result = Board::new(4, 4);
+        let mut y = 0;
+        for row in lines.iter() {
+            let mut x = 0;
+            for cell in row.iter() {
+                result.cols[x][y] = *cell;
+                x += 1;
+            }
+            y += 1;
+        }
+        result
+    }
+
+    pub fn width(&self) -> uint {
+        self.width
+    }
+
+    pub fn height(&self) -> uint {
+        self.height
+    }
+
+    /// Return the tile at position ``(x, y)``.
+    pub fn get(&self, x: uint, y: uint) -> u8 {
+        self.cols[x][y]
+    }
+
+    /// Turn the cell at ``(x, y)`` into a blocker, removing its tile.
+    pub fn set_blocker(&mut self, x: uint, y: uint) {
+        assert!(x < self.width && y < self.height);
+        self.cols[x][y] = 0;
+        self.blockers |= 1u64 << (y * MAX_SIZE + x);
+    }
+
+    pub fn is_blocker(&self, x: uint, y: uint) -> bool {
+        self.blockers & (1u64 << (y * MAX_SIZE + x)) != 0
+    }
+
+    pub fn has_blockers(&self) -> bool {
+        self.blockers != 0
+    }
+
+    /// Return the contents of the row with the given index.
+    pub fn get_row(&self, idx: uint) -> Vec<u8> {
+        Vec::from_fn(self.width, |x| self.cols[x][idx])
+    }
+
+    /// Return the contents of the column with the given index.
+    pub fn get_col(&self, idx: uint) -> Vec<u8> {
+        Vec::from_slice(self.cols[idx].slice_to(self.height))
+    }
+
+    /// Calculate the AI score for the given constellation. This
+    /// calculates the gradient of the game board and gives back score
+    /// for consistent gradients.
+    ///
+    /// The gradients along the vertical and the horizontal axis are
+    /// scored differently. For each of the axis, two different values
+    /// are calculated, but both using the same scheme. One value is
+    /// taken in the forward and one in the backward direction.
+    ///
+    /// For the horizontal axis, the value of a cell is calculated by
+    /// taking the difference of the corresponding edge cell (same y
+    /// value, but 0 x index). If the difference is greater than or
+    /// equal to zero, a small positive score is applied. Otherwise, a
+    /// larger negative score is applied. The second value uses the
+    /// last (x=width-1) cell as reference.
+    ///
+    /// For the vertical axis, the score is based on the difference to
+    /// the previous cell (same x, but y-1). For a positive
+    /// difference, a small positive amount is scored. Negative or
+    /// zero differences are not counted. The second value uses the
+    /// reverse direction (comparing to the next cell instead of the
+    /// previous).
+    ///
+    /// Of both axis, the maximum value (taking the sign into account)
+    /// is used. Both are summed together, rounded to the nearest
+    /// integer and returned as score.
+    pub fn gradient_score(&self) -> Score {
+        let zero_hdiff_score = 1.;
+        let pos_hdiff_score = 1.;
+        let neg_hdiff_score = -12.;
+        let pos_vdiff_score = 0.5;
+
+        let (mut horiz_score_a, mut horiz_score_b) = (0.0f32, 0.0f32);
+        let (mut vert_score_a, mut vert_score_b) = (0.0f32, 0.0f32);
+
+        let last = self.width - 1;
+        let mut x = 0;
+        while x < self.width {
+            let mut y = 0;
+            while y < self.height {
+                let horiz_diff_a = self.cols[0][y] as int - self.cols[x][y] as int;
+                let horiz_diff_b = self.cols[last][y] as int - self.cols[x][y] as int;
+                let vert_diff = if y > 0 {
+                    self.cols[x][y-1] as int - self.cols[x][y] as int
+                } else {
+                    0
+                };
+
+                horiz_score_a += match horiz_diff_a {
+                    diff if diff > 0 => pos_hdiff_score,
+                    diff if diff < 0 => neg_hdiff_score,
+                    _ => zero_hdiff_score
+                };
+
+                horiz_score_b += match horiz_diff_b {
+                    diff if diff > 0 => pos_hdiff_score,
+                    diff if diff < 0 => neg_hdiff_score,
+                    _ => zero_hdiff_score
+                };
+
+
+                if vert_diff > 0 {
+                    vert_score_a += pos_vdiff_score;
+                } else if vert_diff < 0 {
+                    vert_score_b += pos_vdiff_score;
+                };
+
+                y += 1;
+            }
+            x += 1;
+        }
+
+        (horiz_score_a.max(horiz_score_b)+
+         vert_score_a.max(vert_score_b)).round() as Score
+    }
+
+    /// Return a copy of the board in which the tile at position ``(x,
+    /// y)`` is replaced with the value passed as *tile*.
+    pub fn place_tile(&self,
+                      x: uint,
+                      y: uint,
+                      tile: u8) -> Board
+    {
+        let mut copy = self.clone();
+        copy.cols[x][y] = tile;
+        copy
+    }
+
+    /// Set the tile at position ``x, y`` to the value *tile*.
+    pub fn set_tile(&mut self, x: uint, y: uint, tile: u8)
+    {
+        self.cols[x][y] = tile;
+    }
+
+    /// Shift the board in the given direction and return the new
+    /// board, along with the merging score obtained from this move.
+    ///
+    /// This is the innermost operation of the search, so it works on
+    /// a copy of the board in place instead of collecting lines.
+    pub fn shifted_board(&self,
+                         dir: Direction) -> (Board, Score) {
+        let mut score: Score = 0;
+        let result = self.shift_segments(dir, |segment| {
+            score += shift_line(segment);
+        });
+        (result, score)
+    }
+
+    /// Shift the board like shifted_board, but return the points the
+    /// game awards for the move instead of the merging score: the
+    /// value of each tile built by a merge.
+    pub fn shifted_with_points(&self, dir: Direction) -> (Board, u64) {
+        let mut points = 0u64;
+        let result = self.shift_segments(dir, |segment| {
+            let mut merged = [false, ..MAX_SIZE];
+            let len = segment.len();
+            shift_line_tracked(segment, merged.mut_slice_to(len));
+            for (tile, flag) in segment.iter().zip(merged.iter()) {
+                if *flag {
+                    points += num::pow(2u64, *tile as uint);
+                }
+            }
+        });
+        (result, points)
+    }
+
+    /// Apply *shift* to each line of the board, ordered so that it
+    /// moves the tiles towards the low end, and return the resulting
+    /// board. Lines are split at blockers, and each part is shifted
+    /// on its own.
+    fn shift_segments(&self, dir: Direction, shift: |&mut [u8]|) -> Board {
+        let mut result = self.clone();
+        let (lines, len) = match dir {
+            Up | Down => (self.width, self.height),
+            Left | Right => (self.height, self.width)
+        };
+        let mut line = [0u8, ..MAX_SIZE];
+        for i in range(0u, lines) {
+            for j in range(0u, len) {
+                line[j] = match dir {
+                    Up => self.cols[i][j],
+                    Down => self.cols[i][len-1-j],
+                    Left => self.cols[j][i],
+                    Right => self.cols[len-1-j][i]
+                };
+            }
+            if self.blockers == 0 {
+                shift(line.mut_slice_to(len));
+            } else {
+                let mut start = 0;
+                for j in range(0u, len + 1) {
+                    if j == len || self.is_blocker_on_line(dir, i, j, len) {
+                        shift(line.mut_slice(start, j));
+                        start = j + 1;
+                    }
+                }
+            }
+            for j in range(0u, len) {
+                match dir {
+                    Up => result.cols[i][j] = line[j],
+                    Down => result.cols[i][len-1-j] = line[j],
+                    Left => result.cols[j][i] = line[j],
+                    Right => result.cols[len-1-j][i] = line[j]
+                }
+            }
+        }
+        result
+    }
+
+    /// The motion of every tile when shifting towards *dir*, including
+    /// the tiles which stay where they are.
+    pub fn motions(&self, dir: Direction) -> Vec<Motion> {
+        let (lines, len) = match dir {
+            Up | Down => (self.width, self.height),
+            Left | Right => (self.height, self.width)
+        };
+        let mut motions = Vec::new();
+        for i in range(0u, lines) {
+            let mut start = 0;
+            for j in range(0u, len + 1) {
+                if j < len && !self.is_blocker_on_line(dir, i, j, len) {
+                    continue;
+                }
+                let mut line = [0u8, ..MAX_SIZE];
+                let mut origins = [0u16, ..MAX_SIZE];
+                for k in range(start, j) {
+                    let (x, y) = line_cell(dir, i, k, len);
+                    line[k - start] = self.cols[x][y];
+                }
+                shift_line_traced(line.mut_slice(0, j - start),
+                                  origins.mut_slice(0, j - start));
+                for k in range(0, j - start) {
+                    let merged = origins[k] & (origins[k] - 1) != 0;
+                    for source in range(0, j - start) {
+                        if origins[k] & (1 << source) != 0 {
+                            let from = line_cell(dir, i, start + source, len);
+                            let (x, y) = from;
+                            motions.push(Motion { from: from,
+                                                  to: line_cell(dir, i, start + k, len),
+                                                  tile: self.cols[x][y],
+                                                  merged: merged });
+                        }
+                    }
+                }
+                start = j + 1;
+            }
+        }
+        motions
+    }
+
+    /// Whether slot *j* of line *i*, in the order of shift_segments,
+    /// is a blocker.
+    fn is_blocker_on_line(&self, dir: Direction, i: uint, j: uint, len: uint) -> bool {
+        match dir {
+            Up => self.is_blocker(i, j),
+            Down => self.is_blocker(i, len-1-j),
+            Left => self.is_blocker(j, i),
+            Right => self.is_blocker(len-1-j, i)
+        }
+    }
+
+    /// Write the coordinates of the empty cells, in the order
+    /// OptionsIterator yields them, into *cells* and return their
+    /// number. Unlike collecting the iterator, this does not allocate.
+    pub fn empty_cells(&self, cells: &mut [(uint, uint), ..MAX_CELLS]) -> uint {
+        let mut count = 0;
+        for y in range(0u, self.height) {
+            for x in range(0u, self.width) {
+                if self.cols[x][y] == 0 && !self.is_blocker(x, y) {
+                    cells[count] = (x, y);
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// Fold *f* over the cells of the board.
+    fn fold_cells<T>(&self, init: T, f: |T, u8| -> T) -> T {
+        let mut acc = init;
+        for x in range(0u, self.width) {
+            for y in range(0u, self.height) {
+                acc = f(acc, self.cols[x][y]);
+            }
+        }
+        acc
+    }
+
+    pub fn total_value(&self) -> Score {
+        self.fold_cells(0, |prev, curr| prev + curr as Score)
+    }
+
+    pub fn total_value_exp(&self) -> Score {
+        self.fold_cells(0, |prev, curr| prev + num::pow(2 as Score, curr as uint))
+    }
+
+    pub fn total_nonzeros(&self) -> uint {
+        self.fold_cells(0, |prev, curr| prev + match curr {
+            0 => 0,
+            _ => 1
+        })
+    }
+
+    /// The exponent of the largest tile on the board.
+    pub fn highest_tile(&self) -> u8 {
+        self.fold_cells(0, |prev, curr| cmp::max(prev, curr))
+    }
+
+    /// The number of cells of the board.
+    pub fn cells(&self) -> uint {
+        self.width * self.height
+    }
+}
+
+impl Clone for Board {
+    fn clone(&self) -> Board {
+        Board { cols: self.cols,
+                width: self.width,
+                height: self.height,
+                blockers: self.blockers }
+    }
+}
+
+pub struct OptionsIterator<'a> {
+    board: &'a Board,
+    last: Option<(uint, uint)>
+}
+
+impl<'a> OptionsIterator<'a> {
+    pub fn new(board: &'a Board) -> OptionsIterator<'a> {
+        OptionsIterator { board: board,
+                          last: None }
+    }
+}
+
+impl<'a> Iterator<(uint, uint)> for OptionsIterator<'a> {
+    fn next(&mut self) -> Option<(uint, uint)> {
+        loop {
+            let last_x = self.board.width - 1;
+            let last_y = self.board.height - 1;
+            let (nextx, nexty) = match self.last {
+                Some((x, y)) if x == last_x && y < last_y =>
+                    (0, y+1),
+                Some((x, y)) if x == last_x && y == last_y => {
+                    return None;
+                }
+                Some((x, y)) if x < last_x =>
+                    (x+1, y),
+                None =>
+                    (0, 0),
+                Some(_) => {
+                    return None; // invalid state
+                }
+            };
+
+            self.last = Some((nextx, nexty));
+
+            if self.board.cols[nextx][nexty] != 0 ||
+               self.board.is_blocker(nextx, nexty) {
+                continue;
+            }
+
+            return self.last.clone();
+        }
+    }
+}
+
+impl Eq for Board {
+    fn eq(&self, other: &Board) -> bool {
+        // cells outside of the board are zero on both sides, so they
+        // need no special treatment
+        self.width == other.width && self.height == other.height &&
+            self.blockers == other.blockers &&
+            self.cols.iter().zip(other.cols.iter()).fold(
+                true,
+                |prev, (acol, bcol)| prev && acol.iter().zip(bcol.iter()).fold(
+                    true,
+                    |prev, (acell, bcell)| prev && (acell == bcell)))
+    }
+}
+
+/// The cell of slot *j* of line *i*, in the order of shift_segments.
+fn line_cell(dir: Direction, i: uint, j: uint, len: uint) -> (uint, uint) {
+    match dir {
+        Up => (i, j),
+        Down => (i, len-1-j),
+        Left => (j, i),
+        Right => (len-1-j, i)
+    }
+}
+
+impl fmt::Show for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut y = 0;
+        while y < self.height {
+            let mut x = 0;
+            while x < self.width {
+                if self.is_blocker(x, y) {
+                    try!(write!(f.buf, "# "));
+                } else {
+                    try!(write!(f.buf, "{} ", self.cols[x][y]));
+                }
+                x += 1;
+            }
+            try!(write!(f.buf, "\n"));
+            y += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cmp;
+    use std::num;
+    use rand::{Rng, SeedableRng, XorShiftRng};
+    use super::{Board, Line, OptionsIterator, Score};
+    use super::{shift_line, shift_line_tracked, shift_line_traced, SCORE_MERGE_FACTOR};
+    use super::{MAX_EXPONENT, MIN_SIZE, MAX_SIZE, MAX_CELLS, max_tile};
+    use super::{WrongSize, TileOutOfRange, UnsupportedSize};
+    use super::{Up, Down, Left, Right};
+
+    static RANDOM_BOARDS: uint = 2000;
+
+    /// Straightforward model of a 2048 line shift: drop the empty
+    /// cells, merge equal neighbours pairwise from the low end and
+    /// pad with zeroes. Returns the merge flags and score as well.
+    fn reference_shift(line: &Line) -> (Line, [bool, ..4], Score) {
+        let tiles = line.iter().filter(|cell| **cell != 0)
+            .map(|cell| *cell).collect::<Vec<u8>>();
+        let mut result = [0u8, ..4];
+        let mut merged = [false, ..4];
+        let mut score = 0;
+        let mut src = 0;
+        let mut dest = 0;
+        while src < tiles.len() {
+            let tile = *tiles.get(src);
+            if src + 1 < tiles.len() && *tiles.get(src + 1) == tile {
+                result[dest] = tile + 1;
+                merged[dest] = true;
+                score += (tile + 1) as Score;
+                src += 2;
+            } else {
+                result[dest] = tile;
+                src += 1;
+            }
+            dest += 1;
+        }
+        (result, merged, (score as f32 * SCORE_MERGE_FACTOR).round() as Score)
+    }
+
+    fn rng() -> XorShiftRng {
+        SeedableRng::from_seed([2048u32, 0x2049, 0xdead, 0xbeef])
+    }
+
+    fn random_board<R: Rng>(rng: &mut R) -> Board {
+        random_board_sized(rng, 4, 4)
+    }
+
+    fn random_board_sized<R: Rng>(rng: &mut R, width: uint, height: uint) -> Board {
+        let highest = cmp::min(11, max_tile(width, height));
+        let mut raw = Vec::new();
+        for _ in range(0, width * height) {
+            raw.push(if rng.gen_weighted_bool(3) {
+                0
+            } else {
+                rng.gen_range(1u8, highest + 1)
+            });
+        }
+        Board::from_raw_sized(width, height, &raw).unwrap()
+    }
+
+    /// Build a board of the given size with the tile at (x, y) taken
+    /// from *board* at ``f(x, y)``.
+    fn remapped(board: &Board, width: uint, height: uint,
+                f: |uint, uint| -> (uint, uint)) -> Board {
+        let mut result = Board::new(width, height);
+        for x in range(0, width) {
+            for y in range(0, height) {
+                let (src_x, src_y) = f(x, y);
+                result.set_tile(x, y, board.get(src_x, src_y));
+            }
+        }
+        result
+    }
+
+    fn mirrored_horiz(board: &Board) -> Board {
+        let (w, h) = (board.width(), board.height());
+        remapped(board, w, h, |x, y| (w - 1 - x, y))
+    }
+
+    fn mirrored_vert(board: &Board) -> Board {
+        let (w, h) = (board.width(), board.height());
+        remapped(board, w, h, |x, y| (x, h - 1 - y))
+    }
+
+    fn transposed(board: &Board) -> Board {
+        remapped(board, board.height(), board.width(), |x, y| (y, x))
+    }
+
+    fn to_line(cells: &[u8]) -> Line {
+        [cells[0], cells[1], cells[2], cells[3]]
+    }
+
+    fn tile_sum(line: &Line) -> uint {
+        line.iter().fold(0, |prev, cell| match *cell {
+            0 => prev,
+            exp => prev + num::pow(2u, exp as uint)
+        })
+    }
+
+    fn tile_count(line: &Line) -> uint {
+        line.iter().filter(|cell| **cell != 0).count()
+    }
+
+    /// The sum of the tiles on *board*. Unlike total_value_exp, this
+    /// does not count empty cells as ones, which merges would change.
+    fn board_tile_sum(board: &Board) -> uint {
+        board.fold_cells(0, |prev, cell| match cell {
+            0 => prev,
+            exp => prev + num::pow(2u, exp as uint)
+        })
+    }
+
+    #[test]
+    fn test_shift_line_exhaustive() {
+        for a in range(0u8, 16) {
+            for b in range(0u8, 16) {
+                for c in range(0u8, 16) {
+                    for d in range(0u8, 16) {
+                        let input = [a, b, c, d];
+                        let (expected, expected_merged, expected_score) =
+                            reference_shift(&input);
+
+                        let mut line = input;
+                        let mut merged = [false, ..4];
+                        let score = shift_line_tracked(line.as_mut_slice(),
+                                                       merged.as_mut_slice());
+
+                        assert!(line.as_slice() == expected.as_slice(),
+                                "{} shifted to {}, expected {}",
+                                input.as_slice(), line.as_slice(),
+                                expected.as_slice());
+                        assert!(merged.as_slice() == expected_merged.as_slice(),
+                                "{} merged {}, expected {}",
+                                input.as_slice(), merged.as_slice(),
+                                expected_merged.as_slice());
+                        assert_eq!(score, expected_score);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_shift_line_preserves_tile_sum() {
+        for a in range(0u8, 16) {
+            for b in range(0u8, 16) {
+                for c in range(0u8, 16) {
+                    for d in range(0u8, 16) {
+                        let input = [a, b, c, d];
+                        let mut line = input;
+                        shift_line(line.as_mut_slice());
+                        assert_eq!(tile_sum(&line), tile_sum(&input));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_shift_line_merges_each_tile_once() {
+        // each merge consumes exactly two tiles, so a tile merging
+        // twice shows up as more tiles lost than merges flagged
+        for a in range(0u8, 16) {
+            for b in range(0u8, 16) {
+                for c in range(0u8, 16) {
+                    for d in range(0u8, 16) {
+                        let input = [a, b, c, d];
+                        let mut line = input;
+                        let mut merged = [false, ..4];
+                        shift_line_tracked(line.as_mut_slice(), merged.as_mut_slice());
+                        let merges = merged.iter().filter(|m| **m).count();
+                        assert_eq!(tile_count(&input) - tile_count(&line),
+                                   merges);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_mirror_symmetry(board: &Board) {
+        let (left, left_score) = board.shifted_board(Left);
+        let (right, right_score) = mirrored_horiz(board).shifted_board(Right);
+        assert!(left == mirrored_horiz(&right),
+                "left/right asymmetry for\n{}", board);
+        assert_eq!(left_score, right_score);
+
+        let (up, up_score) = board.shifted_board(Up);
+        let (down, down_score) = mirrored_vert(board).shifted_board(Down);
+        assert!(up == mirrored_vert(&down),
+                "up/down asymmetry for\n{}", board);
+        assert_eq!(up_score, down_score);
+
+        let (transposed_left, transposed_score) =
+            transposed(board).shifted_board(Left);
+        assert!(up == transposed(&transposed_left),
+                "up/left asymmetry for\n{}", board);
+        assert_eq!(up_score, transposed_score);
+    }
+
+    #[test]
+    fn test_shifted_board_mirror_symmetry() {
+        let mut rng = rng();
+        for _ in range(0, RANDOM_BOARDS) {
+            check_mirror_symmetry(&random_board(&mut rng));
+        }
+    }
+
+    #[test]
+    fn test_shifted_board_matches_line_shift() {
+        let mut rng = rng();
+        for _ in range(0, RANDOM_BOARDS) {
+            let board = random_board(&mut rng);
+            let (left, score) = board.shifted_board(Left);
+            let mut expected_score = 0;
+            for y in range(0u, 4) {
+                let (expected, _, line_score) =
+                    reference_shift(&to_line(board.get_row(y).as_slice()));
+                assert!(left.get_row(y).as_slice() == expected.as_slice());
+                expected_score += line_score;
+            }
+            assert_eq!(score, expected_score);
+        }
+    }
+
+    #[test]
+    fn test_shifted_board_preserves_tile_sum() {
+        let mut rng = rng();
+        for _ in range(0, RANDOM_BOARDS) {
+            let board = random_board(&mut rng);
+            let before = board_tile_sum(&board);
+            for dir in [Up, Down, Left, Right].iter() {
+                let (shifted, _) = board.shifted_board(*dir);
+                assert_eq!(board_tile_sum(&shifted), before);
+            }
+        }
+    }
+
+    #[test]
+    fn test_board_roundtrips() {
+        let mut rng = rng();
+        for _ in range(0, RANDOM_BOARDS) {
+            let board = random_board(&mut rng);
+
+            let mut raw = Vec::new();
+            for y in range(0u, 4) {
+                raw.push_all(board.get_row(y).as_slice());
+            }
+            assert!(Board::from_raw(&raw).unwrap() == board);
+
+            let rows = Vec::from_fn(4, |y| to_line(board.get_row(y).as_slice()));
+            assert!(Board::from_rows(rows) == board);
+
+            let cols = Vec::from_fn(4, |x| to_line(board.get_col(x).as_slice()));
+            assert!(Board::from_cols(cols) == board);
+
+            assert!(board.clone() == board);
+        }
+    }
+
+    #[test]
+    fn test_from_raw_is_row_major() {
+        let raw = Vec::from_fn(16, |i| i as u8);
+        let board = Board::from_raw(&raw).unwrap();
+        assert_eq!(board.get_row(1).as_slice(), [4u8, 5, 6, 7].as_slice());
+        assert_eq!(board.get_col(1).as_slice(), [1u8, 5, 9, 13].as_slice());
+    }
+
+    #[test]
+    fn test_from_raw_validates() {
+        assert_eq!(Board::from_raw(&Vec::from_elem(15, 0u8)).err(),
+                   Some(WrongSize(16, 15)));
+        assert_eq!(Board::from_raw(&Vec::from_elem(17, 0u8)).err(),
+                   Some(WrongSize(16, 17)));
+
+        let mut raw = Vec::from_elem(16, max_tile(4, 4));
+        assert!(Board::from_raw(&raw).is_ok());
+        *raw.get_mut(5) = 200;
+        assert_eq!(Board::from_raw(&raw).err(),
+                   Some(TileOutOfRange(5, 200)));
+        *raw.get_mut(5) = 18;
+        assert_eq!(Board::from_raw(&raw).err(),
+                   Some(TileOutOfRange(5, 18)));
+    }
+
+    #[test]
+    fn test_eq_detects_every_cell() {
+        let mut rng = rng();
+        for _ in range(0, RANDOM_BOARDS / 16) {
+            let board = random_board(&mut rng);
+            for x in range(0u, 4) {
+                for y in range(0u, 4) {
+                    let changed = board.place_tile(x, y, board.get(x, y) + 1);
+                    assert!(changed != board);
+                    assert!(board != changed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_options_iterator_yields_empty_cells() {
+        let mut rng = rng();
+        for _ in range(0, RANDOM_BOARDS) {
+            let board = random_board(&mut rng);
+            let options = OptionsIterator::new(&board).collect::<Vec<(uint, uint)>>();
+
+            let mut expected = Vec::new();
+            for y in range(0u, 4) {
+                for x in range(0u, 4) {
+                    if board.get(x, y) == 0 {
+                        expected.push((x, y));
+                    }
+                }
+            }
+            assert!(options == expected, "{} != {} for\n{}",
+                    options, expected, board);
+            assert_eq!(options.len(), 16 - board.total_nonzeros());
+
+            let mut cells = [(0u, 0u), ..MAX_CELLS];
+            let count = board.empty_cells(&mut cells);
+            assert!(cells.slice_to(count) == options.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_options_iterator_full_and_empty_board() {
+        let full = Board::from_raw(&Vec::from_elem(16, 1u8)).unwrap();
+        assert_eq!(OptionsIterator::new(&full).count(), 0);
+        let empty = Board::from_raw(&Vec::from_elem(16, 0u8)).unwrap();
+        assert_eq!(OptionsIterator::new(&empty).count(), 16);
+    }
+
+    #[test]
+    fn test_board_sizes() {
+        let mut rng = rng();
+        for width in range(MIN_SIZE, MAX_SIZE + 1) {
+            for height in range(MIN_SIZE, MAX_SIZE + 1) {
+                for _ in range(0, RANDOM_BOARDS / 64) {
+                    let board = random_board_sized(&mut rng, width, height);
+                    check_mirror_symmetry(&board);
+
+                    let before = board_tile_sum(&board);
+                    for dir in [Up, Down, Left, Right].iter() {
+                        let (shifted, _) = board.shifted_board(*dir);
+                        assert_eq!(shifted.width(), width);
+                        assert_eq!(shifted.height(), height);
+                        assert_eq!(board_tile_sum(&shifted), before);
+                    }
+
+                    let mut raw = Vec::new();
+                    for y in range(0u, height) {
+                        raw.push_all(board.get_row(y).as_slice());
+                    }
+                    assert!(Board::from_raw_sized(width, height, &raw).unwrap() == board);
+
+                    let mut cells = [(0u, 0u), ..MAX_CELLS];
+                    let count = board.empty_cells(&mut cells);
+                    assert_eq!(count, board.cells() - board.total_nonzeros());
+                    let options = OptionsIterator::new(&board)
+                        .collect::<Vec<(uint, uint)>>();
+                    assert!(cells.slice_to(count) == options.as_slice());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_raw_sized_validates() {
+        assert_eq!(Board::from_raw_sized(2, 4, &Vec::from_elem(8, 0u8)).err(),
+                   Some(UnsupportedSize(2, 4)));
+        assert_eq!(Board::from_raw_sized(5, 9, &Vec::from_elem(45, 0u8)).err(),
+                   Some(UnsupportedSize(5, 9)));
+        assert_eq!(Board::from_raw_sized(3, 5, &Vec::from_elem(16, 0u8)).err(),
+                   Some(WrongSize(15, 16)));
+
+        let mut raw = Vec::from_elem(9, max_tile(3, 3));
+        assert!(Board::from_raw_sized(3, 3, &raw).is_ok());
+        *raw.get_mut(2) = max_tile(3, 3) + 1;
+        assert_eq!(Board::from_raw_sized(3, 3, &raw).err(),
+                   Some(TileOutOfRange(2, 11)));
+        // 2^17 is the largest tile which can be built on a 4x4 board
+        assert_eq!(max_tile(4, 4), 17);
+        assert_eq!(max_tile(8, 8), MAX_EXPONENT);
+        let raw = Vec::from_elem(64, MAX_EXPONENT + 1);
+        assert_eq!(Board::from_raw_sized(8, 8, &raw).err(),
+                   Some(TileOutOfRange(0, MAX_EXPONENT + 1)));
+    }
+
+    #[test]
+    fn test_eq_compares_size() {
+        assert!(Board::new(3, 4) != Board::new(4, 3));
+        assert!(Board::new(5, 5) == Board::new(5, 5));
+    }
+
+    #[test]
+    fn test_blockers() {
+        let mut board = Board::from_raw(&vec![1u8, 1, 0, 1,
+                                              0, 0, 0, 0,
+                                              2, 0, 0, 2,
+                                              0, 0, 0, 0]).unwrap();
+        board.set_blocker(2, 0);
+        board.set_blocker(1, 2);
+        let (left, _) = board.shifted_board(Left);
+        assert_eq!(left.get_row(0).as_slice(), [2u8, 0, 0, 1].as_slice());
+        assert_eq!(left.get_row(2).as_slice(), [2u8, 0, 2, 0].as_slice());
+        assert!(left.is_blocker(2, 0) && left.is_blocker(1, 2));
+        let (right, _) = board.shifted_board(Right);
+        assert_eq!(right.get_row(0).as_slice(), [0u8, 2, 0, 1].as_slice());
+        assert_eq!(right.get_row(2).as_slice(), [2u8, 0, 0, 2].as_slice());
+
+        let mut cells = [(0, 0), ..MAX_CELLS];
+        assert_eq!(board.empty_cells(&mut cells), 16 - 5 - 2);
+        assert_eq!(OptionsIterator::new(&board).count(), 16 - 5 - 2);
+        assert!(board != Board::from_raw(&vec![1u8, 1, 0, 1,
+                                               0, 0, 0, 0,
+                                               2, 0, 0, 2,
+                                               0, 0, 0, 0]).unwrap());
+    }
+
+    #[test]
+    fn test_shifted_with_points() {
+        let board = Board::from_raw(&vec![1u8, 1, 2, 2,
+                                          0, 2, 2, 0,
+                                          3, 0, 0, 3,
+                                          4, 4, 4, 4]).unwrap();
+        for dir in [Left, Right].iter() {
+            let (moved, points) = board.shifted_with_points(*dir);
+            assert_eq!(points, 4 + 8 + 8 + 16 + 32 + 32);
+            let (shifted, _) = board.shifted_board(*dir);
+            assert!(moved == shifted);
+        }
+        let (_, points) = board.shifted_with_points(Up);
+        assert_eq!(points, 8);
+    }
+
+    #[test]
+    fn test_shift_line_traced() {
+        let mut rng = rng();
+        for _ in range(0u, 2000) {
+            let len = rng.gen_range(1u, MAX_SIZE + 1);
+            let mut line = [0u8, ..MAX_SIZE];
+            for slot in line.mut_slice_to(len).mut_iter() {
+                *slot = if rng.gen_weighted_bool(3) { 0 } else { rng.gen_range(1u8, 4u8) };
+            }
+            let original = line;
+            let mut expected = line;
+            shift_line(expected.mut_slice_to(len));
+            let mut origins = [0u16, ..MAX_SIZE];
+            shift_line_traced(line.mut_slice_to(len), origins.mut_slice_to(len));
+            assert_eq!(line.slice_to(len), expected.slice_to(len));
+            let mut seen = 0u16;
+            for i in range(0, len) {
+                assert_eq!(seen & origins[i], 0);
+                seen |= origins[i];
+                let sources = range(0, len).filter(|k| origins[i] & (1 << *k) != 0)
+                    .collect::<Vec<uint>>();
+                match sources.len() {
+                    0 => assert_eq!(line[i], 0),
+                    1 => assert_eq!(line[i], original[*sources.get(0)]),
+                    2 => {
+                        assert_eq!(original[*sources.get(0)], original[*sources.get(1)]);
+                        assert_eq!(line[i], original[*sources.get(0)] + 1);
+                    }
+                    _ => fail!("tile {} built from {} tiles", i, sources.len())
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_motions_match_shifted_board() {
+        let mut rng = rng();
+        for _ in range(0u, 200) {
+            let width = rng.gen_range(MIN_SIZE, MAX_SIZE + 1);
+            let height = rng.gen_range(MIN_SIZE, MAX_SIZE + 1);
+            let mut board = random_board_sized(&mut rng, width, height);
+            if rng.gen_weighted_bool(2) {
+                let (x, y) = (rng.gen_range(0, width), rng.gen_range(0, height));
+                board.set_blocker(x, y);
+            }
+            for dir in [Up, Down, Left, Right].iter() {
+                let (expected, _) = board.shifted_board(*dir);
+                let mut moved = board.clone();
+                for x in range(0, width) {
+                    for y in range(0, height) {
+                        if !board.is_blocker(x, y) {
+                            moved.set_tile(x, y, 0);
+                        }
+                    }
+                }
+                let motions = board.motions(*dir);
+                assert_eq!(motions.len(), board.total_nonzeros());
+                for motion in motions.iter() {
+                    let (x, y) = motion.to;
+                    moved.set_tile(x, y, if motion.merged { motion.tile + 1 } else { motion.tile });
+                }
+                assert!(moved == expected);
+            }
+        }
+    }
+}
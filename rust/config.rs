@@ -0,0 +1,427 @@
+use std::rt;
+use getopts;
+use getopts::{optopt, optflag, optmulti, OptGroup};
+
+use board::MAX_SIZE;
+use error::{AiError, ConfigFailure};
+use game::Game;
+use logging::SharedSink;
+use rules::{Rules, AdversarialSpawns};
+use search::{DepthPolicy, EvalContext, SearchMode, Expectimax, Minimax};
+
+/// Searcher configuration, as assembled from the command line.
+#[deriving(Clone)]
+pub struct Config {
+    pub policy: DepthPolicy,
+    pub min_fill: f32,
+    pub min_fill_decay_per_level: f32,
+    pub min_new_nodes: uint,
+    pub threads: uint,
+    pub split_depth: uint,
+    pub rules: Rules,
+    pub mode: SearchMode,
+    /// Moves the adversary looks ahead in games with adversarial
+    /// spawns.
+    pub adversary_depth: uint,
+    pub risk_aversion: f64,
+    pub max_game_over: Option<f64>
+}
+
+impl Config {
+    /// The configuration the AI has always been run with.
+    pub fn default() -> Config {
+        Config { policy: DepthPolicy::new(2, 5, 7),
+                 min_fill: 1.0,
+                 min_fill_decay_per_level: 0.6,
+                 min_new_nodes: 2,
+                 threads: rt::default_sched_threads(),
+                 split_depth: 2,
+                 rules: Rules::default(),
+                 mode: Expectimax,
+                 adversary_depth: 1,
+                 risk_aversion: 0.0,
+                 max_game_over: None }
+    }
+
+    /// Create the search context for this configuration. Its
+    /// max_depth is overridden per board by the depth policy.
+    pub fn context(&self) -> EvalContext {
+        self.logged_context(None)
+    }
+
+    /// Like context, but the worker threads of the search log to
+    /// *sink* if given.
+    pub fn logged_context(&self, sink: Option<SharedSink>) -> EvalContext {
+        let mut ctx = EvalContext::new(self.policy.default_depth,
+                                       self.min_fill,
+                                       self.min_fill_decay_per_level,
+                                       self.min_new_nodes);
+        ctx.set_threads(self.threads, self.split_depth, sink);
+        ctx.rules = self.rules.clone();
+        ctx.mode = self.mode;
+        ctx.risk_aversion = self.risk_aversion;
+        ctx.max_game_over = self.max_game_over;
+        ctx
+    }
+
+    /// Start a game of the given size under the rules of this
+    /// configuration.
+    pub fn new_game(&self, width: uint, height: uint,
+                    seed: u32) -> Result<Game, AiError> {
+        let mut game = try!(Game::new(width, height, self.rules.clone(), seed).map_err(
+            |e| ConfigFailure(format!("invalid rules: {}", e))));
+        game.set_adversary_depth(self.adversary_depth);
+        Ok(game)
+    }
+
+    /// Apply the options from *matches* on top of this configuration
+    /// and check the result for consistency.
+    pub fn update(&mut self, matches: &getopts::Matches) -> Result<(), AiError> {
+        match try!(parse_opt::<uint>(matches, "min-depth")) {
+            Some(v) => self.policy.min_depth = v,
+            None => ()
+        }
+        match try!(parse_opt::<uint>(matches, "depth")) {
+            Some(v) => self.policy.default_depth = v,
+            None => ()
+        }
+        match try!(parse_opt::<uint>(matches, "max-depth")) {
+            Some(v) => self.policy.max_depth = v,
+            None => ()
+        }
+        match try!(parse_opt::<f32>(matches, "min-fill")) {
+            Some(v) => self.min_fill = v,
+            None => ()
+        }
+        match try!(parse_opt::<f32>(matches, "fill-decay")) {
+            Some(v) => self.min_fill_decay_per_level = v,
+            None => ()
+        }
+        match try!(parse_opt::<uint>(matches, "min-new-nodes")) {
+            Some(v) => self.min_new_nodes = v,
+            None => ()
+        }
+        match try!(parse_opt::<uint>(matches, "threads")) {
+            Some(v) => self.threads = v,
+            None => ()
+        }
+        match try!(parse_opt::<uint>(matches, "split-depth")) {
+            Some(v) => self.split_depth = v,
+            None => ()
+        }
+        match matches.opt_str("search-mode") {
+            Some(ref name) if name.as_slice() == "expectimax" => self.mode = Expectimax,
+            Some(ref name) if name.as_slice() == "minimax" => self.mode = Minimax,
+            Some(name) => return Err(ConfigFailure(format!(
+                "unknown search mode: {}", name))),
+            None => ()
+        }
+        match try!(parse_opt::<f64>(matches, "risk-aversion")) {
+            Some(v) => self.risk_aversion = v,
+            None => ()
+        }
+        match try!(parse_opt::<f64>(matches, "max-game-over")) {
+            Some(v) => self.max_game_over = Some(v),
+            None => ()
+        }
+        try!(self.update_rules(matches));
+        self.validate()
+    }
+
+    /// Apply only the options from rules_options, for tools which play
+    /// games without searching. The result is not checked; call
+    /// validate for that.
+    pub fn update_rules(&mut self, matches: &getopts::Matches) -> Result<(), AiError> {
+        match matches.opt_str("spawn-tiles") {
+            Some(value) => {
+                let (low, high) = try!(parse_pair::<u8>("spawn-tiles", value.as_slice()));
+                self.rules.low_tile = low;
+                self.rules.high_tile = high;
+            }
+            None => ()
+        }
+        match try!(parse_opt::<f64>(matches, "high-probability")) {
+            Some(v) => self.rules.high_probability = v,
+            None => ()
+        }
+        match try!(parse_opt::<uint>(matches, "spawns-per-move")) {
+            Some(v) => self.rules.spawns_per_move = v,
+            None => ()
+        }
+        match try!(parse_opt::<uint>(matches, "start-tiles")) {
+            Some(v) => self.rules.start_tiles = v,
+            None => ()
+        }
+        for value in matches.opt_strs("blocker").iter() {
+            let cell = try!(parse_pair::<uint>("blocker", value.as_slice()));
+            self.rules.blockers.push(cell);
+        }
+        match try!(parse_opt::<u8>(matches, "target")) {
+            Some(v) => self.rules.target = Some(v),
+            None => ()
+        }
+        if matches.opt_present("adversarial-spawns") {
+            self.rules.spawn_mode = AdversarialSpawns;
+        }
+        match try!(parse_opt::<uint>(matches, "adversary-depth")) {
+            Some(v) => self.adversary_depth = v,
+            None => ()
+        }
+        Ok(())
+    }
+
+    /// The options of rules_options which set the rules of this
+    /// configuration, for passing them on to AIs in other processes.
+    /// Rules left at their defaults are not given, so that AIs which
+    /// only know the original game can play it.
+    pub fn rules_args(&self) -> Vec<String> {
+        let default = Rules::default();
+        let mut args = Vec::new();
+        if self.rules.low_tile != default.low_tile || self.rules.high_tile != default.high_tile {
+            push_arg(&mut args, "spawn-tiles",
+                     format!("{},{}", self.rules.low_tile, self.rules.high_tile));
+        }
+        if self.rules.high_probability != default.high_probability {
+            push_arg(&mut args, "high-probability",
+                     format!("{}", self.rules.high_probability));
+        }
+        if self.rules.spawns_per_move != default.spawns_per_move {
+            push_arg(&mut args, "spawns-per-move",
+                     format!("{}", self.rules.spawns_per_move));
+        }
+        if self.rules.start_tiles != default.start_tiles {
+            push_arg(&mut args, "start-tiles",
+                     format!("{}", self.rules.start_tiles));
+        }
+        for &(x, y) in self.rules.blockers.iter() {
+            push_arg(&mut args, "blocker", format!("{},{}", x, y));
+        }
+        match self.rules.target {
+            Some(tile) => push_arg(&mut args, "target", format!("{}", tile)),
+            None => ()
+        }
+        if self.rules.spawn_mode != default.spawn_mode {
+            args.push(String::from_str("--adversarial-spawns"));
+            push_arg(&mut args, "adversary-depth",
+                     format!("{}", self.adversary_depth));
+        }
+        args
+    }
+
+    pub fn validate(&self) -> Result<(), AiError> {
+        if self.policy.min_depth < 1 {
+            return Err(ConfigFailure(format!("min-depth must be at least 1")));
+        }
+        if self.policy.min_depth > self.policy.default_depth ||
+            self.policy.default_depth > self.policy.max_depth
+        {
+            return Err(ConfigFailure(format!(
+                "depths must satisfy min-depth <= depth <= max-depth, got {} {} {}",
+                self.policy.min_depth,
+                self.policy.default_depth,
+                self.policy.max_depth)));
+        }
+        if !(self.min_fill > 0.0 && self.min_fill <= 1.0) {
+            return Err(ConfigFailure(format!(
+                "min-fill must be in (0, 1], got {}", self.min_fill)));
+        }
+        if !(self.min_fill_decay_per_level > 0.0 &&
+             self.min_fill_decay_per_level <= 1.0)
+        {
+            return Err(ConfigFailure(format!(
+                "fill-decay must be in (0, 1], got {}",
+                self.min_fill_decay_per_level)));
+        }
+        if self.min_new_nodes < 1 {
+            return Err(ConfigFailure(format!(
+                "min-new-nodes must be at least 1")));
+        }
+        if self.threads < 1 {
+            return Err(ConfigFailure(format!("threads must be at least 1")));
+        }
+        if self.adversary_depth < 1 {
+            return Err(ConfigFailure(format!("adversary-depth must be at least 1")));
+        }
+        if !(self.risk_aversion >= 0.0) {
+            return Err(ConfigFailure(format!(
+                "risk-aversion must not be negative, got {}", self.risk_aversion)));
+        }
+        match self.max_game_over {
+            Some(p) if !(p >= 0.0 && p <= 1.0) =>
+                return Err(ConfigFailure(format!(
+                    "max-game-over must be in [0, 1], got {}", p))),
+            _ => ()
+        }
+        // the board size is only known per game, so check against the
+        // largest board; games check their own size
+        try!(self.rules.check(MAX_SIZE, MAX_SIZE).map_err(
+            |e| ConfigFailure(format!("invalid rules: {}", e))));
+        Ok(())
+    }
+}
+
+/// The value of the option *name* in *matches*, or *default* if it is
+/// not given.
+pub fn parse_or<T: FromStr>(matches: &getopts::Matches, name: &str,
+                            default: T) -> Result<T, AiError> {
+    Ok(try!(parse_opt(matches, name)).unwrap_or(default))
+}
+
+fn parse_opt<T: FromStr>(matches: &getopts::Matches,
+                         name: &str) -> Result<Option<T>, AiError> {
+    match matches.opt_str(name) {
+        Some(value) => match from_str::<T>(value.as_slice()) {
+            Some(parsed) => Ok(Some(parsed)),
+            None => Err(ConfigFailure(format!(
+                "invalid value for --{}: {}", name, value)))
+        },
+        None => Ok(None)
+    }
+}
+
+fn push_arg(args: &mut Vec<String>, name: &str, value: String) {
+    args.push(format!("--{}", name));
+    args.push(value);
+}
+
+/// Parse a value of the form ``A,B``.
+fn parse_pair<T: FromStr>(name: &str, value: &str) -> Result<(T, T), AiError> {
+    let parts: Vec<&str> = value.split(',').collect();
+    if parts.len() == 2 {
+        match (from_str::<T>(*parts.get(0)), from_str::<T>(*parts.get(1))) {
+            (Some(a), Some(b)) => return Ok((a, b)),
+            _ => ()
+        }
+    }
+    Err(ConfigFailure(format!("invalid value for --{}: {}", name, value)))
+}
+
+/// The command line options understood by Config::update.
+pub fn options() -> Vec<OptGroup> {
+    let mut opts = vec![
+        optopt("", "min-depth", "search depth for nearly empty boards (2)", "N"),
+        optopt("", "depth", "default search depth (5)", "N"),
+        optopt("", "max-depth", "search depth for crowded boards (7)", "N"),
+        optopt("", "min-fill",
+               "share of spawn positions tried on the first level (1.0)", "F"),
+        optopt("", "fill-decay",
+               "factor applied to min-fill per level (0.6)", "F"),
+        optopt("", "min-new-nodes",
+               "minimum number of spawn positions tried per move (2)", "N"),
+        optopt("", "threads",
+               "number of search threads (one per core)", "N"),
+        optopt("", "split-depth",
+               "deepest level searched in parallel (2)", "N"),
+        optopt("", "search-mode",
+               "average over sampled spawns or assume the worst spawn (expectimax)",
+               "expectimax|minimax"),
+        optopt("", "risk-aversion",
+               "penalty per standard deviation of the outcomes of a move (0)", "F"),
+        optopt("", "max-game-over",
+               "highest accepted probability of a game over within the search depth",
+               "P")];
+    opts.push_all_move(rules_options());
+    opts.push(optflag("h", "help", "show this help"));
+    opts
+}
+
+/// The command line options for the rules of the game, understood by
+/// Config::update_rules.
+pub fn rules_options() -> Vec<OptGroup> {
+    vec![optopt("", "spawn-tiles",
+                "exponents of the usual and the rare spawn tile (1,2)", "LOW,HIGH"),
+         optopt("", "high-probability",
+                "probability that the rare tile spawns (0.1)", "P"),
+         optopt("", "spawns-per-move", "tiles spawned after each move (1)", "N"),
+         optopt("", "start-tiles", "tiles spawned on the empty board (2)", "N"),
+         optmulti("", "blocker",
+                  "make the cell at X,Y a blocker, may be repeated", "X,Y"),
+         optopt("", "target", "end the game once a tile of 2^N is built", "N"),
+         optflag("", "adversarial-spawns",
+                 "spawn the tiles worst for the player instead of random ones"),
+         optopt("", "adversary-depth",
+                "moves the adversary looks ahead with --adversarial-spawns (1)", "N")]
+}
+
+/// Parse a command line (without the program name) into a Config.
+/// Returns None if help was requested.
+pub fn parse_args(args: &[String],
+                  opts: &[OptGroup]) -> Result<Option<(Config, getopts::Matches)>, AiError> {
+    let matches = try!(getopts::getopts(args, opts).map_err(
+        |e| ConfigFailure(e.to_err_msg())));
+    if matches.opt_present("help") {
+        return Ok(None);
+    }
+    if matches.free.len() > 0 {
+        return Err(ConfigFailure(format!(
+            "unexpected argument: {}", matches.free.get(0))));
+    }
+    let mut config = Config::default();
+    try!(config.update(&matches));
+    Ok(Some((config, matches)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{options, parse_args};
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| String::from_str(*arg)).collect()
+    }
+
+    fn parse(args: &[&str]) -> bool {
+        parse_args(strings(args).as_slice(), options().as_slice()).is_ok()
+    }
+
+    #[test]
+    fn test_rules_args() {
+        let (config, _) = parse_args([], options().as_slice()).ok().unwrap().unwrap();
+        assert!(config.rules_args().is_empty());
+
+        let args = strings(&["--spawn-tiles", "1,3", "--high-probability", "0.25",
+                             "--blocker", "1,1", "--blocker", "2,3", "--target", "11",
+                             "--adversarial-spawns", "--adversary-depth", "2"]);
+        let (config, _) = parse_args(args.as_slice(), options().as_slice())
+            .ok().unwrap().unwrap();
+        let forwarded = config.rules_args();
+        let (copy, _) = parse_args(forwarded.as_slice(), options().as_slice())
+            .ok().unwrap().unwrap();
+        assert_eq!(copy.rules_args(), forwarded);
+        assert_eq!(copy.rules.high_tile, 3);
+        assert_eq!(copy.rules.high_probability, 0.25);
+        assert_eq!(copy.rules.blockers, vec![(1u, 1u), (2, 3)]);
+        assert_eq!(copy.rules.target, Some(11));
+        assert_eq!(copy.adversary_depth, 2);
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert!(parse(&[]));
+        assert!(parse(&["--depth", "4", "--max-depth", "6"]));
+        assert!(parse(&["--min-fill", "0.5"]));
+        assert!(!parse(&["--depth", "four"]));
+        assert!(!parse(&["--depth", "9"]));
+        assert!(!parse(&["--min-depth", "0"]));
+        assert!(!parse(&["--min-fill", "0"]));
+        assert!(!parse(&["--min-new-nodes", "0"]));
+        assert!(parse(&["--threads", "1", "--split-depth", "0"]));
+        assert!(!parse(&["--threads", "0"]));
+        assert!(parse(&["--spawn-tiles", "1,3", "--high-probability", "0.5",
+                        "--spawns-per-move", "2", "--blocker", "1,1",
+                        "--blocker", "2,3", "--target", "11",
+                        "--adversarial-spawns"]));
+        assert!(!parse(&["--spawn-tiles", "2"]));
+        assert!(!parse(&["--spawn-tiles", "2,1"]));
+        assert!(!parse(&["--high-probability", "2"]));
+        assert!(!parse(&["--blocker", "8,0"]));
+        assert!(!parse(&["--spawns-per-move", "0"]));
+        assert!(parse(&["--search-mode", "minimax", "--adversary-depth", "3"]));
+        assert!(!parse(&["--search-mode", "maximin"]));
+        assert!(!parse(&["--adversary-depth", "0"]));
+        assert!(parse(&["--risk-aversion", "0.5", "--max-game-over", "0.01"]));
+        assert!(!parse(&["--risk-aversion", "-1"]));
+        assert!(!parse(&["--max-game-over", "1.5"]));
+        assert!(!parse(&["--bogus"]));
+        assert!(!parse(&["stray"]));
+    }
+}
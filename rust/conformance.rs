@@ -0,0 +1,117 @@
+extern crate g2048;
+
+use std::io;
+use std::io::process::Command;
+use std::os;
+
+use g2048::board::shift_line_tracked;
+use g2048::vectors::{ShiftVector, parse_vectors, parse_result};
+use g2048::vectors::{format_input, format_result};
+
+/// Run the line shift of this crate on each vector and return the
+/// results, formatted like the output of the external drivers.
+fn run_builtin(vectors: &Vec<ShiftVector>) -> Vec<String> {
+    vectors.iter().map(|vector| {
+        let mut line = vector.input;
+        let mut merged = [false, ..4];
+        shift_line_tracked(line.as_mut_slice(), merged.as_mut_slice());
+        format_result(&line, &merged)
+    }).collect()
+}
+
+/// Feed the inputs of all vectors to an external driver, one line
+/// per vector, and collect the lines it prints in response.
+fn run_driver(vectors: &Vec<ShiftVector>,
+              cmd: &[String]) -> Result<Vec<String>, io::IoError> {
+    let mut process = try!(Command::new(cmd[0].as_slice())
+                           .args(cmd.slice_from(1))
+                           .spawn());
+    {
+        let mut stdin = process.stdin.take_unwrap();
+        for vector in vectors.iter() {
+            try!(stdin.write_line(format_input(&vector.input).as_slice()));
+        }
+    }
+    let output = try!(process.stdout.get_mut_ref().read_to_str());
+    try!(process.wait());
+    Ok(output.as_slice().lines().map(|line| String::from_str(line)).collect())
+}
+
+fn main() {
+    let args = os::args();
+    if args.len() < 2 {
+        let _ = writeln!(&mut io::stderr(),
+                         "usage: {} VECTORS [DRIVER ARGS...]\n\n\
+                          Check the line shift of DRIVER (or of this crate if \
+                          no driver is given)\nagainst the golden vectors in \
+                          VECTORS.",
+                         args.get(0));
+        os::set_exit_status(2);
+        return;
+    }
+
+    let src = match io::File::open(&Path::new(args.get(1).as_slice()))
+        .read_to_str()
+    {
+        Ok(src) => src,
+        Err(e) => {
+            let _ = writeln!(&mut io::stderr(), "cannot read vectors: {}", e);
+            os::set_exit_status(2);
+            return;
+        }
+    };
+    let vectors = match parse_vectors(src.as_slice()) {
+        Ok(vectors) => vectors,
+        Err(lineno) => {
+            let _ = writeln!(&mut io::stderr(),
+                             "malformed vector in line {}", lineno);
+            os::set_exit_status(2);
+            return;
+        }
+    };
+
+    let (name, results) = if args.len() > 2 {
+        let cmd = args.slice_from(2);
+        match run_driver(&vectors, cmd) {
+            Ok(results) => (cmd.connect(" "), results),
+            Err(e) => {
+                let _ = writeln!(&mut io::stderr(),
+                                 "failed to run driver: {}", e);
+                os::set_exit_status(2);
+                return;
+            }
+        }
+    } else {
+        (String::from_str("builtin"), run_builtin(&vectors))
+    };
+
+    let mut failures = 0u;
+    if results.len() != vectors.len() {
+        println!("{}: expected {} results, got {}",
+                 name, vectors.len(), results.len());
+        failures += 1;
+    }
+
+    for (vector, result) in vectors.iter().zip(results.iter()) {
+        let expected = format_result(&vector.output, &vector.merged);
+        let matches = match parse_result(result.as_slice()) {
+            Some((output, merged)) =>
+                output.as_slice() == vector.output.as_slice() &&
+                merged.as_slice() == vector.merged.as_slice(),
+            None => false
+        };
+        if !matches {
+            println!("{}: {} -> {}, expected {}",
+                     name, format_input(&vector.input), result, expected);
+            failures += 1;
+        }
+    }
+
+    if failures > 0 {
+        println!("{}: {} of {} vectors failed",
+                 name, failures, vectors.len());
+        os::set_exit_status(1);
+    } else {
+        println!("{}: all {} vectors passed", name, vectors.len());
+    }
+}
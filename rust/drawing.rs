@@ -0,0 +1,723 @@
+use std::io;
+
+use board::{Board, Direction, Up, Down, Left, Right};
+use config::Config;
+use error::{AiError, IoFailure, ConfigFailure};
+use hint::Hint;
+use record::GameRecord;
+
+static ALL_DIRECTIONS: [Direction, ..4] = [Up, Down, Left, Right];
+
+/// The colours of the original game, as RGB. Pictures are drawn with
+/// indices into this table, which is the palette of the PNG files.
+static PALETTE: [u32, ..18] = [
+    0xfaf8ef, 0xbbada0, 0x8f7a66, 0x776e65, 0xf9f6f2, 0x3c3a32,
+    // the tiles from the empty cell to 2048
+    0xcdc1b4, 0xeee4da, 0xede0c8, 0xf2b179, 0xf59563, 0xf67c5f,
+    0xf65e3b, 0xedcf72, 0xedcc61, 0xedc850, 0xedc53f, 0xedc22e];
+static PAGE: u8 = 0;
+static BACKGROUND: u8 = 1;
+/// Blockers, and the recommended move in annotations.
+static HIGHLIGHT: u8 = 2;
+static DARK_TEXT: u8 = 3;
+static LIGHT_TEXT: u8 = 4;
+/// Tiles beyond 2048.
+static BIG_TILE: u8 = 5;
+static EMPTY_CELL: u8 = 6;
+
+static TILE_SIZE: uint = 64;
+static GAP: uint = 8;
+/// Height of the strip with the move scores below the board.
+static ANNOTATION_HEIGHT: uint = 48;
+
+/// Frames per move in animations: the tiles sliding, the merged tiles
+/// popping up, and the board after the spawn.
+static SLIDE_FRAMES: uint = 4;
+static SLIDE_MS: u16 = 30;
+static POP_MS: u16 = 60;
+/// How much merged tiles grow on each side when they pop up.
+static POP_SIZE: uint = 4;
+
+/// The glyphs of the pixel font, 5 pixels wide, one byte per row.
+static GLYPHS: [(char, [u8, ..7]), ..17] = [
+    ('0', [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e]),
+    ('1', [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e]),
+    ('2', [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f]),
+    ('3', [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e]),
+    ('4', [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02]),
+    ('5', [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e]),
+    ('6', [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e]),
+    ('7', [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+    ('8', [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e]),
+    ('9', [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c]),
+    ('-', [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00]),
+    ('U', [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e]),
+    ('D', [0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c]),
+    ('L', [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f]),
+    ('R', [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11]),
+    ('k', [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12]),
+    ('M', [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11])];
+static GLYPH_WIDTH: uint = 5;
+static GLYPH_HEIGHT: uint = 7;
+
+fn tile_colour(tile: u8) -> u8 {
+    if (tile as uint) < PALETTE.len() - EMPTY_CELL as uint {
+        EMPTY_CELL + tile
+    } else {
+        BIG_TILE
+    }
+}
+
+fn text_colour(tile: u8) -> u8 {
+    if tile <= 2 { DARK_TEXT } else { LIGHT_TEXT }
+}
+
+fn hex(colour: u8) -> String {
+    format!("#{:06x}", PALETTE[colour as uint])
+}
+
+fn direction_name(dir: Direction) -> &'static str {
+    match dir {
+        Up => "up",
+        Down => "down",
+        Left => "left",
+        Right => "right"
+    }
+}
+
+/// Write *score* in at most five characters, for the pixel font.
+fn compact(score: i64) -> String {
+    let magnitude = if score < 0 { -score } else { score };
+    if magnitude < 10000 {
+        format!("{}", score)
+    } else if magnitude < 10000000 {
+        format!("{}k", score / 1000)
+    } else {
+        format!("{}M", score / 1000000)
+    }
+}
+
+/// The offset of row or column *i* of the board on the picture.
+fn cell_offset(i: uint) -> uint {
+    GAP + i * (TILE_SIZE + GAP)
+}
+
+fn board_pixels(cells: uint) -> uint {
+    cell_offset(cells)
+}
+
+/// A picture made of palette indices.
+pub struct Canvas {
+    pub width: uint,
+    pub height: uint,
+    pixels: Vec<u8>
+}
+
+impl Canvas {
+    fn new(width: uint, height: uint, colour: u8) -> Canvas {
+        Canvas { width: width,
+                 height: height,
+                 pixels: Vec::from_elem(width * height, colour) }
+    }
+
+    /// Fill a rectangle, clipped to the canvas.
+    fn fill(&mut self, x: uint, y: uint, width: uint, height: uint, colour: u8) {
+        for row in range(y, ::std::cmp::min(y + height, self.height)) {
+            let start = row * self.width + x;
+            let end = row * self.width + ::std::cmp::min(x + width, self.width);
+            if start < end {
+                for pixel in self.pixels.mut_slice(start, end).mut_iter() {
+                    *pixel = colour;
+                }
+            }
+        }
+    }
+
+    /// Write *text* centred on (*x*, *y*), each font pixel *scale*
+    /// pixels large. Characters without a glyph are left blank.
+    fn text(&mut self, x: uint, y: uint, text: &str, scale: uint, colour: u8) {
+        let width = text_width(text, scale);
+        let left = x - ::std::cmp::min(width / 2, x);
+        let top = y - ::std::cmp::min(GLYPH_HEIGHT * scale / 2, y);
+        for (i, c) in text.chars().enumerate() {
+            let rows = match GLYPHS.iter().find(|&&(g, _)| g == c) {
+                Some(&(_, rows)) => rows,
+                None => continue
+            };
+            let glyph_left = left + i * (GLYPH_WIDTH + 1) * scale;
+            for (row, bits) in rows.iter().enumerate() {
+                for column in range(0, GLYPH_WIDTH) {
+                    if *bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                        self.fill(glyph_left + column * scale, top + row * scale,
+                                  scale, scale, colour);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The rows of the picture as PNG scanlines, each starting with
+    /// filter type 0.
+    fn scanlines(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity((self.width + 1) * self.height);
+        for row in range(0, self.height) {
+            data.push(0u8);
+            data.push_all(self.pixels.slice(row * self.width, (row + 1) * self.width));
+        }
+        data
+    }
+
+    /// Write the picture as a PNG file.
+    pub fn write_png<W: Writer>(&self, out: &mut W) -> io::IoResult<()> {
+        try!(write_png_header(out, self.width, self.height));
+        try!(write_chunk(out, "IDAT", zlib(self.scanlines().as_slice(),
+                                           self.width + 1).as_slice()));
+        write_chunk(out, "IEND", [])
+    }
+}
+
+fn text_width(text: &str, scale: uint) -> uint {
+    let chars = text.char_len();
+    if chars == 0 { 0 } else { (chars * (GLYPH_WIDTH + 1) - 1) * scale }
+}
+
+/// Draw *tile* with its top left corner at (*x*, *y*), grown by *grow*
+/// pixels on each side.
+fn draw_tile(canvas: &mut Canvas, x: uint, y: uint, tile: u8, grow: uint) {
+    canvas.fill(x - grow, y - grow, TILE_SIZE + 2 * grow, TILE_SIZE + 2 * grow,
+                tile_colour(tile));
+    if tile == 0 {
+        return;
+    }
+    let label = format!("{}", 1u64 << tile as uint);
+    let scale = range(1u, 4).rev()
+        .find(|scale| text_width(label.as_slice(), *scale) <= TILE_SIZE - 10)
+        .unwrap_or(1);
+    canvas.text(x + TILE_SIZE / 2, y + TILE_SIZE / 2, label.as_slice(), scale,
+                text_colour(tile));
+}
+
+/// Draw the background of *board* with its empty cells and blockers,
+/// but without tiles.
+fn draw_grid(canvas: &mut Canvas, board: &Board) {
+    canvas.fill(0, 0, board_pixels(board.width()), board_pixels(board.height()),
+                BACKGROUND);
+    for y in range(0, board.height()) {
+        for x in range(0, board.width()) {
+            let colour = if board.is_blocker(x, y) { HIGHLIGHT } else { EMPTY_CELL };
+            canvas.fill(cell_offset(x), cell_offset(y), TILE_SIZE, TILE_SIZE, colour);
+        }
+    }
+}
+
+/// Draw the scores of *hint* in the strip starting at *top*, one box
+/// per move, the recommended one highlighted.
+fn draw_annotations(canvas: &mut Canvas, top: uint, hint: &Hint) {
+    let width = (canvas.width - 5 * GAP) / 4;
+    for (i, dir) in ALL_DIRECTIONS.iter().enumerate() {
+        let left = GAP + i * (width + GAP);
+        let recommended = hint.best.map_or(false, |best| best as uint == i);
+        let (fill, text) = if recommended {
+            (HIGHLIGHT, LIGHT_TEXT)
+        } else {
+            (EMPTY_CELL, DARK_TEXT)
+        };
+        canvas.fill(left, top, width, ANNOTATION_HEIGHT - GAP, fill);
+        let letter = match *dir { Up => "U", Down => "D", Left => "L", Right => "R" };
+        canvas.text(left + width / 2, top + 12, letter, 2, text);
+        let score = match hint.score(*dir) {
+            Some(score) => compact(score),
+            None => String::from_str("-")
+        };
+        canvas.text(left + width / 2, top + 31, score.as_slice(), 1, text);
+    }
+}
+
+/// Draw *board*, with the scores of the moves below it if *hint* is
+/// given.
+pub fn draw_board(board: &Board, hint: Option<&Hint>) -> Canvas {
+    let (width, height) = (board_pixels(board.width()), board_pixels(board.height()));
+    let extra = if hint.is_some() { ANNOTATION_HEIGHT } else { 0 };
+    let mut canvas = Canvas::new(width, height + extra, PAGE);
+    draw_grid(&mut canvas, board);
+    for y in range(0, board.height()) {
+        for x in range(0, board.width()) {
+            if !board.is_blocker(x, y) {
+                draw_tile(&mut canvas, cell_offset(x), cell_offset(y), board.get(x, y), 0);
+            }
+        }
+    }
+    match hint {
+        Some(hint) => draw_annotations(&mut canvas, height, hint),
+        None => ()
+    }
+    canvas
+}
+
+/// Write *board* as SVG, with the scores of the moves below it if
+/// *hint* is given.
+pub fn write_svg<W: Writer>(board: &Board, hint: Option<&Hint>,
+                            out: &mut W) -> io::IoResult<()> {
+    let (width, height) = (board_pixels(board.width()), board_pixels(board.height()));
+    let extra = if hint.is_some() { ANNOTATION_HEIGHT } else { 0 };
+    try!(writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" \
+                        height=\"{}\" viewBox=\"0 0 {} {}\" \
+                        font-family=\"Clear Sans, Helvetica Neue, Arial, sans-serif\" \
+                        font-weight=\"bold\" text-anchor=\"middle\">",
+                  width, height + extra, width, height + extra));
+    try!(writeln!(out, "<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>",
+                  width, height + extra, hex(PAGE)));
+    try!(writeln!(out, "<rect width=\"{}\" height=\"{}\" rx=\"6\" fill=\"{}\"/>",
+                  width, height, hex(BACKGROUND)));
+    for y in range(0, board.height()) {
+        for x in range(0, board.width()) {
+            let tile = board.get(x, y);
+            let fill = if board.is_blocker(x, y) { HIGHLIGHT } else { tile_colour(tile) };
+            try!(writeln!(out, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+                                rx=\"3\" fill=\"{}\"/>",
+                          cell_offset(x), cell_offset(y), TILE_SIZE, TILE_SIZE, hex(fill)));
+            if tile > 0 && !board.is_blocker(x, y) {
+                let label = format!("{}", 1u64 << tile as uint);
+                let size = match label.len() {
+                    1 | 2 => 32u,
+                    3 => 26,
+                    4 => 20,
+                    _ => 15
+                };
+                try!(writeln!(out, "<text x=\"{}\" y=\"{}\" font-size=\"{}\" \
+                                    dominant-baseline=\"central\" fill=\"{}\">{}</text>",
+                              cell_offset(x) + TILE_SIZE / 2, cell_offset(y) + TILE_SIZE / 2,
+                              size, hex(text_colour(tile)), label));
+            }
+        }
+    }
+    match hint {
+        Some(hint) => {
+            let box_width = (width - 5 * GAP) / 4;
+            for (i, dir) in ALL_DIRECTIONS.iter().enumerate() {
+                let left = GAP + i * (box_width + GAP);
+                let recommended = hint.best.map_or(false, |best| best as uint == i);
+                let (fill, text) = if recommended {
+                    (HIGHLIGHT, LIGHT_TEXT)
+                } else {
+                    (EMPTY_CELL, DARK_TEXT)
+                };
+                let score = match hint.score(*dir) {
+                    Some(score) => format!("{}", score),
+                    None => String::from_str("-")
+                };
+                try!(writeln!(out, "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+                                    rx=\"3\" fill=\"{}\"/>",
+                              left, height, box_width, ANNOTATION_HEIGHT - GAP, hex(fill)));
+                try!(writeln!(out, "<text x=\"{}\" y=\"{}\" font-size=\"12\" \
+                                    fill=\"{}\">{}</text>",
+                              left + box_width / 2, height + 16, hex(text),
+                              direction_name(*dir)));
+                try!(writeln!(out, "<text x=\"{}\" y=\"{}\" font-size=\"10\" \
+                                    font-weight=\"normal\" fill=\"{}\">{}</text>",
+                              left + box_width / 2, height + 32, hex(text), score));
+            }
+        }
+        None => ()
+    }
+    writeln!(out, "</svg>")
+}
+
+/// Writes an animated PNG (APNG), frame by frame.
+pub struct Animation<'a, W> {
+    out: &'a mut W,
+    width: uint,
+    height: uint,
+    /// The frames which are still to come.
+    frames: uint,
+    /// The sequence number of the next fcTL or fdAT chunk.
+    sequence: u32
+}
+
+impl<'a, W: Writer> Animation<'a, W> {
+    /// Start an animation of *frames* frames of the given size, which
+    /// loops forever.
+    pub fn start(out: &'a mut W, width: uint, height: uint,
+                 frames: uint) -> io::IoResult<Animation<'a, W>> {
+        assert!(frames >= 1);
+        try!(write_png_header(out, width, height));
+        let mut control = io::MemWriter::new();
+        try!(control.write_be_u32(frames as u32));
+        try!(control.write_be_u32(0));
+        try!(write_chunk(out, "acTL", control.get_ref()));
+        Ok(Animation { out: out, width: width, height: height,
+                       frames: frames, sequence: 0 })
+    }
+
+    /// Add *canvas* as the next frame, shown for *delay_ms*.
+    pub fn frame(&mut self, canvas: &Canvas, delay_ms: u16) -> io::IoResult<()> {
+        assert!(self.frames > 0);
+        assert!(canvas.width == self.width && canvas.height == self.height);
+        let mut control = io::MemWriter::new();
+        try!(control.write_be_u32(self.sequence));
+        try!(control.write_be_u32(self.width as u32));
+        try!(control.write_be_u32(self.height as u32));
+        try!(control.write_be_u32(0));
+        try!(control.write_be_u32(0));
+        try!(control.write_be_u16(delay_ms));
+        try!(control.write_be_u16(1000));
+        // no disposal, and the frame replaces the picture
+        try!(control.write_u8(0));
+        try!(control.write_u8(0));
+        try!(write_chunk(self.out, "fcTL", control.get_ref()));
+        let data = zlib(canvas.scanlines().as_slice(), self.width + 1);
+        if self.sequence == 0 {
+            try!(write_chunk(self.out, "IDAT", data.as_slice()));
+            self.sequence += 1;
+        } else {
+            let mut chunk = io::MemWriter::with_capacity(data.len() + 4);
+            try!(chunk.write_be_u32(self.sequence + 1));
+            try!(chunk.write(data.as_slice()));
+            try!(write_chunk(self.out, "fdAT", chunk.get_ref()));
+            self.sequence += 2;
+        }
+        self.frames -= 1;
+        Ok(())
+    }
+
+    /// End the file after the last frame.
+    pub fn finish(self) -> io::IoResult<()> {
+        assert!(self.frames == 0);
+        write_chunk(self.out, "IEND", [])
+    }
+}
+
+/// Animate moves *first* to *last* (counted from zero, *last*
+/// excluded) of *record*, replayed under the rules of *config*: each
+/// move shows the tiles sliding and merging, then the board after the
+/// spawn for *hold_ms*.
+pub fn write_game_apng<W: Writer>(record: &GameRecord, config: &Config,
+                                  first: uint, last: uint, hold_ms: u16,
+                                  out: &mut W) -> Result<(), AiError> {
+    if first > last || last > record.moves.len() {
+        return Err(ConfigFailure(format!(
+            "the record has {} moves, cannot animate moves {} to {}",
+            record.moves.len(), first, last)));
+    }
+    let mut game = try!(config.new_game(record.board.width(), record.board.height(),
+                                        record.seed));
+    let invalid = |i: uint| ConfigFailure(format!(
+        "move {} of the record is invalid, was it played under other rules?", i + 1));
+    for i in range(0, first) {
+        if game.play(*record.moves.get(i)).is_none() {
+            return Err(invalid(i));
+        }
+    }
+
+    let (width, height) = (board_pixels(game.board().width()),
+                           board_pixels(game.board().height()));
+    let frames = 1 + (last - first) * (SLIDE_FRAMES + 2);
+    let mut animation = try!(Animation::start(out, width, height, frames)
+                             .map_err(|e| IoFailure(e)));
+    try!(animation.frame(&draw_board(game.board(), None), hold_ms)
+         .map_err(|e| IoFailure(e)));
+    for i in range(first, last) {
+        let dir = *record.moves.get(i);
+        let board = game.board().clone();
+        let motions = board.motions(dir);
+        for step in range(1, SLIDE_FRAMES + 1) {
+            let mut canvas = Canvas::new(width, height, PAGE);
+            draw_grid(&mut canvas, &board);
+            for motion in motions.iter() {
+                let ((from_x, from_y), (to_x, to_y)) = (motion.from, motion.to);
+                let position = |from: uint, to: uint| {
+                    let (from, to) = (cell_offset(from), cell_offset(to));
+                    if to >= from {
+                        from + (to - from) * step / SLIDE_FRAMES
+                    } else {
+                        from - (from - to) * step / SLIDE_FRAMES
+                    }
+                };
+                draw_tile(&mut canvas, position(from_x, to_x), position(from_y, to_y),
+                          motion.tile, 0);
+            }
+            try!(animation.frame(&canvas, SLIDE_MS).map_err(|e| IoFailure(e)));
+        }
+
+        let (shifted, _) = board.shifted_board(dir);
+        let mut canvas = draw_board(&shifted, None);
+        for motion in motions.iter().filter(|motion| motion.merged) {
+            let (x, y) = motion.to;
+            draw_tile(&mut canvas, cell_offset(x), cell_offset(y), shifted.get(x, y),
+                      POP_SIZE);
+        }
+        try!(animation.frame(&canvas, POP_MS).map_err(|e| IoFailure(e)));
+
+        if game.play(dir).is_none() {
+            return Err(invalid(i));
+        }
+        try!(animation.frame(&draw_board(game.board(), None), hold_ms)
+             .map_err(|e| IoFailure(e)));
+    }
+    animation.finish().map_err(|e| IoFailure(e))
+}
+
+/// The PNG signature and the IHDR and PLTE chunks of an 8 bit palette
+/// picture.
+fn write_png_header<W: Writer>(out: &mut W, width: uint, height: uint) -> io::IoResult<()> {
+    try!(out.write([0x89u8, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a]));
+    let mut header = io::MemWriter::new();
+    try!(header.write_be_u32(width as u32));
+    try!(header.write_be_u32(height as u32));
+    // bit depth 8, palette colours, deflate, filters per line, no interlace
+    try!(header.write([8u8, 3, 0, 0, 0]));
+    try!(write_chunk(out, "IHDR", header.get_ref()));
+    let mut palette = Vec::new();
+    for colour in PALETTE.iter() {
+        palette.push_all([(*colour >> 16) as u8, (*colour >> 8) as u8, *colour as u8]);
+    }
+    write_chunk(out, "PLTE", palette.as_slice())
+}
+
+fn write_chunk<W: Writer>(out: &mut W, kind: &str, data: &[u8]) -> io::IoResult<()> {
+    try!(out.write_be_u32(data.len() as u32));
+    try!(out.write_str(kind));
+    try!(out.write(data));
+    out.write_be_u32(!crc32(crc32(0xffffffff, kind.as_bytes()), data))
+}
+
+/// Update the running CRC-32 *crc* (ISO 3309, as used by PNG) with
+/// *data*. The CRC starts out as all ones and is inverted at the end.
+fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = crc;
+    for byte in data.iter() {
+        crc ^= *byte as u32;
+        for _ in range(0u, 8) {
+            crc = if crc & 1 != 0 { 0xedb88320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data.iter() {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+static LENGTH_BASE: [uint, ..29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59,
+    67, 83, 99, 115, 131, 163, 195, 227, 258];
+static LENGTH_EXTRA: [uint, ..29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3,
+    4, 4, 4, 4, 5, 5, 5, 5, 0];
+static DISTANCE_BASE: [uint, ..30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769,
+    1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+static DISTANCE_EXTRA: [uint, ..30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8,
+    9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+static MAX_MATCH: uint = 258;
+
+/// Collects bits from the least significant end, as deflate packs
+/// them.
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    count: uint
+}
+
+impl BitWriter {
+    fn bits(&mut self, value: uint, count: uint) {
+        self.acc |= (value as u32) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Write a Huffman code, which goes most significant bit first.
+    fn code(&mut self, code: uint, count: uint) {
+        let mut reversed = 0;
+        for i in range(0, count) {
+            reversed = (reversed << 1) | ((code >> i) & 1);
+        }
+        self.bits(reversed, count);
+    }
+
+    /// Write a literal or length symbol with the fixed Huffman codes.
+    fn symbol(&mut self, symbol: uint) {
+        match symbol {
+            0..143 => self.code(0x30 + symbol, 8),
+            144..255 => self.code(0x190 + symbol - 144, 9),
+            256..279 => self.code(symbol - 256, 7),
+            _ => self.code(0xc0 + symbol - 280, 8)
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+/// The index of the last entry of *bases* not above *value*.
+fn base_index(bases: &[uint], value: uint) -> uint {
+    let mut i = bases.len() - 1;
+    while bases[i] > value {
+        i -= 1;
+    }
+    i
+}
+
+/// Compress *data* into a zlib stream of one deflate block with the
+/// fixed Huffman codes. Matches are only searched one byte and one
+/// *stride* back, which finds the runs of flat colour and the rows
+/// repeating the one above that make up our pictures.
+fn zlib(data: &[u8], stride: uint) -> Vec<u8> {
+    let mut bits = BitWriter { out: vec![0x78u8, 0x01], acc: 0, count: 0 };
+    // a final block with fixed codes
+    bits.bits(1, 1);
+    bits.bits(1, 2);
+    let mut pos = 0;
+    while pos < data.len() {
+        let (mut length, mut distance) = (0, 0);
+        for d in [1, stride].iter() {
+            if *d > pos || *d > 32768 {
+                continue;
+            }
+            let mut l = 0;
+            while l < MAX_MATCH && pos + l < data.len() && data[pos + l] == data[pos + l - *d] {
+                l += 1;
+            }
+            if l > length {
+                length = l;
+                distance = *d;
+            }
+        }
+        if length >= 3 {
+            let i = base_index(LENGTH_BASE, length);
+            bits.symbol(257 + i);
+            bits.bits(length - LENGTH_BASE[i], LENGTH_EXTRA[i]);
+            let j = base_index(DISTANCE_BASE, distance);
+            bits.code(j, 5);
+            bits.bits(distance - DISTANCE_BASE[j], DISTANCE_EXTRA[j]);
+            pos += length;
+        } else {
+            bits.symbol(data[pos] as uint);
+            pos += 1;
+        }
+    }
+    bits.symbol(256);
+    let mut out = bits.finish();
+    let checksum = adler32(data);
+    out.push_all([(checksum >> 24) as u8, (checksum >> 16) as u8,
+                  (checksum >> 8) as u8, checksum as u8]);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::MemWriter;
+    use std::str;
+
+    use board::{Board, Up, Down, Left, Right};
+    use config::Config;
+    use hint::Hint;
+    use record::GameRecord;
+    use super::{draw_board, write_svg, write_game_apng, crc32, adler32, zlib, compact};
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(!crc32(0xffffffff, "IEND".as_bytes()), 0xae426082);
+        assert_eq!(adler32("Wikipedia".as_bytes()), 0x11e60398);
+    }
+
+    #[test]
+    fn test_zlib() {
+        // rows of flat colour and a pattern, each row repeating the last
+        let data = Vec::from_fn(1000, |i| match i % 100 {
+            x if x < 60 => 7u8,
+            x => (x % 3) as u8
+        });
+        let compressed = zlib(data.as_slice(), 100);
+        assert_eq!(compressed.slice_to(2), [0x78u8, 0x01].as_slice());
+        assert!(compressed.len() < data.len() / 4);
+        let checksum = adler32(data.as_slice());
+        let n = compressed.len();
+        assert_eq!(compressed.slice_from(n - 4),
+                   [(checksum >> 24) as u8, (checksum >> 16) as u8,
+                    (checksum >> 8) as u8, checksum as u8].as_slice());
+    }
+
+    #[test]
+    fn test_png() {
+        let board = Board::from_raw(&vec![1u8, 2, 3, 11,
+                                          0, 0, 0, 12,
+                                          0, 0, 0, 0,
+                                          0, 0, 0, 0]).ok().unwrap();
+        let canvas = draw_board(&board, None);
+        assert_eq!(canvas.width, 4 * 64 + 5 * 8);
+        assert_eq!(canvas.height, canvas.width);
+        let mut out = MemWriter::new();
+        canvas.write_png(&mut out).unwrap();
+        let data = out.unwrap();
+        assert_eq!(data.slice_to(8),
+                   [0x89u8, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a].as_slice());
+        assert_eq!(data.slice_from(data.len() - 8),
+                   [0x49u8, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82].as_slice());
+    }
+
+    #[test]
+    fn test_svg() {
+        let mut board = Board::from_raw(&vec![1u8, 2, 0, 0,
+                                              0, 0, 0, 0,
+                                              0, 0, 0, 0,
+                                              0, 0, 0, 11]).ok().unwrap();
+        board.set_blocker(2, 2);
+        let hint = Hint { scores: [Some(12), None, Some(-3), None],
+                          best: Some(Up),
+                          depth: 2 };
+        let mut out = MemWriter::new();
+        write_svg(&board, Some(&hint), &mut out).unwrap();
+        let data = out.unwrap();
+        let svg = str::from_utf8(data.as_slice()).unwrap();
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains(">2048</text>"));
+        // the blocker
+        assert!(svg.contains("fill=\"#8f7a66\""));
+        assert!(svg.contains(">-3</text>"));
+        assert!(svg.trim_right().ends_with("</svg>"));
+        assert!(draw_board(&board, Some(&hint)).height > draw_board(&board, None).height);
+    }
+
+    #[test]
+    fn test_game_apng() {
+        let config = Config::default();
+        let mut game = config.new_game(4, 4, 5).ok().unwrap();
+        let mut record = GameRecord::new("test", 5, &game);
+        for _ in range(0u, 3) {
+            let dir = [Up, Left, Down, Right].iter().map(|d| *d)
+                .find(|d| game.play(*d).is_some()).unwrap();
+            record.push(dir, 0, &game);
+        }
+        let mut out = MemWriter::new();
+        write_game_apng(&record, &config, 1, 3, 200, &mut out).ok().unwrap();
+        let data = out.unwrap();
+        let count = |kind: &str| range(0, data.len() - 3)
+            .filter(|i| data.slice(*i, *i + 4) == kind.as_bytes()).count();
+        assert_eq!(count("acTL"), 1);
+        assert_eq!(count("fcTL"), 1 + 2 * 6);
+        assert_eq!(count("IDAT"), 1);
+        assert!(write_game_apng(&record, &config, 2, 4, 200, &mut MemWriter::new()).is_err());
+    }
+
+    #[test]
+    fn test_compact() {
+        assert_eq!(compact(-42), String::from_str("-42"));
+        assert_eq!(compact(123456), String::from_str("123k"));
+        assert_eq!(compact(45000000), String::from_str("45M"));
+    }
+}
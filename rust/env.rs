@@ -0,0 +1,388 @@
+use std::cmp;
+
+use board::{Direction, Up, Down, Left, Right, MAX_EXPONENT, max_tile};
+use game::Game;
+use rules::{Rules, RulesError};
+
+static ALL_DIRECTIONS: [Direction, ..4] = [Up, Down, Left, Right];
+
+/// The value of a blocker in the RawExponents encoding.
+pub static RAW_BLOCKER: f32 = -1.0;
+
+/// How a board is turned into the numbers an agent observes.
+#[deriving(Clone, Show, Eq)]
+pub enum Encoding {
+    /// One value per cell, the tile exponent, in row-major order;
+    /// RAW_BLOCKER for blockers.
+    RawExponents,
+    /// One plane of width times height values per tile exponent, from
+    /// zero (empty cells) to the largest tile which can be built under
+    /// the rules; a cell is 1 in the plane of its exponent and 0 in the
+    /// others. If the rules place blockers, one more plane follows,
+    /// which is 1 on the blockers; they are 0 in all tile planes.
+    /// Planes are stored one after another, each in row-major order.
+    OneHotPlanes
+}
+
+/// What happened in a step besides the reward.
+#[deriving(Clone, Show)]
+pub struct StepInfo {
+    /// The score of the game, as the game displays it.
+    pub score: u64,
+    pub max_tile: u8,
+    pub moves: uint,
+    /// The action did not change the board, so nothing happened.
+    pub invalid_action: bool,
+    /// The game ended by building the target tile of the rules.
+    pub won: bool
+}
+
+/// The result of a step: the points gained by the merges of the move
+/// as reward, and whether the game is over.
+#[deriving(Clone, Show)]
+pub struct Step {
+    pub reward: f64,
+    pub done: bool,
+    pub info: StepInfo
+}
+
+/// A game as an environment for reinforcement learning. Actions are the
+/// directions in the order of the AI protocol: 0 up, 1 down, 2 left
+/// and 3 right.
+pub struct Env {
+    game: Game,
+    encoding: Encoding
+}
+
+impl Env {
+    /// Create an environment on a board of the given size. It starts
+    /// with the game of seed zero; call reset to pick another.
+    pub fn new(width: uint, height: uint, rules: Rules,
+               encoding: Encoding) -> Result<Env, RulesError> {
+        Ok(Env { game: try!(Game::new(width, height, rules, 0)),
+                 encoding: encoding })
+    }
+
+    pub fn game<'a>(&'a self) -> &'a Game {
+        &self.game
+    }
+
+    /// The number of values in an observation.
+    pub fn observation_size(&self) -> uint {
+        let cells = self.game.board().cells();
+        match self.encoding {
+            RawExponents => cells,
+            OneHotPlanes => cells * (self.planes() + self.blocker_planes())
+        }
+    }
+
+    /// The number of planes for blockers, one if the rules place any.
+    fn blocker_planes(&self) -> uint {
+        if self.game.rules().blockers.is_empty() { 0 } else { 1 }
+    }
+
+    /// The number of one-hot planes. Larger spawn tiles than those of
+    /// the original game build larger tiles than max_tile: each cell can
+    /// hold one tile of a chain down to the high tile. Tiles beyond
+    /// MAX_EXPONENT, which no game gets to, share the last plane.
+    fn planes(&self) -> uint {
+        let board = self.game.board();
+        let chain = cmp::min(self.game.rules().high_tile as uint + board.cells() - 1,
+                             MAX_EXPONENT as uint);
+        cmp::max(max_tile(board.width(), board.height()) as uint, chain) + 1
+    }
+
+    /// Start a new game, whose spawns are drawn with *seed*, and return
+    /// its observation.
+    pub fn reset(&mut self, seed: u32) -> Vec<f32> {
+        let (width, height) = {
+            let board = self.game.board();
+            (board.width(), board.height())
+        };
+        let rules = self.game.rules().clone();
+        // the rules were checked when the environment was created
+        self.game = Game::new(width, height, rules, seed).ok().unwrap();
+        self.observation()
+    }
+
+    /// Play *action* and return the new observation with the outcome.
+    /// An invalid action leaves the game as it is and gains nothing;
+    /// see action_mask to avoid them.
+    pub fn step(&mut self, action: uint) -> (Vec<f32>, Step) {
+        let step = self.play(action);
+        (self.observation(), step)
+    }
+
+    fn play(&mut self, action: uint) -> Step {
+        assert!(action < ALL_DIRECTIONS.len());
+        let points = self.game.play(ALL_DIRECTIONS[action]);
+        Step { reward: points.unwrap_or(0) as f64,
+               done: self.game.is_over(),
+               info: StepInfo { score: self.game.score(),
+                                max_tile: self.game.board().highest_tile(),
+                                moves: self.game.moves(),
+                                invalid_action: points.is_none(),
+                                won: self.game.won() } }
+    }
+
+    pub fn observation(&self) -> Vec<f32> {
+        let mut out = Vec::from_elem(self.observation_size(), 0.0f32);
+        self.observe(out.as_mut_slice());
+        out
+    }
+
+    /// Write the observation to *out*, which holds observation_size
+    /// values.
+    pub fn observe(&self, out: &mut [f32]) {
+        assert_eq!(out.len(), self.observation_size());
+        let board = self.game.board();
+        let cells = board.cells();
+        let planes = self.planes();
+        if self.encoding == OneHotPlanes {
+            for value in out.mut_iter() {
+                *value = 0.0;
+            }
+        }
+        for y in range(0u, board.height()) {
+            for x in range(0u, board.width()) {
+                let cell = y * board.width() + x;
+                let tile = board.get(x, y);
+                match self.encoding {
+                    RawExponents if board.is_blocker(x, y) => out[cell] = RAW_BLOCKER,
+                    RawExponents => out[cell] = tile as f32,
+                    OneHotPlanes if board.is_blocker(x, y) => out[planes * cells + cell] = 1.0,
+                    OneHotPlanes =>
+                        out[cmp::min(tile as uint, planes - 1) * cells + cell] = 1.0
+                }
+            }
+        }
+    }
+
+    /// Which actions change the board, indexed like the actions. The
+    /// search skips the same moves as invalid. All are false once the
+    /// game is over.
+    pub fn action_mask(&self) -> [bool, ..4] {
+        let mut mask = [false, ..4];
+        if self.game.won() {
+            return mask;
+        }
+        let board = self.game.board();
+        for (i, dir) in ALL_DIRECTIONS.iter().enumerate() {
+            let (new_board, _) = board.shifted_board(*dir);
+            mask[i] = new_board != *board;
+        }
+        mask
+    }
+}
+
+/// Several environments stepped together, for agents which act on a
+/// batch of games. Observations, rewards and done flags are written to
+/// buffers holding one entry per environment. An environment whose
+/// game ends is reset right away; the step reports the end and the
+/// final info, and the observation is the one of the new game.
+pub struct VecEnv {
+    envs: Vec<Env>,
+    next_seed: u32
+}
+
+impl VecEnv {
+    pub fn new(count: uint, width: uint, height: uint, rules: Rules,
+               encoding: Encoding) -> Result<VecEnv, RulesError> {
+        assert!(count >= 1);
+        let mut envs = Vec::with_capacity(count);
+        for _ in range(0, count) {
+            envs.push(try!(Env::new(width, height, rules.clone(), encoding)));
+        }
+        Ok(VecEnv { envs: envs, next_seed: 0 })
+    }
+
+    pub fn len(&self) -> uint {
+        self.envs.len()
+    }
+
+    pub fn env<'a>(&'a self, i: uint) -> &'a Env {
+        self.envs.get(i)
+    }
+
+    pub fn observation_size(&self) -> uint {
+        self.envs.get(0).observation_size()
+    }
+
+    /// Reset all environments, the i-th with seed *seed* + i; the games
+    /// started by automatic resets get the seeds following those. The
+    /// observations go to *obs*.
+    pub fn reset(&mut self, seed: u32, obs: &mut [f32]) {
+        self.next_seed = seed;
+        for i in range(0, self.envs.len()) {
+            self.reset_env(i);
+        }
+        self.observe(obs);
+    }
+
+    fn reset_env(&mut self, i: uint) {
+        let seed = self.next_seed;
+        self.next_seed += 1;
+        self.envs.get_mut(i).reset(seed);
+    }
+
+    /// Play *actions*, one per environment, and write the outcomes.
+    /// Returns the info of each step.
+    pub fn step(&mut self, actions: &[uint], obs: &mut [f32],
+                rewards: &mut [f64], dones: &mut [bool]) -> Vec<StepInfo> {
+        assert_eq!(actions.len(), self.envs.len());
+        let mut infos = Vec::with_capacity(self.envs.len());
+        for (i, action) in actions.iter().enumerate() {
+            let step = self.envs.get_mut(i).play(*action);
+            if step.done {
+                self.reset_env(i);
+            }
+            rewards[i] = step.reward;
+            dones[i] = step.done;
+            infos.push(step.info);
+        }
+        self.observe(obs);
+        infos
+    }
+
+    fn observe(&self, obs: &mut [f32]) {
+        let size = self.observation_size();
+        assert_eq!(obs.len(), size * self.envs.len());
+        for (i, env) in self.envs.iter().enumerate() {
+            env.observe(obs.mut_slice(i * size, (i + 1) * size));
+        }
+    }
+
+    /// The action masks of all environments, four entries each.
+    pub fn action_masks(&self, masks: &mut [bool]) {
+        assert_eq!(masks.len(), 4 * self.envs.len());
+        for (i, env) in self.envs.iter().enumerate() {
+            let mask = env.action_mask();
+            for j in range(0u, 4) {
+                masks[4 * i + j] = mask[j];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::iter::AdditiveIterator;
+    use rules::Rules;
+    use super::{Env, VecEnv, RawExponents, OneHotPlanes, RAW_BLOCKER};
+
+    #[test]
+    fn test_env() {
+        let mut env = Env::new(4, 4, Rules::default(), RawExponents).ok().unwrap();
+        let obs = env.reset(3);
+        assert_eq!(obs.len(), 16);
+        assert_eq!(obs.iter().filter(|v| **v != 0.0).count(), 2);
+        assert!(obs == env.reset(3));
+
+        let mut total = 0.0;
+        let mut done = false;
+        while !done {
+            let mask = env.action_mask();
+            let action = range(0u, 4).find(|a| mask[*a]).unwrap();
+            let (_, step) = env.step(action);
+            assert!(!step.info.invalid_action);
+            total += step.reward;
+            done = step.done;
+            assert_eq!(step.info.score as f64, total);
+        }
+        assert!(env.action_mask().iter().all(|valid| !*valid));
+        let (obs, step) = env.step(0);
+        assert!(step.info.invalid_action && step.done);
+        assert_eq!(step.reward, 0.0);
+        assert!(obs == env.observation());
+    }
+
+    #[test]
+    fn test_one_hot() {
+        let mut env = Env::new(3, 3, Rules::default(), OneHotPlanes).ok().unwrap();
+        let obs = env.reset(1);
+        // exponents 0 to 10 on a 3x3 board
+        assert_eq!(obs.len(), 9 * 11);
+        for cell in range(0u, 9) {
+            let hot = range(0u, 11).filter(|p| *obs.get(*p * 9 + cell) == 1.0).count();
+            assert_eq!(hot, 1);
+        }
+        assert_eq!(obs.iter().map(|v| *v).sum(), 9.0);
+        let empty = range(0u, 9).filter(|c| *obs.get(*c) == 1.0).count();
+        assert_eq!(empty, 7);
+    }
+
+    #[test]
+    fn test_one_hot_large_spawn_tiles() {
+        let mut rules = Rules::default();
+        rules.low_tile = 9;
+        rules.high_tile = 10;
+        let mut env = Env::new(3, 3, rules, OneHotPlanes).ok().unwrap();
+        // a chain from 2^18 down to the 2^10 spawned last
+        assert_eq!(env.observation_size(), 9 * 19);
+        let mut beyond = false;
+        for seed in range(0u32, 20) {
+            env.reset(seed);
+            while !env.game().is_over() {
+                let mask = env.action_mask();
+                let action = range(0u, 4).find(|a| mask[*a]).unwrap();
+                let (obs, step) = env.step(action);
+                assert_eq!(obs.iter().map(|v| *v).sum(), 9.0);
+                beyond = beyond || step.info.max_tile > 10;
+            }
+        }
+        assert!(beyond);
+    }
+
+    #[test]
+    fn test_blockers() {
+        let mut rules = Rules::default();
+        rules.blockers.push((1, 1));
+        let mut env = Env::new(3, 3, rules.clone(), RawExponents).ok().unwrap();
+        let obs = env.reset(2);
+        assert_eq!(obs.len(), 9);
+        assert_eq!(*obs.get(4), RAW_BLOCKER);
+        assert_eq!(obs.iter().filter(|v| **v > 0.0).count(), 2);
+
+        let mut env = Env::new(3, 3, rules, OneHotPlanes).ok().unwrap();
+        let obs = env.reset(2);
+        // the tile planes of exponents 0 to 10 and the blocker plane
+        assert_eq!(obs.len(), 9 * 12);
+        assert_eq!(*obs.get(11 * 9 + 4), 1.0);
+        assert_eq!(range(0u, 11).filter(|p| *obs.get(*p * 9 + 4) == 1.0).count(), 0);
+        assert_eq!(obs.slice_from(11 * 9).iter().map(|v| *v).sum(), 1.0);
+        assert_eq!(obs.iter().map(|v| *v).sum(), 9.0);
+    }
+
+    #[test]
+    fn test_vec_env() {
+        let mut envs = VecEnv::new(3, 3, 3, Rules::default(), RawExponents).ok().unwrap();
+        let size = envs.observation_size();
+        let mut obs = Vec::from_elem(3 * size, -1.0f32);
+        envs.reset(10, obs.as_mut_slice());
+        let mut single = Env::new(3, 3, Rules::default(), RawExponents).ok().unwrap();
+        assert!(obs.slice(size, 2 * size) == single.reset(11).as_slice());
+
+        let mut rewards = [0.0, ..3];
+        let mut dones = [false, ..3];
+        let mut masks = [false, ..12];
+        let mut finished = 0u;
+        for _ in range(0u, 2000) {
+            envs.action_masks(masks.as_mut_slice());
+            let actions: Vec<uint> = range(0u, 3).map(|i| {
+                range(0u, 4).find(|a| masks[4 * i + *a]).unwrap()
+            }).collect();
+            let infos = envs.step(actions.as_slice(), obs.as_mut_slice(),
+                                  rewards.as_mut_slice(), dones.as_mut_slice());
+            assert_eq!(infos.len(), 3);
+            for (i, info) in infos.iter().enumerate() {
+                assert!(!info.invalid_action);
+                if dones[i] {
+                    finished += 1;
+                    // the environment starts over right away
+                    assert_eq!(envs.env(i).game().moves(), 0);
+                }
+            }
+        }
+        assert!(finished > 0);
+    }
+}
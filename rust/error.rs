@@ -0,0 +1,56 @@
+use std::fmt;
+use std::io;
+
+use protocol::ProtocolError;
+use search::SearchError;
+use solver::SolverError;
+
+/// Exit status after the host closed the input.
+pub static EXIT_INPUT_CLOSED: int = 0;
+/// Exit status after the AI resigned because no move was left.
+pub static EXIT_RESIGNED: int = 1;
+/// Exit status for an invalid request, see ProtocolError.
+pub static EXIT_PROTOCOL: int = 2;
+/// Exit status for a failure to read requests, write responses or
+/// write the log.
+pub static EXIT_IO: int = 3;
+/// Exit status for invalid command line options.
+pub static EXIT_CONFIG: int = 4;
+/// Exit status for a search, or a solver, which could not produce a
+/// result.
+pub static EXIT_SEARCH: int = 5;
+
+/// Everything which can make the AI stop before the host closes its
+/// input.
+pub enum AiError {
+    ProtocolFailure(ProtocolError),
+    IoFailure(io::IoError),
+    ConfigFailure(String),
+    SearchFailure(SearchError),
+    SolverFailure(SolverError)
+}
+
+impl AiError {
+    /// The process exit status documented for this error.
+    pub fn exit_code(&self) -> int {
+        match *self {
+            ProtocolFailure(_) => EXIT_PROTOCOL,
+            IoFailure(_) => EXIT_IO,
+            ConfigFailure(_) => EXIT_CONFIG,
+            SearchFailure(_) => EXIT_SEARCH,
+            SolverFailure(_) => EXIT_SEARCH
+        }
+    }
+}
+
+impl fmt::Show for AiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProtocolFailure(ref e) => write!(f.buf, "protocol error: {}", e),
+            IoFailure(ref e) => write!(f.buf, "I/O error: {}", e),
+            ConfigFailure(ref msg) => write!(f.buf, "configuration error: {}", msg),
+            SearchFailure(ref e) => write!(f.buf, "search failure: {}", e),
+            SolverFailure(ref e) => write!(f.buf, "solver failure: {}", e)
+        }
+    }
+}
@@ -0,0 +1,176 @@
+extern crate g2048;
+extern crate rand;
+
+use std::io;
+use std::os;
+use std::task;
+use rand::{Rng, SeedableRng, XorShiftRng};
+
+use g2048::board::{MIN_SIZE, MAX_SIZE, max_tile};
+use g2048::protocol::{serve, EXT_SIZED_BOARD};
+use g2048::search::{DepthPolicy, EvalContext};
+
+static DEFAULT_ITERATIONS: uint = 10000;
+
+/// Generate a byte stream for the protocol loop. Most streams consist
+/// of well-formed requests, so that the searcher gets to see them,
+/// which are then randomly corrupted, interleaved with garbage and
+/// truncated.
+fn random_stream<R: Rng>(rng: &mut R) -> Vec<u8> {
+    let mut stream = Vec::new();
+    let frames = rng.gen_range(0u, 6);
+    for _ in range(0, frames) {
+        let mut frame = Vec::new();
+        if rng.gen_weighted_bool(4) {
+            let len = rng.gen_range(0u, 40);
+            for _ in range(0, len) {
+                frame.push(rng.gen::<u8>());
+            }
+        } else if rng.gen_weighted_bool(4) {
+            let width = rng.gen_range(MIN_SIZE, MAX_SIZE + 1);
+            let height = rng.gen_range(MIN_SIZE, MAX_SIZE + 1);
+            frame.push_all(Vec::from_elem(16, 0u8).as_slice());
+            frame.push(EXT_SIZED_BOARD);
+            frame.push(width as u8);
+            frame.push(height as u8);
+            for _ in range(0, width * height) {
+                frame.push(if rng.gen_weighted_bool(2) {
+                    0
+                } else {
+                    rng.gen_range(1u8, max_tile(width, height) + 1)
+                });
+            }
+        } else {
+            for _ in range(0u, 16) {
+                frame.push(if rng.gen_weighted_bool(2) {
+                    0
+                } else {
+                    rng.gen_range(1u8, max_tile(4, 4) + 1)
+                });
+            }
+            frame.push(0);
+        }
+
+        while rng.gen_weighted_bool(4) {
+            let idx = rng.gen_range(0u, frame.len() + 1);
+            if idx < frame.len() {
+                *frame.get_mut(idx) = rng.gen::<u8>();
+            }
+        }
+        stream.push_all(frame.as_slice());
+    }
+
+    if rng.gen_weighted_bool(3) {
+        let len = rng.gen_range(0u, stream.len() + 1);
+        stream.truncate(len);
+    }
+    stream
+}
+
+/// Run a stream through the protocol loop in a separate task. Returns
+/// a description of the problem if the task failed or the AI wrote
+/// something which is not a valid direction.
+fn check_stream(stream: Vec<u8>) -> Option<String> {
+    let result = task::try(proc() {
+        let mut ctx = EvalContext::new(1, 1.0, 0.6, 2);
+        let policy = DepthPolicy::new(1, 1, 2);
+        let mut input = io::MemReader::new(stream);
+        let mut output = io::MemWriter::new();
+        // any error is fine, as long as it is reported instead of
+        // bringing down the task
+        let _ = serve(&mut ctx, &policy, &mut input, &mut output, |_| ());
+        output.unwrap()
+    });
+
+    match result {
+        Ok(responses) => {
+            match responses.iter().find(|dir| **dir >= 4) {
+                Some(dir) => Some(format!("invalid direction {}", *dir)),
+                None => None
+            }
+        }
+        Err(_) => Some(format!("task failed"))
+    }
+}
+
+fn replay(paths: &[String]) -> bool {
+    let mut ok = true;
+    for path in paths.iter() {
+        let stream = match io::File::open(&Path::new(path.as_slice())).read_to_end() {
+            Ok(stream) => stream,
+            Err(e) => {
+                println!("{}: cannot read: {}", path, e);
+                ok = false;
+                continue;
+            }
+        };
+        match check_stream(stream) {
+            Some(problem) => {
+                println!("{}: {}", path, problem);
+                ok = false;
+            }
+            None => println!("{}: ok", path)
+        }
+    }
+    ok
+}
+
+fn fuzz(iterations: uint, seed: u32) -> bool {
+    let mut rng: XorShiftRng = SeedableRng::from_seed([seed, 0x2048, 0xf00, 0xba5]);
+    let mut failures = 0u;
+    for i in range(0, iterations) {
+        let stream = random_stream(&mut rng);
+        match check_stream(stream.clone()) {
+            Some(problem) => {
+                let name = format!("fuzz-crash-{}-{}.bin", seed, i);
+                println!("iteration {}: {}, input saved to {}",
+                         i, problem, name);
+                match io::File::create(&Path::new(name.as_slice()))
+                    .write(stream.as_slice())
+                {
+                    Ok(()) => (),
+                    Err(e) => println!("failed to save input: {}", e)
+                }
+                failures += 1;
+            }
+            None => ()
+        }
+    }
+    println!("{} iterations with seed {}, {} failures",
+             iterations, seed, failures);
+    failures == 0
+}
+
+fn main() {
+    let args = os::args();
+    let ok = if args.len() > 2 && args.get(1).as_slice() == "--replay" {
+        replay(args.slice_from(2))
+    } else if args.len() <= 3 {
+        let iterations = if args.len() > 1 {
+            from_str::<uint>(args.get(1).as_slice())
+        } else {
+            Some(DEFAULT_ITERATIONS)
+        };
+        let seed = if args.len() > 2 {
+            from_str::<u32>(args.get(2).as_slice())
+        } else {
+            Some(rand::task_rng().gen::<u32>())
+        };
+        match (iterations, seed) {
+            (Some(iterations), Some(seed)) => fuzz(iterations, seed),
+            _ => {
+                println!("usage: {} [ITERATIONS [SEED]] | --replay FILE...",
+                         args.get(0));
+                false
+            }
+        }
+    } else {
+        println!("usage: {} [ITERATIONS [SEED]] | --replay FILE...",
+                 args.get(0));
+        false
+    };
+
+    if !ok {
+        os::set_exit_status(1);
+    }
+}
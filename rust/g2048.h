@@ -0,0 +1,176 @@
+/* Generated from native.rs by gen_header.py, do not edit. */
+#ifndef G2048_H
+#define G2048_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct g2048_env g2048_env;
+typedef struct g2048_searcher g2048_searcher;
+
+/*
+ * Raised whenever a function changes or is removed.
+ */
+#define NATIVE_ABI_VERSION 1
+
+#define NATIVE_OK 0
+
+/*
+ * The size, the direction or another argument is out of range.
+ */
+#define NATIVE_INVALID_ARGUMENT (-1)
+
+/*
+ * A board holds a tile too large for its size.
+ */
+#define NATIVE_INVALID_BOARD (-2)
+
+/*
+ * The search could not produce a result.
+ */
+#define NATIVE_SEARCH_FAILED (-3)
+
+/*
+ * There is no valid move on the board.
+ */
+#define NATIVE_NO_MOVE (-4)
+
+/*
+ * The score reported for a move which does not change the board.
+ */
+#define NATIVE_INVALID_MOVE INT64_MIN
+
+/*
+ * Shift each board towards *dir* (0 up, 1 down, 2 left, 3 right, as
+ * in the protocol). The shifted boards go to *out* and the points of
+ * the merges, as the game counts them, to *points*.
+ */
+int g2048_shift(unsigned int width, unsigned int height, unsigned int count, const uint8_t *boards, unsigned int dir, uint8_t *out, uint64_t *points);
+
+/*
+ * Spawn a tile on a random empty cell of each board, in place, with
+ * the probabilities of the original game. The spawns are drawn from a
+ * generator seeded with *seed*. Boards without an empty cell are left
+ * as they are.
+ */
+int g2048_spawn(unsigned int width, unsigned int height, unsigned int count, uint8_t *boards, uint32_t seed);
+
+/*
+ * Store 1 in *out* for each board without a valid move, 0 otherwise.
+ */
+int g2048_game_over(unsigned int width, unsigned int height, unsigned int count, const uint8_t *boards, uint8_t *out);
+
+/*
+ * Create a searcher with the default configuration of the AI, running
+ * on *threads* threads. A *depth* of zero picks the depth per board
+ * like the AI does; otherwise every board is searched that deep.
+ * Returns null for zero threads. The searcher has to be released with
+ * g2048_searcher_free.
+ */
+g2048_searcher *g2048_searcher_new(unsigned int depth, unsigned int threads);
+
+/*
+ * Create a searcher from the search and rule options of the AI, e.g.
+ * --depth 4 --threads 2, given as *argc* strings in *argv*. The
+ * depth is picked per board by the depth options, as in the AI.
+ * Returns null if the options are invalid.
+ */
+g2048_searcher *g2048_searcher_configure(int argc, const char *const *argv);
+
+/*
+ * Release a searcher; null is ignored.
+ */
+void g2048_searcher_free(g2048_searcher *searcher);
+
+/*
+ * Search each board. The best move goes to *moves* (-1 if there is
+ * none) and the scores of all four moves to the four entries of
+ * *scores* per board, NATIVE_INVALID_MOVE for moves which do not
+ * change the board.
+ */
+int g2048_searcher_eval(g2048_searcher *searcher, unsigned int width, unsigned int height, unsigned int count, const uint8_t *boards, int8_t *moves, int64_t *scores);
+
+/*
+ * Advise on each board like the hints of the play binary: write the
+ * best move and the scores as g2048_searcher_eval does, the loss of
+ * each move to the four entries of *losses* per board, and the valid
+ * moves, best first, to the four entries of *ranking* per board, with
+ * -1 after the last valid move. The loss is the share by which a move
+ * is expected to score less than the best one, 0 for the best move
+ * and 0.25 for a move a quarter worse; it is NaN for moves which do
+ * not change the board, and for all moves if there is no best move.
+ */
+int g2048_searcher_hint(g2048_searcher *searcher, unsigned int width, unsigned int height, unsigned int count, const uint8_t *boards, int8_t *moves, int64_t *scores, double *losses, int8_t *ranking);
+
+/*
+ * Search a 4x4 board of 16 bytes, as sent in the AI protocol, and
+ * return the direction of the best move (0 up, 1 down, 2 left,
+ * 3 right), NATIVE_NO_MOVE or an error code.
+ */
+int g2048_searcher_move(g2048_searcher *searcher, const uint8_t *board);
+
+/*
+ * Create *count* environments for reinforcement learning on boards of
+ * the given size, under the rules of the original game. Observations
+ * are the tile exponents, or one-hot planes if *one_hot* is not zero;
+ * see g2048_env_observation_size. Returns null for invalid sizes or a
+ * count of zero. The environments have to be released with
+ * g2048_env_free.
+ */
+g2048_env *g2048_env_new(unsigned int count, unsigned int width, unsigned int height, int one_hot);
+
+/*
+ * Like g2048_env_new, but under the rules given by the game variant
+ * options of the AI, e.g. --blocker 1,1 --target 11, as *argc*
+ * strings in *argv*. Blockers are -1 in the tile exponents and have a
+ * plane of their own after the one-hot planes of the tiles. Returns
+ * null if the options are invalid or the rules cannot be played on
+ * the board.
+ */
+g2048_env *g2048_env_configure(unsigned int count, unsigned int width, unsigned int height, int one_hot, int argc, const char *const *argv);
+
+/*
+ * Release environments; null is ignored.
+ */
+void g2048_env_free(g2048_env *envs);
+
+/*
+ * The number of values in the observation of one environment.
+ */
+unsigned int g2048_env_observation_size(g2048_env *envs);
+
+/*
+ * Start new games in all environments, the i-th with seed *seed* + i,
+ * and write their observations to *obs*, one after another.
+ */
+int g2048_env_reset(g2048_env *envs, uint32_t seed, float *obs);
+
+/*
+ * Play one action per environment (0 up, 1 down, 2 left, 3 right)
+ * and write the new observations, the rewards, whether each game
+ * ended and the scores of the games. Games which end are started over
+ * right away with the next seeds; their score is the final one, their
+ * observation the one of the new game. Invalid moves change nothing
+ * and gain nothing.
+ */
+int g2048_env_step(g2048_env *envs, const uint8_t *actions, float *obs, double *rewards, uint8_t *dones, uint64_t *scores);
+
+/*
+ * Write four entries per environment to *masks*: 1 for the actions
+ * which change the board, 0 for the others.
+ */
+int g2048_env_action_masks(g2048_env *envs, uint8_t *masks);
+
+/*
+ * The ABI version the library implements, NATIVE_ABI_VERSION.
+ */
+unsigned int g2048_abi_version(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,315 @@
+use rand::{Rng, SeedableRng, XorShiftRng};
+
+use board::{Board, Direction, Up, Down, Left, Right, MAX_CELLS};
+use rules::{Rules, RulesError, RandomSpawns, AdversarialSpawns};
+use search::{EvalContext, Minimax};
+
+static ALL_DIRECTIONS: [Direction, ..4] = [Up, Down, Left, Right];
+
+/// The spawn generator of a game, which counts its draws so that it
+/// can be rewound.
+struct SpawnRng {
+    rng: XorShiftRng,
+    draws: u64
+}
+
+impl SpawnRng {
+    fn new(seed: u32) -> SpawnRng {
+        SpawnRng { rng: SeedableRng::from_seed([seed, 0x2048, 0x9a3e, 0x5eed]),
+                   draws: 0 }
+    }
+}
+
+impl Rng for SpawnRng {
+    fn next_u32(&mut self) -> u32 {
+        self.draws += 1;
+        self.rng.next_u32()
+    }
+}
+
+/// The state of a game between two moves, to take moves back.
+#[deriving(Clone)]
+pub struct Checkpoint {
+    board: Board,
+    score: u64,
+    moves: uint,
+    draws: u64
+}
+
+/// A game in progress under a set of rules. The spawns are drawn from
+/// a generator seeded when the game starts, so that a game is
+/// repeated exactly by playing the same moves with the same seed.
+pub struct Game {
+    board: Board,
+    rules: Rules,
+    seed: u32,
+    rng: SpawnRng,
+    /// Picks the spawns under adversarial rules.
+    adversary: EvalContext,
+    score: u64,
+    moves: uint
+}
+
+impl Game {
+    /// Start a game on a board of the given size, with the start tiles
+    /// of *rules* spawned.
+    pub fn new(width: uint, height: uint, rules: Rules,
+               seed: u32) -> Result<Game, RulesError> {
+        try!(rules.check(width, height));
+        let mut adversary = EvalContext::new(1, 1.0, 0.6, 2);
+        adversary.mode = Minimax;
+        adversary.rules = rules.clone();
+        let mut game = Game {
+            board: rules.empty_board(width, height),
+            rules: rules,
+            seed: seed,
+            rng: SpawnRng::new(seed),
+            adversary: adversary,
+            score: 0,
+            moves: 0
+        };
+        let start_tiles = game.rules.start_tiles;
+        game.spawn(start_tiles);
+        Ok(game)
+    }
+
+    /// Let the adversary look *depth* moves ahead when picking spawns
+    /// under adversarial rules. By default, it only looks at the next
+    /// move, which is cheap enough to play against.
+    pub fn set_adversary_depth(&mut self, depth: uint) {
+        assert!(depth >= 1);
+        self.adversary.max_depth = depth;
+    }
+
+    pub fn board<'a>(&'a self) -> &'a Board {
+        &self.board
+    }
+
+    pub fn rules<'a>(&'a self) -> &'a Rules {
+        &self.rules
+    }
+
+    /// The points collected so far, as the game displays them.
+    pub fn score(&self) -> u64 {
+        self.score
+    }
+
+    /// The number of moves played so far.
+    pub fn moves(&self) -> uint {
+        self.moves
+    }
+
+    /// Whether the target tile of the rules was built.
+    pub fn won(&self) -> bool {
+        self.rules.reached_target(&self.board)
+    }
+
+    /// Whether the game has ended, either won or without a valid move.
+    pub fn is_over(&self) -> bool {
+        self.won() || !has_move(&self.board)
+    }
+
+    /// Save the current state, to return to it with restore.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint { board: self.board.clone(),
+                     score: self.score,
+                     moves: self.moves,
+                     draws: self.rng.draws }
+    }
+
+    /// Return to the state of *checkpoint*, taken from this game. The
+    /// spawn generator is rewound too, so that the game still follows
+    /// from its seed and the moves which were not taken back.
+    pub fn restore(&mut self, checkpoint: &Checkpoint) {
+        self.board = checkpoint.board.clone();
+        self.score = checkpoint.score;
+        self.moves = checkpoint.moves;
+        self.rng = SpawnRng::new(self.seed);
+        for _ in range(0, checkpoint.draws) {
+            self.rng.next_u32();
+        }
+    }
+
+    /// Play *dir* and spawn the tiles after it. Returns the points
+    /// gained, or None if the move is invalid or the game is over, in
+    /// which case nothing changes.
+    pub fn play(&mut self, dir: Direction) -> Option<u64> {
+        if self.won() {
+            return None;
+        }
+        let (new_board, points) = self.board.shifted_with_points(dir);
+        if new_board == self.board {
+            return None;
+        }
+        self.board = new_board;
+        self.score += points;
+        self.moves += 1;
+        if !self.won() {
+            let spawns = self.rules.spawns_per_move;
+            self.spawn(spawns);
+        }
+        Some(points)
+    }
+
+    /// Spawn up to *count* tiles, fewer if the board fills up.
+    fn spawn(&mut self, count: uint) {
+        for _ in range(0, count) {
+            let mut cells = [(0u, 0u), ..MAX_CELLS];
+            let free = self.board.empty_cells(&mut cells);
+            if free == 0 {
+                return;
+            }
+            match self.rules.spawn_mode {
+                RandomSpawns => {
+                    spawn_random(&mut self.board, &self.rules, &mut self.rng);
+                }
+                AdversarialSpawns => {
+                    let (x, y, tile) = self.adversary.worst_spawn(&self.board).unwrap();
+                    self.board.set_tile(x, y, tile);
+                }
+            }
+        }
+    }
+}
+
+/// Spawn a tile of *rules* on a random empty cell of *board*, drawing
+/// from *rng*. Returns false if the board has no empty cell.
+pub fn spawn_random<R: Rng>(board: &mut Board, rules: &Rules, rng: &mut R) -> bool {
+    let mut cells = [(0u, 0u), ..MAX_CELLS];
+    let free = board.empty_cells(&mut cells);
+    if free == 0 {
+        return false;
+    }
+    let (x, y) = cells[rng.gen_range(0u, free)];
+    let tile = if rng.gen::<f64>() < rules.high_probability {
+        rules.high_tile
+    } else {
+        rules.low_tile
+    };
+    board.set_tile(x, y, tile);
+    true
+}
+
+/// Whether any move changes *board*.
+pub fn has_move(board: &Board) -> bool {
+    ALL_DIRECTIONS.iter().any(|dir| {
+        let (new_board, _) = board.shifted_board(*dir);
+        new_board != *board
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use board::{Up, Down, Left, Right};
+    use rules::{Rules, AdversarialSpawns, InvalidProbability};
+    use super::{Game, has_move};
+
+    fn tiles(game: &Game) -> uint {
+        game.board().total_nonzeros()
+    }
+
+    fn play_out(game: &mut Game) {
+        let mut i = 0u;
+        while !game.is_over() {
+            let dirs = [Up, Left, Down, Right];
+            if game.play(dirs[i % 4]).is_none() {
+                i += 1;
+            }
+            assert!(game.moves() < 100000);
+        }
+    }
+
+    #[test]
+    fn test_new_game() {
+        let game = Game::new(4, 4, Rules::default(), 1).ok().unwrap();
+        assert_eq!(tiles(&game), 2);
+        assert_eq!(game.score(), 0);
+        assert!(!game.is_over());
+
+        let mut rules = Rules::default();
+        rules.high_probability = -1.0;
+        match Game::new(4, 4, rules, 1) {
+            Err(InvalidProbability(_)) => (),
+            _ => fail!("expected InvalidProbability")
+        }
+    }
+
+    #[test]
+    fn test_restore() {
+        let mut game = Game::new(4, 4, Rules::default(), 3).ok().unwrap();
+        let start = game.checkpoint();
+        play_out(&mut game);
+        assert!(game.moves() > 0);
+        game.restore(&start);
+        assert!(game.board() == &start.board);
+        assert_eq!(game.score(), 0);
+        assert_eq!(game.moves(), 0);
+        assert!(!game.is_over());
+
+        // a move taken back and played again spawns the same tiles
+        let mut replayed = Game::new(4, 4, Rules::default(), 3).ok().unwrap();
+        play_out(&mut replayed);
+        play_out(&mut game);
+        assert!(game.board() == replayed.board());
+        assert_eq!(game.score(), replayed.score());
+    }
+
+    #[test]
+    fn test_same_seed_same_game() {
+        let mut a = Game::new(4, 4, Rules::default(), 7).ok().unwrap();
+        let mut b = Game::new(4, 4, Rules::default(), 7).ok().unwrap();
+        play_out(&mut a);
+        play_out(&mut b);
+        assert!(a.board() == b.board());
+        assert_eq!(a.score(), b.score());
+        assert_eq!(a.moves(), b.moves());
+        assert!(!has_move(a.board()));
+    }
+
+    #[test]
+    fn test_variants() {
+        let mut rules = Rules::default();
+        rules.spawns_per_move = 2;
+        rules.blockers.push((1, 1));
+        let mut game = Game::new(4, 4, rules, 3).ok().unwrap();
+        let dir = [Up, Down, Left, Right].iter().map(|d| *d)
+            .find(|d| {
+                let (moved, _) = game.board().shifted_board(*d);
+                moved != *game.board()
+            }).unwrap();
+        let (moved, _) = game.board().shifted_board(dir);
+        game.play(dir).unwrap();
+        assert_eq!(tiles(&game), moved.total_nonzeros() + 2);
+        play_out(&mut game);
+        assert!(game.board().is_blocker(1, 1));
+        assert_eq!(game.board().get(1, 1), 0);
+
+        let mut rules = Rules::default();
+        rules.target = Some(4);
+        let mut game = Game::new(3, 3, rules, 5).ok().unwrap();
+        play_out(&mut game);
+        assert!(game.won() || !has_move(game.board()));
+        if game.won() {
+            assert!(game.play(Up).is_none() && game.play(Left).is_none());
+        }
+    }
+
+    #[test]
+    fn test_adversarial_spawns() {
+        let mut rules = Rules::default();
+        rules.spawn_mode = AdversarialSpawns;
+        let mut a = Game::new(3, 3, rules.clone(), 1).ok().unwrap();
+        let mut b = Game::new(3, 3, rules, 2).ok().unwrap();
+        // the adversary does not use the generator
+        assert!(a.board() == b.board());
+        play_out(&mut a);
+        play_out(&mut b);
+        assert_eq!(a.score(), b.score());
+
+        let mut c = Game::new(3, 3, Rules { spawn_mode: AdversarialSpawns,
+                                            ..Rules::default() }, 1).ok().unwrap();
+        c.set_adversary_depth(2);
+        play_out(&mut c);
+        assert!(!has_move(c.board()));
+    }
+}
@@ -0,0 +1,129 @@
+#!/usr/bin/python3
+"""
+Generate the C header for the C ABI in native.rs.
+
+Every ``pub static NATIVE_*`` becomes a #define and every ``#[no_mangle]``
+function a prototype, each with its doc comment. Run as:
+
+    python3 gen_header.py native.rs > g2048.h
+"""
+import re
+import sys
+
+TYPES = {
+    "c_int": "int",
+    "c_uint": "unsigned int",
+    "c_char": "char",
+    "u8": "uint8_t",
+    "i8": "int8_t",
+    "u32": "uint32_t",
+    "u64": "uint64_t",
+    "i64": "int64_t",
+    "f32": "float",
+    "f64": "double",
+    "Searcher": "g2048_searcher",
+    "VecEnv": "g2048_env",
+}
+
+VALUES = {
+    "i64::MIN": "INT64_MIN",
+}
+
+STATIC_RE = re.compile(
+    r"pub static (NATIVE_\w+): (\w+) = ([^;]+);")
+FUNCTION_RE = re.compile(
+    r"pub (?:unsafe )?extern \"C\" fn (\w+)\((.*?)\)(?: -> ([^{]+))?\s*\{",
+    re.S)
+
+def c_type(rust):
+    """
+    Translate a Rust type of the ABI, with its raw pointers, to C.
+    """
+    rust = rust.strip()
+    if rust.startswith("**"):
+        return "const {} *const *".format(c_type(rust[2:]))
+    if rust.startswith("*mut "):
+        return "{} *".format(c_type(rust[5:]))
+    if rust.startswith("*"):
+        return "const {} *".format(c_type(rust[1:]))
+    return TYPES[rust]
+
+def declaration(type_, name):
+    if type_.endswith("*"):
+        return type_ + name
+    return "{} {}".format(type_, name)
+
+def comment(doc_lines):
+    if not doc_lines:
+        return []
+    text = [line.replace("``", "") for line in doc_lines]
+    return ["/*"] + [(" * " + line).rstrip() for line in text] + [" */"]
+
+def generate(source):
+    out = [
+        "/* Generated from native.rs by gen_header.py, do not edit. */",
+        "#ifndef G2048_H",
+        "#define G2048_H",
+        "",
+        "#include <stdint.h>",
+        "",
+        "#ifdef __cplusplus",
+        "extern \"C\" {",
+        "#endif",
+        "",
+    ]
+    for opaque in sorted(t for t in TYPES.values() if t.startswith("g2048_")):
+        out.append("typedef struct {0} {0};".format(opaque))
+    out.append("")
+    doc = []
+    lines = source.split("\n")
+    i = 0
+    while i < len(lines):
+        stripped = lines[i].strip()
+        if stripped.startswith("#[cfg(test)]"):
+            break
+        if stripped.startswith("///"):
+            doc.append(stripped[4:] if len(stripped) > 3 else "")
+            i += 1
+            continue
+        match = STATIC_RE.match(stripped)
+        if match:
+            name, _, value = match.groups()
+            value = VALUES.get(value, value)
+            if value.startswith("-"):
+                value = "({})".format(value)
+            out.extend(comment(doc))
+            out.append("#define {} {}".format(name, value))
+            out.append("")
+        elif stripped == "#[no_mangle]":
+            # the signature spans the lines up to the opening brace
+            end = i + 1
+            while "{" not in lines[end]:
+                end += 1
+            match = FUNCTION_RE.search(" ".join(lines[i+1:end+1]))
+            name, args, ret = match.groups()
+            params = []
+            for arg in filter(None, (a.strip() for a in args.split(","))):
+                arg_name, arg_type = arg.split(":", 1)
+                params.append(declaration(c_type(arg_type), arg_name.strip()))
+            ret_type = c_type(ret) if ret else "void"
+            out.extend(comment(doc))
+            out.append("{}({});".format(declaration(ret_type, name),
+                                        ", ".join(params) or "void"))
+            out.append("")
+            i = end
+        doc = []
+        i += 1
+
+    out.extend([
+        "#ifdef __cplusplus",
+        "}",
+        "#endif",
+        "",
+        "#endif",
+    ])
+    return "\n".join(out) + "\n"
+
+if __name__ == "__main__":
+    with open(sys.argv[1]) as f:
+        sys.stdout.write(generate(f.read()))
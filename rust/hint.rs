@@ -0,0 +1,111 @@
+use board::{Board, Direction, Up, Down, Left, Right, Score};
+use search::{EvalContext, DepthPolicy, Decision, SearchError, Move, NoMove, Valid, InvalidMove};
+
+static ALL_DIRECTIONS: [Direction, ..4] = [Up, Down, Left, Right];
+
+/// The advice of the searcher for a human player: the expected score
+/// of each move and the move it recommends. Frontends show these and
+/// use loss to warn about moves much worse than the recommended one.
+pub struct Hint {
+    /// The expected score of each move, indexed by the Direction value;
+    /// None for moves which do not change the board.
+    pub scores: [Option<Score>, ..4],
+    /// The recommended move, None if no move is left.
+    pub best: Option<Direction>,
+    /// The depth the board was searched to.
+    pub depth: uint
+}
+
+impl Clone for Hint {
+    fn clone(&self) -> Hint {
+        Hint { scores: self.scores,
+               best: self.best,
+               depth: self.depth }
+    }
+}
+
+impl Hint {
+    /// Search *board* to the depth *policy* picks for it.
+    pub fn search(ctx: &mut EvalContext, policy: &DepthPolicy,
+                  board: &Board) -> Result<Hint, SearchError> {
+        ctx.max_depth = policy.depth_for(board);
+        let decision = try!(ctx.eval(board));
+        Ok(Hint::from_decision(&decision))
+    }
+
+    /// The hint of a decision the searcher already made.
+    pub fn from_decision(decision: &Decision) -> Hint {
+        let mut scores = [None, ..4];
+        for (i, eval) in decision.scores.iter().enumerate() {
+            scores[i] = match *eval {
+                Valid(score) => Some(score),
+                InvalidMove => None
+            };
+        }
+        Hint { scores: scores,
+               best: match decision.best {
+                   Move(_, dir) => Some(dir),
+                   NoMove => None
+               },
+               depth: decision.depth }
+    }
+
+    pub fn score(&self, dir: Direction) -> Option<Score> {
+        self.scores[dir as uint]
+    }
+
+    /// How much less *dir* is expected to score than the recommended
+    /// move, as a share of the score of the recommended move: 0 for the
+    /// recommended move, 0.25 for a move a quarter worse. None if *dir*
+    /// does not change the board.
+    pub fn loss(&self, dir: Direction) -> Option<f64> {
+        let best = match self.best {
+            Some(best) => self.score(best).unwrap(),
+            None => return None
+        };
+        self.score(dir).map(|score| {
+            let scale = if best.abs() > 0 { best.abs() } else { 1 };
+            (best - score) as f64 / scale as f64
+        })
+    }
+
+    /// The valid moves, best first.
+    pub fn ranking(&self) -> Vec<Direction> {
+        let mut dirs = ALL_DIRECTIONS.iter()
+            .filter(|dir| self.score(**dir).is_some())
+            .map(|dir| *dir)
+            .collect::<Vec<Direction>>();
+        dirs.sort_by(|a, b| self.score(*b).unwrap().cmp(&self.score(*a).unwrap()));
+        dirs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use board::{Board, Up, Down, Left, Right};
+    use config::Config;
+    use super::Hint;
+
+    #[test]
+    fn test_hint() {
+        let board = Board::from_raw(&vec![1u8, 2, 3, 4,
+                                          0, 0, 0, 5,
+                                          0, 0, 0, 0,
+                                          0, 0, 0, 0]).ok().unwrap();
+        let config = Config::default();
+        let mut ctx = config.context();
+        let hint = Hint::search(&mut ctx, &config.policy, &board).ok().unwrap();
+        // the tiles are pushed up and right already
+        assert!(hint.score(Up).is_none());
+        assert!(hint.score(Right).is_none());
+        assert!(hint.score(Down).is_some());
+        assert!(hint.score(Left).is_some());
+        let best = hint.best.unwrap();
+        assert_eq!(hint.loss(best), Some(0.0));
+        assert!(hint.loss(Up).is_none());
+        let ranking = hint.ranking();
+        assert_eq!(ranking.len(), 2);
+        assert!(*ranking.get(0) as uint == best as uint);
+        assert!(hint.loss(*ranking.get(1)).unwrap() >= 0.0);
+    }
+}
@@ -0,0 +1,122 @@
+extern crate collections;
+extern crate getopts;
+extern crate rand;
+extern crate g2048;
+
+use collections::TreeMap;
+use std::cmp;
+use std::io;
+use std::os;
+use rand::Rng;
+
+use g2048::board::check_size;
+use g2048::config;
+use g2048::config::Config;
+use g2048::error::{AiError, IoFailure, ConfigFailure, EXIT_CONFIG};
+use g2048::pipe::host_game;
+use g2048::record;
+
+fn options() -> Vec<getopts::OptGroup> {
+    let mut opts = config::rules_options();
+    opts.push(getopts::optopt(
+        "", "size", "width and height of the board (4)", "N"));
+    opts.push(getopts::optopt(
+        "", "games", "number of games to play (1)", "N"));
+    opts.push(getopts::optopt(
+        "", "seed", "seed of the first game, the others count up (random)", "N"));
+    opts.push(getopts::optopt(
+        "", "timeout", "time the AI gets per move, in milliseconds (10000)", "MS"));
+    opts.push(getopts::optopt(
+        "", "record", "append each game as a line of JSON to PATH", "PATH"));
+    opts.push(getopts::optflag("h", "help", "show this help"));
+    opts
+}
+
+/// Play the games and print a line for each and a summary. Returns
+/// whether all games ended by the rules.
+fn run(args: &[String]) -> Result<bool, AiError> {
+    let opts = options();
+    let matches = try!(getopts::getopts(args, opts.as_slice()).map_err(
+        |e| ConfigFailure(e.to_err_msg())));
+    if matches.opt_present("help") {
+        println!("{}", getopts::usage(
+            "Usage: host [OPTIONS] [--] AI [ARGS...]\n\n\
+             Play games with an AI speaking the pipe protocol, e.g. ./ai or \
+             ../cpp/2049,\nstarting a new process of the AI for each game.",
+            opts.as_slice()));
+        return Ok(true);
+    }
+    if matches.free.len() == 0 {
+        return Err(ConfigFailure(format!("no AI command given")));
+    }
+    let cmd = matches.free.as_slice();
+
+    let mut config = Config::default();
+    try!(config.update_rules(&matches));
+    try!(config.validate());
+    let size = try!(config::parse_or(&matches, "size", 4u));
+    try!(check_size(size, size).map_err(|e| ConfigFailure(format!("{}", e))));
+    let games = try!(config::parse_or(&matches, "games", 1u));
+    let seed = try!(config::parse_or(&matches, "seed", rand::task_rng().gen::<u32>()));
+    let timeout_ms = try!(config::parse_or(&matches, "timeout", 10000u64));
+    let mut record_out = try!(record::open_record_file(matches.opt_str("record")));
+
+    let mut total_score = 0u64;
+    let mut max_score = 0u64;
+    let mut total_moves = 0u;
+    let mut max_tiles = TreeMap::new();
+    let mut ends = TreeMap::new();
+    let mut regular = true;
+    for i in range(0, games) {
+        let game_seed = seed + i as u32;
+        let record = try!(host_game(cmd, &config, size, size, game_seed, timeout_ms));
+        println!("game {} seed={} score={} max_tile={} moves={} \
+                  mean_move_ms={:.1f} end={}",
+                 i + 1, game_seed, record.score, 1u64 << record.max_tile() as uint,
+                 record.moves.len(), record.mean_move_ms(), record.end);
+        match record_out {
+            Some(ref mut out) => try!(record.write_json(out).map_err(|e| IoFailure(e))),
+            None => ()
+        }
+
+        total_score += record.score;
+        max_score = cmp::max(max_score, record.score);
+        total_moves += record.moves.len();
+        let tile = 1u64 << record.max_tile() as uint;
+        let count = max_tiles.find(&tile).map(|n| *n).unwrap_or(0u);
+        max_tiles.insert(tile, count + 1);
+        let count = ends.find(&record.end.name()).map(|n| *n).unwrap_or(0u);
+        ends.insert(record.end.name(), count + 1);
+        regular = regular && record.end.is_regular();
+    }
+
+    println!("ai={} games={} seed={} mean_score={:.1f} max_score={} mean_moves={:.1f}",
+             cmd.connect(" "), games, seed,
+             total_score as f64 / cmp::max(games, 1) as f64, max_score,
+             total_moves as f64 / cmp::max(games, 1) as f64);
+    let mut tiles = max_tiles.iter()
+        .map(|(tile, n)| format!("{}:{}", *tile, *n))
+        .collect::<Vec<String>>();
+    tiles.reverse();
+    println!("max_tiles {}", tiles.connect(" "));
+    let end_counts = ends.iter()
+        .map(|(name, n)| format!("{}={}", *name, *n))
+        .collect::<Vec<String>>();
+    println!("ends {}", end_counts.connect(" "));
+    Ok(regular)
+}
+
+fn main() {
+    let args = os::args();
+    match run(args.tail()) {
+        Ok(true) => (),
+        Ok(false) => os::set_exit_status(1),
+        Err(e) => {
+            let _ = writeln!(&mut io::stderr(), "host: {}", e);
+            if e.exit_code() == EXIT_CONFIG {
+                let _ = writeln!(&mut io::stderr(), "host: try --help");
+            }
+            os::set_exit_status(e.exit_code());
+        }
+    }
+}
@@ -0,0 +1,34 @@
+/// Quote and escape *s* as a JSON string literal.
+pub fn quote(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push_char('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                result.push_str("\\u");
+                result.push_str(format!("{:04x}", c as u32).as_slice());
+            }
+            c => result.push_char(c)
+        }
+    }
+    result.push_char('"');
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::quote;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("abc").as_slice(), "\"abc\"");
+        assert_eq!(quote("a\"b\\c").as_slice(), "\"a\\\"b\\\\c\"");
+        assert_eq!(quote("line\nbreak\x01").as_slice(),
+                   "\"line\\nbreak\\u0001\"");
+    }
+}
@@ -0,0 +1,31 @@
+#![crate_id = "g2048"]
+#![crate_type = "rlib"]
+#![feature(phase)]
+#[phase(syntax, link)] extern crate log;
+#[phase(syntax, link)] extern crate rand;
+extern crate collections;
+extern crate sync;
+extern crate getopts;
+extern crate serialize;
+extern crate time;
+
+pub mod analysis;
+pub mod board;
+pub mod config;
+pub mod drawing;
+pub mod env;
+pub mod error;
+pub mod game;
+pub mod hint;
+pub mod json;
+pub mod logging;
+pub mod pipe;
+pub mod pool;
+pub mod record;
+pub mod search;
+pub mod server;
+pub mod solver;
+pub mod trace;
+pub mod protocol;
+pub mod rules;
+pub mod vectors;
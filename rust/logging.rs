@@ -0,0 +1,305 @@
+use std::io;
+use log;
+use sync::{Arc, Mutex};
+use time;
+
+use json;
+use search::{Decision, SearchStats, Move, NoMove, Valid, InvalidMove};
+
+/// Output format of a LogSink.
+#[deriving(Eq, Clone, Show)]
+pub enum LogFormat {
+    /// One line per record: timestamp, level, module, location and
+    /// message.
+    TextFormat,
+    /// One JSON object per line.
+    JsonFormat
+}
+
+impl LogFormat {
+    pub fn parse(name: &str) -> Option<LogFormat> {
+        match name {
+            "text" => Some(TextFormat),
+            "json" => Some(JsonFormat),
+            _ => None
+        }
+    }
+}
+
+/// Destination for log records and decision records. Which records
+/// reach the sink is controlled with the usual RUST_LOG directives,
+/// e.g. ``RUST_LOG=g2048::search=debug,ai=info``.
+pub struct LogSink {
+    out: Option<Box<Writer:Send>>,
+    format: LogFormat
+}
+
+pub type SharedSink = Arc<Mutex<LogSink>>;
+
+fn timestamp() -> f64 {
+    let now = time::get_time();
+    now.sec as f64 + (now.nsec as f64) * 1e-9
+}
+
+impl LogSink {
+    pub fn new(out: Box<Writer:Send>, format: LogFormat) -> LogSink {
+        LogSink { out: Some(out), format: format }
+    }
+
+    /// Open a sink writing to the file at *path*, which is truncated.
+    /// A path of ``-`` selects stderr.
+    pub fn open(path: &str, format: LogFormat) -> io::IoResult<LogSink> {
+        let out = if path == "-" {
+            box io::stderr() as Box<Writer:Send>
+        } else {
+            box try!(io::File::create(&Path::new(path))) as Box<Writer:Send>
+        };
+        Ok(LogSink::new(out, format))
+    }
+
+    pub fn shared(self) -> SharedSink {
+        Arc::new(Mutex::new(self))
+    }
+
+    fn write_line(&mut self, line: &str) {
+        let result = match self.out {
+            Some(ref mut out) => out.write_line(line).and_then(|_| out.flush()),
+            None => Ok(())
+        };
+        match result {
+            Ok(()) => (),
+            Err(e) => {
+                // the search itself is not affected, so keep playing
+                // without a log instead of giving up
+                let _ = writeln!(&mut io::stderr(),
+                                 "failed to write log, logging disabled: {}", e);
+                self.out = None;
+            }
+        }
+    }
+
+    /// Write a record from the log macros.
+    pub fn record(&mut self, record: &log::LogRecord) {
+        let msg = format!("{}", record.args);
+        let msg = msg.as_slice().trim_right();
+        let line = match self.format {
+            TextFormat => format!(
+                "{:.3f} {:>5} {} {}:{}: {}",
+                timestamp(), record.level, record.module_path,
+                record.file, record.line, msg),
+            JsonFormat => format!(
+                "\\{\"ts\":{:.3f},\"level\":{},\"target\":{},\
+                 \"file\":{},\"line\":{},\"msg\":{}\\}",
+                timestamp(),
+                json::quote(format!("{}", record.level).as_slice()),
+                json::quote(record.module_path),
+                json::quote(record.file),
+                record.line,
+                json::quote(msg))
+        };
+        self.write_line(line.as_slice());
+    }
+
+    /// Write one structured record for a decision of the searcher.
+    pub fn decision(&mut self, decision: &Decision) {
+        let line = match self.format {
+            TextFormat => format!("{:.3f}  INFO decision {}",
+                                  timestamp(), decision),
+            JsonFormat => format!("\\{\"ts\":{:.3f},\"level\":\"INFO\",\
+                                   \"target\":\"decision\",{}\\}",
+                                  timestamp(), decision_json_fields(decision))
+        };
+        self.write_line(line.as_slice());
+    }
+
+    /// Write one record with the statistics of a search.
+    pub fn stats(&mut self, stats: &SearchStats) {
+        let line = match self.format {
+            TextFormat => format!("{:.3f}  INFO stats {}", timestamp(), stats),
+            JsonFormat => format!("\\{\"ts\":{:.3f},\"level\":\"INFO\",\
+                                   \"target\":\"stats\",{}\\}",
+                                  timestamp(), stats_json_fields(stats))
+        };
+        self.write_line(line.as_slice());
+    }
+}
+
+fn stats_json_fields(stats: &SearchStats) -> String {
+    let nodes = stats.nodes_per_depth.iter().map(|n| format!("{}", *n))
+        .collect::<Vec<String>>();
+    let times = stats.time_per_depth_ns.iter()
+        .map(|t| format!("{:.3f}", *t as f64 / 1e6))
+        .collect::<Vec<String>>();
+    format!("\"nodes\":{},\"chance_nodes\":{},\"game_over_leaves\":{},\
+             \"depth_exceeded_leaves\":{},\"branching_factor\":{:.2f},\
+             \"effective_depth\":{},\"nodes_per_depth\":[{}],\
+             \"ms_per_depth\":[{}]",
+            stats.nodes(), stats.chance_nodes, stats.game_over_leaves,
+            stats.depth_exceeded_leaves, stats.branching_factor(),
+            stats.effective_depth(), nodes.connect(","), times.connect(","))
+}
+
+fn decision_json_fields(decision: &Decision) -> String {
+    let mut board = Vec::new();
+    for y in range(0u, decision.board.height()) {
+        for cell in decision.board.get_row(y).iter() {
+            board.push(format!("{}", *cell));
+        }
+    }
+
+    let (move, score) = match decision.best {
+        Move(score, dir) => (json::quote(format!("{}", dir).as_slice()),
+                             format!("{}", score)),
+        NoMove => (String::from_str("null"), String::from_str("null"))
+    };
+
+    let names = ["up", "down", "left", "right"];
+    let scores = decision.scores.iter().zip(names.iter()).map(|(eval, name)| {
+        match *eval {
+            Valid(score) => format!("\"{}\":{}", *name, score),
+            InvalidMove => format!("\"{}\":null", *name)
+        }
+    }).collect::<Vec<String>>();
+    let mut game_over = Vec::new();
+    let mut stddev = Vec::new();
+    for (i, name) in names.iter().enumerate() {
+        match decision.scores[i] {
+            Valid(_) => {
+                game_over.push(format!("\"{}\":{:.3f}", *name,
+                                       decision.risks[i].game_over));
+                stddev.push(format!("\"{}\":{:.1f}", *name,
+                                    decision.risks[i].stddev()));
+            }
+            InvalidMove => {
+                game_over.push(format!("\"{}\":null", *name));
+                stddev.push(format!("\"{}\":null", *name));
+            }
+        }
+    }
+
+    format!("\"width\":{},\"height\":{},\"board\":[{}],\"move\":{},\
+             \"score\":{},\"scores\":\\{{}\\},\"game_over\":\\{{}\\},\
+             \"stddev\":\\{{}\\},\"depth\":{},\"nodes\":{},\"elapsed_ms\":{:.3f}",
+            decision.board.width(), decision.board.height(),
+            board.connect(","), move, score, scores.connect(","),
+            game_over.connect(","), stddev.connect(","),
+            decision.depth, decision.stats.nodes(),
+            decision.elapsed_ns as f64 / 1e6)
+}
+
+struct SinkLogger {
+    sink: SharedSink
+}
+
+impl log::Logger for SinkLogger {
+    fn log(&mut self, record: &log::LogRecord) {
+        let mut sink = self.sink.lock();
+        sink.record(record);
+    }
+}
+
+/// Send the records of the log macros in the calling task to *sink*.
+/// Loggers are per task, so this has to be called in every task which
+/// is supposed to log there.
+pub fn install(sink: &SharedSink) {
+    log::set_logger(box SinkLogger { sink: sink.clone() });
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::str;
+    use board::Board;
+    use search::{Decision, SearchStats, Risk, Move, Valid, InvalidMove};
+    use board::Left;
+    use super::{LogSink, TextFormat, JsonFormat};
+
+    struct SharedWriter {
+        buf: ::sync::Arc<::sync::Mutex<Vec<u8>>>
+    }
+
+    impl Writer for SharedWriter {
+        fn write(&mut self, data: &[u8]) -> io::IoResult<()> {
+            let mut buf = self.buf.lock();
+            buf.push_all(data);
+            Ok(())
+        }
+    }
+
+    fn stats() -> SearchStats {
+        SearchStats { nodes_per_depth: vec![3, 8, 6],
+                      chance_nodes: 20,
+                      game_over_leaves: 1,
+                      depth_exceeded_leaves: 5,
+                      time_per_depth_ns: vec![1500000, 1200000, 250000] }
+    }
+
+    fn decision() -> Decision {
+        let raw = Vec::from_fn(16, |i| (i % 4) as u8);
+        Decision { board: Board::from_raw(&raw).unwrap(),
+                   best: Move(42, Left),
+                   scores: [Valid(10), InvalidMove, Valid(42), Valid(-3)],
+                   risks: [Risk { variance: 4.0, game_over: 0.25 }, Risk::none(),
+                           Risk::none(), Risk { variance: 100.0, game_over: 1.0 }],
+                   depth: 3,
+                   stats: stats(),
+                   elapsed_ns: 1500000,
+                   trace: None }
+    }
+
+    fn logged(format: super::LogFormat, with_stats: bool) -> String {
+        let buf = ::sync::Arc::new(::sync::Mutex::new(Vec::new()));
+        let mut sink = LogSink::new(box SharedWriter { buf: buf.clone() }, format);
+        if with_stats {
+            sink.stats(&stats());
+        } else {
+            sink.decision(&decision());
+        }
+        let mut data = buf.lock();
+        String::from_str(str::from_utf8(data.as_slice()).unwrap())
+    }
+
+    #[test]
+    fn test_decision_json() {
+        let line = logged(JsonFormat, false);
+        assert!(line.as_slice().contains(
+            "\"board\":[0,1,2,3,0,1,2,3,0,1,2,3,0,1,2,3],\"move\":\"Left\",\
+             \"score\":42,\"scores\":{\"up\":10,\"down\":null,\"left\":42,\
+             \"right\":-3},\"game_over\":{\"up\":0.250,\"down\":null,\
+             \"left\":0.000,\"right\":1.000},\"stddev\":{\"up\":2.0,\
+             \"down\":null,\"left\":0.0,\"right\":10.0},\"depth\":3,\"nodes\":17,\
+             \"elapsed_ms\":1.500}"),
+            "{}", line);
+    }
+
+    #[test]
+    fn test_decision_text() {
+        let line = logged(TextFormat, false);
+        assert!(line.as_slice().contains(
+            "INFO decision move=Left score=42 scores=10,-,42,-3 \
+             game_over=0.250,-,0.000,1.000 stddev=2.0,-,0.0,10.0 depth=3 \
+             nodes=17 elapsed_ms=1.500 board=0,1,2,3/0,1,2,3/0,1,2,3/0,1,2,3"),
+            "{}", line);
+    }
+
+    #[test]
+    fn test_stats_json() {
+        let line = logged(JsonFormat, true);
+        assert!(line.as_slice().contains(
+            "\"target\":\"stats\",\"nodes\":17,\"chance_nodes\":20,\
+             \"game_over_leaves\":1,\"depth_exceeded_leaves\":5,\
+             \"branching_factor\":1.27,\"effective_depth\":3,\
+             \"nodes_per_depth\":[3,8,6],\"ms_per_depth\":[1.500,1.200,0.250]}"),
+            "{}", line);
+    }
+
+    #[test]
+    fn test_stats_text() {
+        let line = logged(TextFormat, true);
+        assert!(line.as_slice().contains(
+            "INFO stats nodes=17 chance_nodes=20 game_over_leaves=1 \
+             depth_exceeded_leaves=5 branching_factor=1.27 effective_depth=3 \
+             nodes_per_depth=3,8,6 ms_per_depth=1.500,1.200,0.250"),
+            "{}", line);
+    }
+}
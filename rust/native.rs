@@ -0,0 +1,818 @@
+#![crate_id = "g2048native"]
+#![crate_type = "dylib"]
+//! A C ABI over the engine and the searcher, for callers in other
+//! languages; g2048/native.py wraps it with ctypes.
+//!
+//! Boards are passed in batches: *count* boards of *width* times
+//! *height* bytes each, every board in the row-major order of the AI
+//! protocol. Functions return NATIVE_OK or one of the negative error
+//! codes below, and write nothing unless all boards are valid.
+//!
+//! The interface is stable within an ABI version: functions are only
+//! added, never changed. The C header g2048.h is generated from this
+//! file by gen_header.py; see ``make header``.
+extern crate libc;
+extern crate rand;
+extern crate g2048;
+
+use libc::{c_char, c_int, c_uint};
+use std::c_str::CString;
+use std::f64;
+use std::i64;
+use std::mem;
+use std::slice::raw::{buf_as_slice, mut_buf_as_slice};
+use rand::{SeedableRng, XorShiftRng};
+
+use g2048::board::{Board, Direction, Up, Down, Left, Right, check_size};
+use g2048::config;
+use g2048::config::Config;
+use g2048::env::{VecEnv, RawExponents, OneHotPlanes};
+use g2048::game::{spawn_random, has_move};
+use g2048::hint::Hint;
+use g2048::rules::Rules;
+use g2048::search::{Decision, EvalContext, Move, NoMove, Valid, InvalidMove};
+
+/// Raised whenever a function changes or is removed.
+pub static NATIVE_ABI_VERSION: c_uint = 1;
+
+pub static NATIVE_OK: c_int = 0;
+/// The size, the direction or another argument is out of range.
+pub static NATIVE_INVALID_ARGUMENT: c_int = -1;
+/// A board holds a tile too large for its size.
+pub static NATIVE_INVALID_BOARD: c_int = -2;
+/// The search could not produce a result.
+pub static NATIVE_SEARCH_FAILED: c_int = -3;
+/// There is no valid move on the board.
+pub static NATIVE_NO_MOVE: c_int = -4;
+
+/// The score reported for a move which does not change the board.
+pub static NATIVE_INVALID_MOVE: i64 = i64::MIN;
+
+static ALL_DIRECTIONS: [Direction, ..4] = [Up, Down, Left, Right];
+
+/// A searcher with its configuration, handed out as an opaque pointer.
+pub struct Searcher {
+    config: Config,
+    ctx: EvalContext,
+    /// Fixed search depth, or zero to use the depth policy.
+    depth: uint
+}
+
+impl Searcher {
+    fn new(config: Config, depth: uint) -> *mut Searcher {
+        let searcher = box Searcher { ctx: config.context(),
+                                      config: config,
+                                      depth: depth };
+        unsafe { mem::transmute(searcher) }
+    }
+
+    /// Search *board* with the blockers of the rules placed on it, as
+    /// the AI does with the boards of the protocol.
+    fn search(&mut self, board: &Board) -> Result<Decision, c_int> {
+        let mut board = board.clone();
+        for &(x, y) in self.ctx.rules.blockers.iter() {
+            if x < board.width() && y < board.height() {
+                board.set_blocker(x, y);
+            }
+        }
+        self.ctx.max_depth = if self.depth > 0 {
+            self.depth
+        } else {
+            self.config.policy.depth_for(&board)
+        };
+        self.ctx.eval(&board).map_err(|_| NATIVE_SEARCH_FAILED)
+    }
+
+    /// Search each of *boards*; fails on the first board which cannot
+    /// be searched.
+    fn search_all(&mut self, boards: &[Board]) -> Result<Vec<Decision>, c_int> {
+        let mut decisions = Vec::with_capacity(boards.len());
+        for board in boards.iter() {
+            decisions.push(try!(self.search(board)));
+        }
+        Ok(decisions)
+    }
+}
+
+/// Decode a batch of boards, or return the error code for it.
+unsafe fn read_boards(width: c_uint, height: c_uint, count: c_uint,
+                      boards: *u8) -> Result<Vec<Board>, c_int> {
+    let (width, height, count) = (width as uint, height as uint, count as uint);
+    if check_size(width, height).is_err() || (count > 0 && boards.is_null()) {
+        return Err(NATIVE_INVALID_ARGUMENT);
+    }
+    let cells = width * height;
+    buf_as_slice(boards, cells * count, |raw| {
+        let mut result = Vec::with_capacity(count);
+        for i in range(0, count) {
+            let cells = Vec::from_slice(raw.slice(i * cells, (i + 1) * cells));
+            match Board::from_raw_sized(width, height, &cells) {
+                Ok(board) => result.push(board),
+                Err(_) => return Err(NATIVE_INVALID_BOARD)
+            }
+        }
+        Ok(result)
+    })
+}
+
+/// Encode *boards* into *out*, which has room for all of them.
+unsafe fn write_boards(boards: &[Board], out: *mut u8) {
+    if boards.len() == 0 {
+        return;
+    }
+    let cells = boards[0].cells();
+    mut_buf_as_slice(out, cells * boards.len(), |raw| {
+        for (i, board) in boards.iter().enumerate() {
+            for y in range(0u, board.height()) {
+                for x in range(0u, board.width()) {
+                    raw[i * cells + y * board.width() + x] = board.get(x, y);
+                }
+            }
+        }
+    })
+}
+
+/// Encode the best move of each decision into *moves* and the scores
+/// of its four moves into *scores*, as g2048_searcher_eval describes.
+unsafe fn write_decisions(decisions: &[Decision], moves: *mut i8, scores: *mut i64) {
+    mut_buf_as_slice(moves, decisions.len(), |moves| {
+        mut_buf_as_slice(scores, 4 * decisions.len(), |scores| {
+            for (i, decision) in decisions.iter().enumerate() {
+                moves[i] = match decision.best {
+                    Move(_, dir) => dir as i8,
+                    NoMove => -1
+                };
+                for (j, eval) in decision.scores.iter().enumerate() {
+                    scores[4 * i + j] = match *eval {
+                        Valid(score) => score,
+                        InvalidMove => NATIVE_INVALID_MOVE
+                    };
+                }
+            }
+        })
+    });
+}
+
+/// Decode *argc* C strings from *argv*, or None if one is null or not
+/// UTF-8.
+unsafe fn read_args(argc: c_int, argv: **c_char) -> Option<Vec<String>> {
+    if argc < 0 || (argc > 0 && argv.is_null()) {
+        return None;
+    }
+    let mut args = Vec::with_capacity(argc as uint);
+    let mut valid = true;
+    buf_as_slice(argv, argc as uint, |argv| {
+        for arg in argv.iter() {
+            if arg.is_null() {
+                valid = false;
+                return;
+            }
+            match CString::new(*arg, false).as_str() {
+                Some(arg) => args.push(String::from_str(arg)),
+                None => valid = false
+            }
+        }
+    });
+    if valid { Some(args) } else { None }
+}
+
+fn direction(dir: c_uint) -> Option<Direction> {
+    if (dir as uint) < ALL_DIRECTIONS.len() {
+        Some(ALL_DIRECTIONS[dir as uint])
+    } else {
+        None
+    }
+}
+
+/// Shift each board towards *dir* (0 up, 1 down, 2 left, 3 right, as
+/// in the protocol). The shifted boards go to *out* and the points of
+/// the merges, as the game counts them, to *points*.
+#[no_mangle]
+pub unsafe extern "C" fn g2048_shift(width: c_uint, height: c_uint, count: c_uint,
+                                     boards: *u8, dir: c_uint,
+                                     out: *mut u8, points: *mut u64) -> c_int {
+    if count > 0 && (out.is_null() || points.is_null()) {
+        return NATIVE_INVALID_ARGUMENT;
+    }
+    let dir = match direction(dir) {
+        Some(dir) => dir,
+        None => return NATIVE_INVALID_ARGUMENT
+    };
+    let boards = match read_boards(width, height, count, boards) {
+        Ok(boards) => boards,
+        Err(code) => return code
+    };
+    let mut shifted = Vec::with_capacity(boards.len());
+    mut_buf_as_slice(points, boards.len(), |points| {
+        for (i, board) in boards.iter().enumerate() {
+            let (new_board, gained) = board.shifted_with_points(dir);
+            shifted.push(new_board);
+            points[i] = gained;
+        }
+    });
+    write_boards(shifted.as_slice(), out);
+    NATIVE_OK
+}
+
+/// Spawn a tile on a random empty cell of each board, in place, with
+/// the probabilities of the original game. The spawns are drawn from a
+/// generator seeded with *seed*. Boards without an empty cell are left
+/// as they are.
+#[no_mangle]
+pub unsafe extern "C" fn g2048_spawn(width: c_uint, height: c_uint, count: c_uint,
+                                     boards: *mut u8, seed: u32) -> c_int {
+    let mut decoded = match read_boards(width, height, count, boards as *u8) {
+        Ok(boards) => boards,
+        Err(code) => return code
+    };
+    let rules = Rules::default();
+    let mut rng: XorShiftRng = SeedableRng::from_seed([seed, 0x2048, 0x9a3e, 0x5eed]);
+    for board in decoded.mut_iter() {
+        spawn_random(board, &rules, &mut rng);
+    }
+    write_boards(decoded.as_slice(), boards);
+    NATIVE_OK
+}
+
+/// Store 1 in *out* for each board without a valid move, 0 otherwise.
+#[no_mangle]
+pub unsafe extern "C" fn g2048_game_over(width: c_uint, height: c_uint, count: c_uint,
+                                         boards: *u8, out: *mut u8) -> c_int {
+    if count > 0 && out.is_null() {
+        return NATIVE_INVALID_ARGUMENT;
+    }
+    let boards = match read_boards(width, height, count, boards) {
+        Ok(boards) => boards,
+        Err(code) => return code
+    };
+    mut_buf_as_slice(out, boards.len(), |out| {
+        for (i, board) in boards.iter().enumerate() {
+            out[i] = if has_move(board) { 0 } else { 1 };
+        }
+    });
+    NATIVE_OK
+}
+
+/// Create a searcher with the default configuration of the AI, running
+/// on *threads* threads. A *depth* of zero picks the depth per board
+/// like the AI does; otherwise every board is searched that deep.
+/// Returns null for zero threads. The searcher has to be released with
+/// g2048_searcher_free.
+#[no_mangle]
+pub extern "C" fn g2048_searcher_new(depth: c_uint, threads: c_uint) -> *mut Searcher {
+    if threads < 1 {
+        return 0 as *mut Searcher;
+    }
+    let mut config = Config::default();
+    config.threads = threads as uint;
+    Searcher::new(config, depth as uint)
+}
+
+/// Create a searcher from the search and rule options of the AI, e.g.
+/// ``--depth 4 --threads 2``, given as *argc* strings in *argv*. The
+/// depth is picked per board by the depth options, as in the AI.
+/// Returns null if the options are invalid.
+#[no_mangle]
+pub unsafe extern "C" fn g2048_searcher_configure(argc: c_int,
+                                                  argv: **c_char) -> *mut Searcher {
+    let args = match read_args(argc, argv) {
+        Some(args) => args,
+        None => return 0 as *mut Searcher
+    };
+    match config::parse_args(args.as_slice(), config::options().as_slice()) {
+        Ok(Some((config, _))) => Searcher::new(config, 0),
+        _ => 0 as *mut Searcher
+    }
+}
+
+/// Release a searcher; null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn g2048_searcher_free(searcher: *mut Searcher) {
+    if !searcher.is_null() {
+        let _: Box<Searcher> = mem::transmute(searcher);
+    }
+}
+
+/// Search each board. The best move goes to *moves* (-1 if there is
+/// none) and the scores of all four moves to the four entries of
+/// *scores* per board, NATIVE_INVALID_MOVE for moves which do not
+/// change the board.
+#[no_mangle]
+pub unsafe extern "C" fn g2048_searcher_eval(searcher: *mut Searcher,
+                                             width: c_uint, height: c_uint, count: c_uint,
+                                             boards: *u8,
+                                             moves: *mut i8, scores: *mut i64) -> c_int {
+    if searcher.is_null() || (count > 0 && (moves.is_null() || scores.is_null())) {
+        return NATIVE_INVALID_ARGUMENT;
+    }
+    let boards = match read_boards(width, height, count, boards) {
+        Ok(boards) => boards,
+        Err(code) => return code
+    };
+    let decisions = match (*searcher).search_all(boards.as_slice()) {
+        Ok(decisions) => decisions,
+        Err(code) => return code
+    };
+    write_decisions(decisions.as_slice(), moves, scores);
+    NATIVE_OK
+}
+
+/// Advise on each board like the hints of the play binary: write the
+/// best move and the scores as g2048_searcher_eval does, the loss of
+/// each move to the four entries of *losses* per board, and the valid
+/// moves, best first, to the four entries of *ranking* per board, with
+/// -1 after the last valid move. The loss is the share by which a move
+/// is expected to score less than the best one, 0 for the best move
+/// and 0.25 for a move a quarter worse; it is NaN for moves which do
+/// not change the board, and for all moves if there is no best move.
+#[no_mangle]
+pub unsafe extern "C" fn g2048_searcher_hint(searcher: *mut Searcher,
+                                             width: c_uint, height: c_uint, count: c_uint,
+                                             boards: *u8,
+                                             moves: *mut i8, scores: *mut i64,
+                                             losses: *mut f64, ranking: *mut i8) -> c_int {
+    if searcher.is_null() || (count > 0 && (moves.is_null() || scores.is_null() ||
+                                            losses.is_null() || ranking.is_null())) {
+        return NATIVE_INVALID_ARGUMENT;
+    }
+    let boards = match read_boards(width, height, count, boards) {
+        Ok(boards) => boards,
+        Err(code) => return code
+    };
+    let decisions = match (*searcher).search_all(boards.as_slice()) {
+        Ok(decisions) => decisions,
+        Err(code) => return code
+    };
+    write_decisions(decisions.as_slice(), moves, scores);
+    mut_buf_as_slice(losses, 4 * decisions.len(), |losses| {
+        mut_buf_as_slice(ranking, 4 * decisions.len(), |ranking| {
+            for (i, decision) in decisions.iter().enumerate() {
+                let hint = Hint::from_decision(decision);
+                for (j, dir) in ALL_DIRECTIONS.iter().enumerate() {
+                    losses[4 * i + j] = hint.loss(*dir).unwrap_or(f64::NAN);
+                }
+                let ranked = hint.ranking();
+                for j in range(0u, 4) {
+                    ranking[4 * i + j] = match ranked.as_slice().get(j) {
+                        Some(dir) => *dir as i8,
+                        None => -1
+                    };
+                }
+            }
+        })
+    });
+    NATIVE_OK
+}
+
+/// Search a 4x4 board of 16 bytes, as sent in the AI protocol, and
+/// return the direction of the best move (0 up, 1 down, 2 left,
+/// 3 right), NATIVE_NO_MOVE or an error code.
+#[no_mangle]
+pub unsafe extern "C" fn g2048_searcher_move(searcher: *mut Searcher,
+                                             board: *u8) -> c_int {
+    if searcher.is_null() {
+        return NATIVE_INVALID_ARGUMENT;
+    }
+    let board = match read_boards(4, 4, 1, board) {
+        Ok(mut boards) => boards.pop().unwrap(),
+        Err(code) => return code
+    };
+    match (*searcher).search(&board) {
+        Ok(decision) => match decision.best {
+            Move(_, dir) => dir as c_int,
+            NoMove => NATIVE_NO_MOVE
+        },
+        Err(code) => code
+    }
+}
+
+/// Create *count* environments for reinforcement learning on boards of
+/// the given size, under the rules of the original game. Observations
+/// are the tile exponents, or one-hot planes if *one_hot* is not zero;
+/// see g2048_env_observation_size. Returns null for invalid sizes or a
+/// count of zero. The environments have to be released with
+/// g2048_env_free.
+#[no_mangle]
+pub extern "C" fn g2048_env_new(count: c_uint, width: c_uint, height: c_uint,
+                                one_hot: c_int) -> *mut VecEnv {
+    new_envs(count, width, height, one_hot, Rules::default())
+}
+
+/// Like g2048_env_new, but under the rules given by the game variant
+/// options of the AI, e.g. ``--blocker 1,1 --target 11``, as *argc*
+/// strings in *argv*. Blockers are -1 in the tile exponents and have a
+/// plane of their own after the one-hot planes of the tiles. Returns
+/// null if the options are invalid or the rules cannot be played on
+/// the board.
+#[no_mangle]
+pub unsafe extern "C" fn g2048_env_configure(count: c_uint, width: c_uint, height: c_uint,
+                                             one_hot: c_int, argc: c_int,
+                                             argv: **c_char) -> *mut VecEnv {
+    let args = match read_args(argc, argv) {
+        Some(args) => args,
+        None => return 0 as *mut VecEnv
+    };
+    match config::parse_args(args.as_slice(), config::options().as_slice()) {
+        Ok(Some((config, _))) => new_envs(count, width, height, one_hot, config.rules),
+        _ => 0 as *mut VecEnv
+    }
+}
+
+fn new_envs(count: c_uint, width: c_uint, height: c_uint, one_hot: c_int,
+            rules: Rules) -> *mut VecEnv {
+    if count < 1 || check_size(width as uint, height as uint).is_err() {
+        return 0 as *mut VecEnv;
+    }
+    let encoding = if one_hot != 0 { OneHotPlanes } else { RawExponents };
+    match VecEnv::new(count as uint, width as uint, height as uint, rules, encoding) {
+        Ok(envs) => unsafe { mem::transmute(box envs) },
+        Err(_) => 0 as *mut VecEnv
+    }
+}
+
+/// Release environments; null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn g2048_env_free(envs: *mut VecEnv) {
+    if !envs.is_null() {
+        let _: Box<VecEnv> = mem::transmute(envs);
+    }
+}
+
+/// The number of values in the observation of one environment.
+#[no_mangle]
+pub unsafe extern "C" fn g2048_env_observation_size(envs: *mut VecEnv) -> c_uint {
+    if envs.is_null() {
+        return 0;
+    }
+    (*envs).observation_size() as c_uint
+}
+
+/// Start new games in all environments, the i-th with seed *seed* + i,
+/// and write their observations to *obs*, one after another.
+#[no_mangle]
+pub unsafe extern "C" fn g2048_env_reset(envs: *mut VecEnv, seed: u32,
+                                         obs: *mut f32) -> c_int {
+    if envs.is_null() || obs.is_null() {
+        return NATIVE_INVALID_ARGUMENT;
+    }
+    let envs = &mut *envs;
+    mut_buf_as_slice(obs, envs.len() * envs.observation_size(), |obs| {
+        envs.reset(seed, obs);
+    });
+    NATIVE_OK
+}
+
+/// Play one action per environment (0 up, 1 down, 2 left, 3 right)
+/// and write the new observations, the rewards, whether each game
+/// ended and the scores of the games. Games which end are started over
+/// right away with the next seeds; their score is the final one, their
+/// observation the one of the new game. Invalid moves change nothing
+/// and gain nothing.
+#[no_mangle]
+pub unsafe extern "C" fn g2048_env_step(envs: *mut VecEnv, actions: *u8,
+                                        obs: *mut f32, rewards: *mut f64,
+                                        dones: *mut u8, scores: *mut u64) -> c_int {
+    if envs.is_null() || actions.is_null() || obs.is_null() || rewards.is_null() ||
+       dones.is_null() || scores.is_null() {
+        return NATIVE_INVALID_ARGUMENT;
+    }
+    let envs = &mut *envs;
+    let count = envs.len();
+    let actions = buf_as_slice(actions, count, |actions| {
+        actions.iter().map(|a| *a as uint).collect::<Vec<uint>>()
+    });
+    if actions.iter().any(|a| *a >= 4) {
+        return NATIVE_INVALID_ARGUMENT;
+    }
+
+    let mut done = Vec::from_elem(count, false);
+    let infos = mut_buf_as_slice(obs, count * envs.observation_size(), |obs| {
+        mut_buf_as_slice(rewards, count, |rewards| {
+            envs.step(actions.as_slice(), obs, rewards, done.as_mut_slice())
+        })
+    });
+    mut_buf_as_slice(dones, count, |dones| {
+        mut_buf_as_slice(scores, count, |scores| {
+            for (i, info) in infos.iter().enumerate() {
+                dones[i] = if *done.get(i) { 1 } else { 0 };
+                scores[i] = info.score;
+            }
+        })
+    });
+    NATIVE_OK
+}
+
+/// Write four entries per environment to *masks*: 1 for the actions
+/// which change the board, 0 for the others.
+#[no_mangle]
+pub unsafe extern "C" fn g2048_env_action_masks(envs: *mut VecEnv,
+                                                masks: *mut u8) -> c_int {
+    if envs.is_null() || masks.is_null() {
+        return NATIVE_INVALID_ARGUMENT;
+    }
+    let envs = &*envs;
+    let mut valid = Vec::from_elem(4 * envs.len(), false);
+    envs.action_masks(valid.as_mut_slice());
+    mut_buf_as_slice(masks, valid.len(), |masks| {
+        for (i, v) in valid.iter().enumerate() {
+            masks[i] = if *v { 1 } else { 0 };
+        }
+    });
+    NATIVE_OK
+}
+
+/// The ABI version the library implements, NATIVE_ABI_VERSION.
+#[no_mangle]
+pub extern "C" fn g2048_abi_version() -> c_uint {
+    NATIVE_ABI_VERSION
+}
+
+#[cfg(test)]
+mod test {
+    use libc::c_char;
+    use super::{g2048_shift, g2048_spawn, g2048_game_over};
+    use super::{g2048_searcher_new, g2048_searcher_free, g2048_searcher_eval};
+    use super::{g2048_searcher_configure, g2048_searcher_move, g2048_searcher_hint};
+    use super::{g2048_env_new, g2048_env_configure, g2048_env_free};
+    use super::g2048_env_observation_size;
+    use super::{g2048_env_reset, g2048_env_step, g2048_env_action_masks};
+    use super::{NATIVE_OK, NATIVE_INVALID_ARGUMENT, NATIVE_INVALID_BOARD};
+    use super::{NATIVE_INVALID_MOVE, NATIVE_NO_MOVE};
+
+    #[test]
+    fn test_shift() {
+        let boards = vec![1u8, 1, 0, 0,
+                          0, 0, 0, 0,
+                          0, 0, 0, 0,
+                          0, 0, 0, 2,
+
+                          0, 0, 0, 0,
+                          0, 0, 0, 0,
+                          0, 0, 0, 0,
+                          0, 0, 0, 0];
+        let mut out = Vec::from_elem(32, 9u8);
+        let mut points = vec![7u64, 7];
+        let code = unsafe {
+            g2048_shift(4, 4, 2, boards.as_ptr(), 2, out.as_mut_ptr(), points.as_mut_ptr())
+        };
+        assert_eq!(code, NATIVE_OK);
+        assert_eq!(out.slice_to(16), [2u8, 0, 0, 0,
+                                      0, 0, 0, 0,
+                                      0, 0, 0, 0,
+                                      2, 0, 0, 0].as_slice());
+        assert_eq!(out.slice_from(16), boards.slice_from(16));
+        assert_eq!(points, vec![4u64, 0]);
+
+        let code = unsafe {
+            g2048_shift(4, 4, 2, boards.as_ptr(), 4, out.as_mut_ptr(), points.as_mut_ptr())
+        };
+        assert_eq!(code, NATIVE_INVALID_ARGUMENT);
+        let code = unsafe {
+            g2048_shift(9, 4, 1, boards.as_ptr(), 0, out.as_mut_ptr(), points.as_mut_ptr())
+        };
+        assert_eq!(code, NATIVE_INVALID_ARGUMENT);
+        let code = unsafe {
+            g2048_shift(4, 4, 2, boards.as_ptr(), 2, 0 as *mut u8, points.as_mut_ptr())
+        };
+        assert_eq!(code, NATIVE_INVALID_ARGUMENT);
+        let code = unsafe {
+            g2048_shift(4, 4, 2, boards.as_ptr(), 2, out.as_mut_ptr(), 0 as *mut u64)
+        };
+        assert_eq!(code, NATIVE_INVALID_ARGUMENT);
+    }
+
+    #[test]
+    fn test_spawn_and_game_over() {
+        let mut boards = Vec::from_elem(18, 0u8);
+        let code = unsafe { g2048_spawn(3, 3, 2, boards.as_mut_ptr(), 1) };
+        assert_eq!(code, NATIVE_OK);
+        assert_eq!(boards.slice_to(9).iter().filter(|t| **t != 0).count(), 1);
+        assert_eq!(boards.slice_from(9).iter().filter(|t| **t != 0).count(), 1);
+
+        let boards = vec![1u8, 2, 1,
+                          2, 1, 2,
+                          1, 2, 1,
+
+                          1, 2, 1,
+                          2, 1, 2,
+                          1, 2, 2];
+        let mut out = vec![9u8, 9];
+        let code = unsafe { g2048_game_over(3, 3, 2, boards.as_ptr(), out.as_mut_ptr()) };
+        assert_eq!(code, NATIVE_OK);
+        assert_eq!(out, vec![1u8, 0]);
+
+        let code = unsafe { g2048_game_over(3, 3, 2, boards.as_ptr(), 0 as *mut u8) };
+        assert_eq!(code, NATIVE_INVALID_ARGUMENT);
+
+        let boards = Vec::from_elem(9, 11u8);
+        let code = unsafe { g2048_game_over(3, 3, 1, boards.as_ptr(), out.as_mut_ptr()) };
+        assert_eq!(code, NATIVE_INVALID_BOARD);
+    }
+
+    #[test]
+    fn test_searcher() {
+        let searcher = g2048_searcher_new(2, 1);
+        assert!(!searcher.is_null());
+        let boards = vec![1u8, 0, 0, 0,
+                          1, 0, 0, 0,
+                          0, 0, 0, 0,
+                          0, 0, 0, 0,
+
+                          1, 2, 1, 2,
+                          2, 1, 2, 1,
+                          1, 2, 1, 2,
+                          2, 1, 2, 1];
+        let mut moves = vec![9i8, 9];
+        let mut scores = Vec::from_elem(8, 0i64);
+        let code = unsafe {
+            g2048_searcher_eval(searcher, 4, 4, 2, boards.as_ptr(),
+                                moves.as_mut_ptr(), scores.as_mut_ptr())
+        };
+        assert_eq!(code, NATIVE_OK);
+        assert!(*moves.get(0) >= 0 && *moves.get(0) < 4);
+        // moving left does not change the first board
+        assert_eq!(*scores.get(2), NATIVE_INVALID_MOVE);
+        assert!(*scores.get(0) != NATIVE_INVALID_MOVE);
+        assert_eq!(*moves.get(1), -1);
+        assert!(scores.slice_from(4).iter().all(|s| *s == NATIVE_INVALID_MOVE));
+        let code = unsafe {
+            g2048_searcher_eval(searcher, 4, 4, 2, boards.as_ptr(),
+                                0 as *mut i8, scores.as_mut_ptr())
+        };
+        assert_eq!(code, NATIVE_INVALID_ARGUMENT);
+        let code = unsafe {
+            g2048_searcher_eval(searcher, 4, 4, 2, boards.as_ptr(),
+                                moves.as_mut_ptr(), 0 as *mut i64)
+        };
+        assert_eq!(code, NATIVE_INVALID_ARGUMENT);
+        unsafe { g2048_searcher_free(searcher) };
+
+        assert!(g2048_searcher_new(2, 0).is_null());
+    }
+
+    #[test]
+    fn test_searcher_hint() {
+        let searcher = g2048_searcher_new(2, 1);
+        let boards = vec![1u8, 0, 0, 0,
+                          1, 0, 0, 0,
+                          0, 0, 0, 0,
+                          0, 0, 0, 0,
+
+                          1, 2, 1, 2,
+                          2, 1, 2, 1,
+                          1, 2, 1, 2,
+                          2, 1, 2, 1];
+        let mut moves = [9i8, ..2];
+        let mut scores = [0i64, ..8];
+        let mut losses = [-1.0f64, ..8];
+        let mut ranking = [9i8, ..8];
+        let code = unsafe {
+            g2048_searcher_hint(searcher, 4, 4, 2, boards.as_ptr(), moves.as_mut_ptr(),
+                                scores.as_mut_ptr(), losses.as_mut_ptr(),
+                                ranking.as_mut_ptr())
+        };
+        assert_eq!(code, NATIVE_OK);
+        let best = moves[0] as uint;
+        assert_eq!(ranking[0] as uint, best);
+        assert_eq!(losses[best], 0.0);
+        // moving left does not change the first board
+        assert!(losses[2].is_nan());
+        assert_eq!(ranking[3], -1);
+        let scale = if scores[best] != 0 { scores[best].abs() } else { 1 };
+        for j in range(1u, 3) {
+            let dir = ranking[j] as uint;
+            assert!(losses[dir] >= losses[ranking[j - 1] as uint]);
+            assert_eq!(losses[dir], (scores[best] - scores[dir]) as f64 / scale as f64);
+        }
+        assert_eq!(moves[1], -1);
+        assert!(losses.slice_from(4).iter().all(|loss| loss.is_nan()));
+        assert!(ranking.slice_from(4).iter().all(|dir| *dir == -1));
+
+        let code = unsafe {
+            g2048_searcher_hint(searcher, 4, 4, 2, boards.as_ptr(), moves.as_mut_ptr(),
+                                scores.as_mut_ptr(), 0 as *mut f64, ranking.as_mut_ptr())
+        };
+        assert_eq!(code, NATIVE_INVALID_ARGUMENT);
+        unsafe { g2048_searcher_free(searcher) };
+    }
+
+    #[test]
+    fn test_configure_and_move() {
+        let args = ["--depth".to_c_str(), "3".to_c_str(),
+                    "--min-depth".to_c_str(), "2".to_c_str(),
+                    "--max-depth".to_c_str(), "3".to_c_str()];
+        let argv = args.iter().map(|arg| arg.with_ref(|p| p)).collect::<Vec<*c_char>>();
+        let searcher = unsafe { g2048_searcher_configure(6, argv.as_ptr()) };
+        assert!(!searcher.is_null());
+
+        let board = [1u8, 0, 0, 0,
+                     1, 0, 0, 0,
+                     0, 0, 0, 0,
+                     0, 0, 0, 0];
+        let dir = unsafe { g2048_searcher_move(searcher, board.as_ptr()) };
+        assert!(dir >= 0 && dir < 4 && dir != 2);
+        let full = [1u8, 2, 1, 2,
+                    2, 1, 2, 1,
+                    1, 2, 1, 2,
+                    2, 1, 2, 1];
+        assert_eq!(unsafe { g2048_searcher_move(searcher, full.as_ptr()) },
+                   NATIVE_NO_MOVE);
+        let invalid = [18u8, ..16];
+        assert_eq!(unsafe { g2048_searcher_move(searcher, invalid.as_ptr()) },
+                   NATIVE_INVALID_BOARD);
+        unsafe { g2048_searcher_free(searcher) };
+
+        // the blockers of the rules keep the tiles from moving right
+        let args = ["--depth".to_c_str(), "2".to_c_str(),
+                    "--blocker".to_c_str(), "1,0".to_c_str(),
+                    "--blocker".to_c_str(), "1,1".to_c_str()];
+        let argv = args.iter().map(|arg| arg.with_ref(|p| p)).collect::<Vec<*c_char>>();
+        let searcher = unsafe { g2048_searcher_configure(6, argv.as_ptr()) };
+        assert!(!searcher.is_null());
+        let mut moves = [9i8];
+        let mut scores = [0i64, ..4];
+        let code = unsafe {
+            g2048_searcher_eval(searcher, 4, 4, 1, board.as_ptr(),
+                                moves.as_mut_ptr(), scores.as_mut_ptr())
+        };
+        assert_eq!(code, NATIVE_OK);
+        assert_eq!(scores[3], NATIVE_INVALID_MOVE);
+        assert!(scores[1] != NATIVE_INVALID_MOVE);
+        unsafe { g2048_searcher_free(searcher) };
+
+        let bogus = ["--depth".to_c_str(), "nine".to_c_str()];
+        let argv = bogus.iter().map(|arg| arg.with_ref(|p| p)).collect::<Vec<*c_char>>();
+        assert!(unsafe { g2048_searcher_configure(2, argv.as_ptr()) }.is_null());
+    }
+
+    #[test]
+    fn test_env_configure() {
+        let args = ["--blocker".to_c_str(), "1,1".to_c_str()];
+        let argv = args.iter().map(|arg| arg.with_ref(|p| p)).collect::<Vec<*c_char>>();
+        let envs = unsafe { g2048_env_configure(2, 3, 3, 1, 2, argv.as_ptr()) };
+        assert!(!envs.is_null());
+        // the planes of exponents 0 to 10 and the blocker plane
+        let size = unsafe { g2048_env_observation_size(envs) } as uint;
+        assert_eq!(size, 9 * 12);
+        let mut obs = Vec::from_elem(2 * size, -1.0f32);
+        assert_eq!(unsafe { g2048_env_reset(envs, 5, obs.as_mut_ptr()) }, NATIVE_OK);
+        assert_eq!(*obs.get(11 * 9 + 4), 1.0);
+        unsafe { g2048_env_free(envs) };
+
+        let args = ["--blocker".to_c_str(), "3,3".to_c_str()];
+        let argv = args.iter().map(|arg| arg.with_ref(|p| p)).collect::<Vec<*c_char>>();
+        assert!(unsafe { g2048_env_configure(2, 3, 3, 1, 2, argv.as_ptr()) }.is_null());
+    }
+
+    #[test]
+    fn test_env() {
+        assert!(g2048_env_new(0, 4, 4, 0).is_null());
+        assert!(g2048_env_new(2, 9, 4, 0).is_null());
+        let envs = g2048_env_new(2, 4, 4, 1);
+        assert!(!envs.is_null());
+        let size = unsafe { g2048_env_observation_size(envs) } as uint;
+        assert_eq!(size, 16 * 18);
+
+        let mut obs = Vec::from_elem(2 * size, -1.0f32);
+        assert_eq!(unsafe { g2048_env_reset(envs, 5, obs.as_mut_ptr()) }, NATIVE_OK);
+        assert_eq!(obs.iter().filter(|v| **v == 1.0).count(), 32);
+
+        let mut masks = [9u8, ..8];
+        assert_eq!(unsafe { g2048_env_action_masks(envs, masks.as_mut_ptr()) }, NATIVE_OK);
+        let actions: Vec<u8> = range(0u, 2).map(|i| {
+            range(0u8, 4).find(|a| masks[4 * i + *a as uint] == 1).unwrap()
+        }).collect();
+        let mut rewards = [-1.0f64, ..2];
+        let mut dones = [9u8, ..2];
+        let mut scores = [9u64, ..2];
+        let code = unsafe {
+            g2048_env_step(envs, actions.as_ptr(), obs.as_mut_ptr(), rewards.as_mut_ptr(),
+                           dones.as_mut_ptr(), scores.as_mut_ptr())
+        };
+        assert_eq!(code, NATIVE_OK);
+        assert_eq!(dones.as_slice(), [0u8, 0].as_slice());
+        assert_eq!(scores[0] as f64, rewards[0]);
+
+        let invalid = [4u8, 0];
+        let code = unsafe {
+            g2048_env_step(envs, invalid.as_ptr(), obs.as_mut_ptr(), rewards.as_mut_ptr(),
+                           dones.as_mut_ptr(), scores.as_mut_ptr())
+        };
+        assert_eq!(code, NATIVE_INVALID_ARGUMENT);
+        let code = unsafe {
+            g2048_env_step(envs, actions.as_ptr(), obs.as_mut_ptr(), 0 as *mut f64,
+                           dones.as_mut_ptr(), scores.as_mut_ptr())
+        };
+        assert_eq!(code, NATIVE_INVALID_ARGUMENT);
+        let code = unsafe {
+            g2048_env_step(envs, actions.as_ptr(), obs.as_mut_ptr(), rewards.as_mut_ptr(),
+                           0 as *mut u8, scores.as_mut_ptr())
+        };
+        assert_eq!(code, NATIVE_INVALID_ARGUMENT);
+        assert_eq!(unsafe { g2048_env_reset(envs, 5, 0 as *mut f32) },
+                   NATIVE_INVALID_ARGUMENT);
+        assert_eq!(unsafe { g2048_env_action_masks(envs, 0 as *mut u8) },
+                   NATIVE_INVALID_ARGUMENT);
+        unsafe { g2048_env_free(envs) };
+    }
+}
@@ -0,0 +1,232 @@
+use std::comm;
+use std::comm::Select;
+use std::io;
+use std::io::process::{Command, Process, ProcessExit, InheritFd};
+use std::io::timer::Timer;
+use std::task;
+use time;
+
+use board::{Board, Direction, Up, Down, Left, Right};
+use config::Config;
+use error::{AiError, IoFailure};
+use protocol::write_request;
+use record::{GameRecord, GameEnd, NoMoveLeft, TargetReached, AiExited, TimedOut};
+use record::{InvalidResponse, InvalidMove, PipeFailure};
+
+static ALL_DIRECTIONS: [Direction, ..4] = [Up, Down, Left, Right];
+
+/// How long an AI gets to exit after its input was closed before it is
+/// killed.
+static EXIT_GRACE_MS: u64 = 1000;
+
+/// An AI speaking the pipe protocol in a child process. Its stderr is
+/// passed through to ours.
+pub struct PipeAi {
+    process: Process,
+    /// The bytes the AI writes, read on a task of their own, so that
+    /// waiting for one can be given up after the time limit.
+    responses: Receiver<io::IoResult<u8>>,
+    timer: Timer
+}
+
+impl PipeAi {
+    /// Start *cmd*, the program followed by its arguments.
+    pub fn spawn(cmd: &[String]) -> io::IoResult<PipeAi> {
+        let mut process = try!(Command::new(cmd[0].as_slice())
+                               .args(cmd.slice_from(1))
+                               .stderr(InheritFd(2))
+                               .spawn());
+        let stdout = process.stdout.take_unwrap();
+        let (tx, rx) = comm::channel();
+        task::spawn(proc() {
+            let mut stdout = stdout;
+            loop {
+                let byte = stdout.read_byte();
+                let failed = byte.is_err();
+                if tx.send_opt(byte).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Ok(PipeAi { process: process,
+                    responses: rx,
+                    timer: try!(Timer::new()) })
+    }
+
+    /// Send *board* and wait up to *timeout_ms* for the move. The AI
+    /// must not be asked again after an error.
+    pub fn request(&mut self, board: &Board, timeout_ms: u64) -> Result<Direction, GameEnd> {
+        let written = write_request(board, self.process.stdin.get_mut_ref());
+        if written.is_err() {
+            // most likely the AI is gone; its exit status tells why
+            return Err(self.exit_end());
+        }
+
+        let timeout = self.timer.oneshot(timeout_ms);
+        let response = {
+            let select = Select::new();
+            let mut response = select.handle(&self.responses);
+            let mut timer = select.handle(&timeout);
+            unsafe {
+                response.add();
+                timer.add();
+            }
+            if select.wait() == response.id() {
+                Some(response.recv_opt())
+            } else {
+                None
+            }
+        };
+        match response {
+            None => {
+                let _ = self.process.signal_kill();
+                Err(TimedOut)
+            }
+            Some(Ok(Ok(code))) if (code as uint) < ALL_DIRECTIONS.len() =>
+                Ok(ALL_DIRECTIONS[code as uint]),
+            Some(Ok(Ok(code))) => Err(InvalidResponse(code)),
+            Some(Ok(Err(ref e))) if e.kind != io::EndOfFile =>
+                Err(PipeFailure(format!("{}", e))),
+            // the output was closed
+            Some(_) => Err(self.exit_end())
+        }
+    }
+
+    fn exit_end(&mut self) -> GameEnd {
+        match self.close() {
+            Ok(status) => AiExited(status),
+            Err(e) => PipeFailure(format!("{}", e))
+        }
+    }
+
+    /// Close the input of the AI, which tells it that no more requests
+    /// come, and wait for it to exit. It is killed if it does not exit
+    /// within a second.
+    pub fn close(&mut self) -> io::IoResult<ProcessExit> {
+        drop(self.process.stdin.take());
+        self.process.set_timeout(Some(EXIT_GRACE_MS));
+        let status = self.process.wait();
+        self.process.set_timeout(None);
+        match status {
+            Err(ref e) if e.kind == io::TimedOut => {
+                try!(self.process.signal_kill());
+                self.process.wait()
+            }
+            status => status
+        }
+    }
+}
+
+/// Play the game of *seed* under the rules of *config* with a new
+/// process of the AI *cmd*, which gets *timeout_ms* per move. The
+/// protocol does not carry the rules, so rules other than those of the
+/// original game are appended to the command line of the AI, see
+/// Config::rules_args. The game ends when it is over, or as soon as
+/// the AI fails to give a valid move.
+pub fn host_game(cmd: &[String], config: &Config, width: uint, height: uint,
+                 seed: u32, timeout_ms: u64) -> Result<GameRecord, AiError> {
+    let mut game = try!(config.new_game(width, height, seed));
+    let mut cmd = Vec::from_slice(cmd);
+    cmd.push_all_move(config.rules_args());
+    let mut record = GameRecord::new(cmd.connect(" ").as_slice(), seed, &game);
+    let mut ai = try!(PipeAi::spawn(cmd.as_slice()).map_err(|e| IoFailure(e)));
+    loop {
+        if game.is_over() {
+            record.end = if game.won() { TargetReached } else { NoMoveLeft };
+            // the AI has done its job, so how it exits does not matter
+            let _ = ai.close();
+            break;
+        }
+        let start = time::precise_time_ns();
+        match ai.request(game.board(), timeout_ms) {
+            Ok(dir) => {
+                let elapsed = time::precise_time_ns() - start;
+                if game.play(dir).is_none() {
+                    record.end = InvalidMove(dir);
+                    let _ = ai.close();
+                    break;
+                }
+                record.push(dir, elapsed, &game);
+            }
+            Err(end) => {
+                record.end = end;
+                let _ = ai.close();
+                break;
+            }
+        }
+    }
+    Ok(record)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::process::ExitStatus;
+    use config::Config;
+    use record::{GameEnd, AiExited, TimedOut, InvalidResponse, InvalidMove, NoMoveLeft};
+    use super::host_game;
+
+    fn sh(script: &str) -> Vec<String> {
+        vec![String::from_str("/bin/sh"), String::from_str("-c"),
+             String::from_str(script)]
+    }
+
+    fn end_of(script: &str, timeout_ms: u64) -> GameEnd {
+        host_game(sh(script).as_slice(), &Config::default(),
+                  4, 4, 1, timeout_ms).ok().unwrap().end
+    }
+
+    #[test]
+    fn test_host_game() {
+        // answers every request with up, left, down, right in turn,
+        // until the board is full or one of them is invalid
+        let script = "while :; do \
+                          for m in 0 2 1 3; do \
+                              [ \"$(head -c 17 | wc -c)\" -eq 17 ] || exit 0; \
+                              printf \"\\\\$m\"; \
+                          done; \
+                      done";
+        let record = host_game(sh(script).as_slice(), &Config::default(),
+                               4, 4, 1, 5000).ok().unwrap();
+        let replayed = record.replay(&Config::default()).ok().unwrap();
+        assert!(replayed.board() == &record.board);
+        assert_eq!(record.move_times_ns.len(), record.moves.len());
+        match record.end {
+            NoMoveLeft => assert!(replayed.is_over()),
+            InvalidMove(dir) => {
+                let (moved, _) = replayed.board().shifted_board(dir);
+                assert!(moved == record.board);
+            }
+            end => fail!("unexpected end: {}", end)
+        }
+    }
+
+    #[test]
+    fn test_host_game_failures() {
+        match end_of("exit 3", 5000) {
+            AiExited(ExitStatus(3)) => (),
+            end => fail!("unexpected end: {}", end)
+        }
+        match end_of("head -c 17 >/dev/null; printf '\\011'; cat >/dev/null", 5000) {
+            InvalidResponse(9) => (),
+            end => fail!("unexpected end: {}", end)
+        }
+        match end_of("exec sleep 10", 100) {
+            TimedOut => (),
+            end => fail!("unexpected end: {}", end)
+        }
+    }
+
+    #[test]
+    fn test_host_game_passes_rules() {
+        let mut config = Config::default();
+        config.rules.blockers.push((1, 1));
+        // sh takes the first argument after the script as $0
+        let record = host_game(sh("exit $#").as_slice(), &config,
+                               4, 4, 1, 5000).ok().unwrap();
+        assert!(record.ai.as_slice().ends_with(" --blocker 1,1"));
+        match record.end {
+            AiExited(ExitStatus(1)) => (),
+            end => fail!("unexpected end: {}", end)
+        }
+    }
+}
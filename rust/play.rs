@@ -0,0 +1,540 @@
+extern crate getopts;
+extern crate rand;
+extern crate time;
+extern crate g2048;
+
+use std::cmp;
+use std::io;
+use std::io::process::{Command, InheritFd};
+use std::io::timer;
+use std::os;
+use std::str;
+use rand::Rng;
+
+use g2048::board::{Board, Direction, Up, Down, Left, Right, check_size};
+use g2048::config;
+use g2048::config::Config;
+use g2048::error::{AiError, IoFailure, ConfigFailure, SearchFailure, EXIT_CONFIG};
+use g2048::game::{Game, Checkpoint};
+use g2048::hint::Hint;
+use g2048::record;
+use g2048::record::{GameRecord, GameEnd, NoMoveLeft, TargetReached, Unfinished};
+use g2048::search::EvalContext;
+
+static ALL_DIRECTIONS: [Direction, ..4] = [Up, Down, Left, Right];
+
+/// Background colours of the tiles by exponent, xterm 256-colour
+/// approximations of the palette of the urwid frontend. Larger tiles
+/// are white.
+static TILE_COLOURS: [u8, ..12] = [16, 224, 223, 216, 209, 210, 203, 222, 221, 220, 214, 208];
+static BIG_TILE_COLOUR: u8 = 231;
+static BLOCKER_COLOUR: u8 = 240;
+
+/// Size of a cell on the screen.
+static CELL_WIDTH: uint = 8;
+static CELL_HEIGHT: uint = 3;
+
+/// How often the keyboard is polled while waiting.
+static POLL_MS: u64 = 10;
+
+static MIN_DELAY_MS: u64 = 25;
+static MAX_DELAY_MS: u64 = 10000;
+
+enum Key {
+    MoveKey(Direction),
+    HintKey,
+    HintsKey,
+    UndoKey,
+    WatchKey,
+    FasterKey,
+    SlowerKey,
+    NewGameKey,
+    QuitKey,
+    OtherKey
+}
+
+fn io_error(desc: &'static str) -> io::IoError {
+    io::IoError { kind: io::OtherIoError, desc: desc, detail: None }
+}
+
+/// Run stty on our terminal and return its output.
+fn stty(args: &[&str]) -> io::IoResult<String> {
+    let output = try!(Command::new("stty").args(args).stdin(InheritFd(0)).output());
+    if !output.status.success() {
+        return Err(io_error("stdin is not a terminal"));
+    }
+    match str::from_utf8(output.output.as_slice()) {
+        Some(text) => Ok(String::from_str(text.trim())),
+        None => Err(io_error("unexpected output of stty"))
+    }
+}
+
+/// The terminal, switched to raw mode and the alternate screen for as
+/// long as this lives. Reads do not wait for input in this mode, so
+/// that the AI can play on while no key is pressed.
+struct Terminal {
+    saved: String,
+    input: io::stdio::StdReader,
+    output: io::stdio::StdWriter
+}
+
+impl Terminal {
+    fn open() -> io::IoResult<Terminal> {
+        let saved = try!(stty(&["-g"]));
+        try!(stty(&["raw", "-echo", "min", "0", "time", "0"]));
+        let mut term = Terminal { saved: saved,
+                                  input: io::stdio::stdin_raw(),
+                                  output: io::stdio::stdout_raw() };
+        try!(term.output.write_str("\x1b[?1049h\x1b[?25l"));
+        Ok(term)
+    }
+
+    /// Replace the screen contents by *lines*.
+    fn draw(&mut self, lines: &[String]) -> io::IoResult<()> {
+        let mut frame = String::from_str("\x1b[H\x1b[2J");
+        frame.push_str(lines.connect("\r\n").as_slice());
+        try!(self.output.write_str(frame.as_slice()));
+        self.output.flush()
+    }
+
+    fn byte(&mut self) -> io::IoResult<Option<u8>> {
+        match self.input.read_byte() {
+            Ok(byte) => Ok(Some(byte)),
+            // nothing was typed
+            Err(ref e) if e.kind == io::EndOfFile => Ok(None),
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Wait for a byte for up to *timeout_ms*.
+    fn byte_within(&mut self, timeout_ms: u64) -> io::IoResult<Option<u8>> {
+        let deadline = time::precise_time_ns() + timeout_ms * 1000000;
+        loop {
+            match try!(self.byte()) {
+                Some(byte) => return Ok(Some(byte)),
+                None if time::precise_time_ns() >= deadline => return Ok(None),
+                None => timer::sleep(POLL_MS)
+            }
+        }
+    }
+
+    /// Wait for a key, for up to *timeout_ms* if given.
+    fn key(&mut self, timeout_ms: Option<u64>) -> io::IoResult<Option<Key>> {
+        let byte = match timeout_ms {
+            Some(ms) => try!(self.byte_within(ms)),
+            None => {
+                let mut byte = None;
+                while byte.is_none() {
+                    byte = try!(self.byte_within(1000));
+                }
+                byte
+            }
+        };
+        match byte {
+            None => Ok(None),
+            // the arrow keys send ESC [ A to ESC [ D; a lone ESC quits
+            Some(27) => match try!(self.byte_within(50)) {
+                Some(91) => match try!(self.byte_within(50)) {
+                    Some(65) => Ok(Some(MoveKey(Up))),
+                    Some(66) => Ok(Some(MoveKey(Down))),
+                    Some(67) => Ok(Some(MoveKey(Right))),
+                    Some(68) => Ok(Some(MoveKey(Left))),
+                    _ => Ok(Some(OtherKey))
+                },
+                None => Ok(Some(QuitKey)),
+                Some(_) => Ok(Some(OtherKey))
+            },
+            Some(byte) => Ok(Some(match byte as char {
+                'k' => MoveKey(Up),
+                'j' => MoveKey(Down),
+                'h' => MoveKey(Left),
+                'l' => MoveKey(Right),
+                '?' => HintKey,
+                'c' => HintsKey,
+                'u' => UndoKey,
+                'a' => WatchKey,
+                '+' => FasterKey,
+                '-' => SlowerKey,
+                'n' => NewGameKey,
+                // ctrl-c does not interrupt in raw mode
+                'q' | 'Q' | '\x03' => QuitKey,
+                _ => OtherKey
+            }))
+        }
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.output.write_str("\x1b[?25h\x1b[?1049l");
+        let _ = self.output.flush();
+        let _ = stty(&[self.saved.as_slice()]);
+    }
+}
+
+fn centred(text: &str, width: uint) -> String {
+    let left = (width - cmp::min(text.len(), width)) / 2;
+    let right = width - cmp::min(text.len() + left, width);
+    format!("{}{}{}", " ".repeat(left), text, " ".repeat(right))
+}
+
+/// Draw *board* with coloured tiles, CELL_HEIGHT lines per row.
+fn board_lines(board: &Board) -> Vec<String> {
+    let mut lines = Vec::new();
+    for y in range(0, board.height()) {
+        for line in range(0, CELL_HEIGHT) {
+            let mut text = String::new();
+            for x in range(0, board.width()) {
+                let tile = board.get(x, y);
+                let colour = if board.is_blocker(x, y) {
+                    BLOCKER_COLOUR
+                } else if (tile as uint) < TILE_COLOURS.len() {
+                    TILE_COLOURS[tile as uint]
+                } else {
+                    BIG_TILE_COLOUR
+                };
+                let label = if line == CELL_HEIGHT / 2 && tile > 0 {
+                    format!("{}", 1u64 << tile as uint)
+                } else {
+                    String::new()
+                };
+                text.push_str(format!("\x1b[38;5;16;48;5;{}m{}\x1b[0m ",
+                                      colour,
+                                      centred(label.as_slice(), CELL_WIDTH - 1)).as_slice());
+            }
+            lines.push(text);
+        }
+        lines.push(String::new());
+    }
+    lines
+}
+
+/// Describe the expected score of each move by *hint*, with the
+/// recommended move shown in reverse video.
+fn hint_line(label: &str, hint: &Hint) -> String {
+    let mut parts = Vec::new();
+    for (i, dir) in ALL_DIRECTIONS.iter().enumerate() {
+        let score = match hint.scores[i] {
+            Some(score) => format!("{}", score),
+            None => String::from_str("-")
+        };
+        let part = format!("{}: {}", *dir, score);
+        parts.push(match hint.best {
+            Some(best) if best as uint == i => format!("\x1b[7m{}\x1b[0m", part),
+            _ => part
+        });
+    }
+    format!("{} at depth {}: {}", label, hint.depth, parts.connect("  "))
+}
+
+/// A game on the screen, played by the player or watched while the AI
+/// plays it.
+struct Ui {
+    config: Config,
+    ctx: EvalContext,
+    size: uint,
+    seed: u32,
+    game: Game,
+    /// The states before each move, for undo.
+    history: Vec<Checkpoint>,
+    watching: bool,
+    delay_ms: u64,
+    /// How the AI rated the moves before its last one, shown while
+    /// watching.
+    last_move: Option<Hint>,
+    /// The advice of the AI on the current board, once asked for.
+    hint: Option<Hint>,
+    /// Whether to advise on every board.
+    hints: bool,
+    /// Warn about moves expected to score this share less than the
+    /// recommended one.
+    max_loss: Option<f64>,
+    message: String,
+    /// The moves played so far, without those taken back.
+    record: GameRecord,
+    /// Where finished and abandoned games are appended.
+    record_out: Option<io::File>,
+    /// Whether the game was written to record_out already.
+    recorded: bool,
+    /// When the last move was played, to record the time per move.
+    moved_at: u64
+}
+
+impl Ui {
+    fn new_game(&mut self, seed: u32) -> Result<(), AiError> {
+        self.seed = seed;
+        self.game = try!(self.config.new_game(self.size, self.size, seed));
+        self.record = GameRecord::new("play", seed, &self.game);
+        self.recorded = false;
+        self.moved_at = time::precise_time_ns();
+        self.history.clear();
+        self.last_move = None;
+        self.hint = None;
+        self.message = String::new();
+        Ok(())
+    }
+
+    /// Play *dir*, returning whether it moved any tile.
+    fn play(&mut self, dir: Direction) -> bool {
+        let checkpoint = self.game.checkpoint();
+        match self.game.play(dir) {
+            Some(_) => {
+                let now = time::precise_time_ns();
+                self.record.push(dir, now - self.moved_at, &self.game);
+                self.moved_at = now;
+                self.history.push(checkpoint);
+                self.hint = None;
+                self.message = String::new();
+                true
+            }
+            None => {
+                self.message = format!("{} does not move any tile", dir);
+                false
+            }
+        }
+    }
+
+    /// The advice of the AI on the current board.
+    fn current_hint(&mut self) -> Result<Hint, AiError> {
+        if self.hint.is_none() {
+            let hint = try!(Hint::search(&mut self.ctx, &self.config.policy,
+                                         self.game.board()).map_err(|e| SearchFailure(e)));
+            self.hint = Some(hint);
+        }
+        Ok(self.hint.get_ref().clone())
+    }
+
+    /// Play *dir* for the player, with a warning if the AI expects it
+    /// to score much less than its recommendation.
+    fn player_move(&mut self, dir: Direction) -> Result<(), AiError> {
+        let warning = match self.max_loss {
+            Some(max_loss) if !self.game.is_over() => {
+                let hint = try!(self.current_hint());
+                match (hint.loss(dir), hint.best) {
+                    (Some(loss), Some(best)) if loss > max_loss =>
+                        Some(format!("Careful: {} is expected to score {:.0f}% \
+                                      less than {}", dir, loss * 100.0, best)),
+                    _ => None
+                }
+            }
+            _ => None
+        };
+        if self.play(dir) {
+            match warning {
+                Some(warning) => self.message = warning,
+                None => ()
+            }
+        }
+        Ok(())
+    }
+
+    fn undo(&mut self) {
+        self.watching = false;
+        self.last_move = None;
+        match self.history.pop() {
+            Some(checkpoint) => {
+                self.game.restore(&checkpoint);
+                self.record.moves.pop();
+                self.record.move_times_ns.pop();
+                self.record.board = self.game.board().clone();
+                self.record.score = self.game.score();
+                self.recorded = false;
+                self.hint = None;
+                self.message = String::new();
+            }
+            None => self.message = String::from_str("nothing to undo")
+        }
+    }
+
+    /// Append the game to the record file, unless it was written
+    /// already or has no moves.
+    fn save(&mut self, end: GameEnd) -> Result<(), AiError> {
+        if self.recorded || self.record.moves.len() == 0 {
+            return Ok(());
+        }
+        match self.record_out {
+            Some(ref mut out) => {
+                self.record.end = end;
+                try!(self.record.write_json(out).map_err(|e| IoFailure(e)));
+            }
+            None => ()
+        }
+        self.recorded = true;
+        Ok(())
+    }
+
+    /// Let the AI make the next move.
+    fn ai_move(&mut self) -> Result<(), AiError> {
+        let hint = try!(self.current_hint());
+        match hint.best {
+            Some(dir) => {
+                self.play(dir);
+            }
+            None => {
+                self.watching = false;
+                self.message = String::from_str("the AI found no move");
+            }
+        }
+        self.last_move = Some(hint);
+        Ok(())
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let mut lines = board_lines(self.game.board());
+        lines.push(format!("Score: {:06u}   Moves: {}   Seed: {}",
+                           self.game.score(), self.game.moves(), self.seed));
+        if self.game.won() {
+            lines.push(String::from_str("You won!"));
+        } else if self.game.is_over() {
+            lines.push(String::from_str("Game over."));
+        } else {
+            lines.push(self.message.clone());
+        }
+        lines.push(String::new());
+        if self.watching {
+            match self.last_move {
+                Some(ref hint) => lines.push(hint_line("AI scores", hint)),
+                None => lines.push(String::new())
+            }
+            lines.push(format!("Watching the AI, {} ms per move. \
+                                [+/-] speed  [a] take over  [u] undo  [q] quit",
+                               self.delay_ms));
+        } else {
+            match self.hint {
+                Some(ref hint) if !self.game.is_over() => lines.push(hint_line("Hint", hint)),
+                _ => lines.push(String::new())
+            }
+            lines.push(String::from_str(
+                "[arrows/hjkl] move  [?] hint  [c] hints on/off  [u] undo  \
+                 [a] watch the AI  [n] new game  [q] quit"));
+        }
+        lines
+    }
+
+    fn handle(&mut self, key: Key) -> Result<(), AiError> {
+        match key {
+            MoveKey(dir) => if !self.watching { try!(self.player_move(dir)) },
+            HintKey => if !self.game.is_over() { try!(self.current_hint()); },
+            HintsKey => self.hints = !self.hints,
+            UndoKey => self.undo(),
+            WatchKey => {
+                self.watching = !self.watching;
+                self.last_move = None;
+            }
+            FasterKey => self.delay_ms = cmp::max(self.delay_ms / 2, MIN_DELAY_MS),
+            SlowerKey => self.delay_ms = cmp::min(self.delay_ms * 2, MAX_DELAY_MS),
+            NewGameKey => {
+                self.watching = false;
+                try!(self.save(Unfinished));
+                try!(self.new_game(rand::task_rng().gen::<u32>()));
+            }
+            QuitKey | OtherKey => ()
+        }
+        Ok(())
+    }
+
+    /// Show the game and act on keys until the player quits.
+    fn run(&mut self, term: &mut Terminal) -> Result<(), AiError> {
+        loop {
+            if self.hints && !self.watching && !self.game.is_over() {
+                try!(self.current_hint());
+            }
+            try!(term.draw(self.lines().as_slice()).map_err(|e| IoFailure(e)));
+            let timeout = if self.watching && !self.game.is_over() {
+                Some(self.delay_ms)
+            } else {
+                None
+            };
+            match try!(term.key(timeout).map_err(|e| IoFailure(e))) {
+                Some(QuitKey) => return self.save(Unfinished),
+                Some(key) => try!(self.handle(key)),
+                None => try!(self.ai_move())
+            }
+            if self.game.is_over() {
+                let end = if self.game.won() { TargetReached } else { NoMoveLeft };
+                try!(self.save(end));
+            }
+        }
+    }
+}
+
+fn options() -> Vec<getopts::OptGroup> {
+    let mut opts = config::options();
+    opts.push(getopts::optopt(
+        "", "size", "width and height of the board (4)", "N"));
+    opts.push(getopts::optopt(
+        "", "seed", "seed of the game (random)", "N"));
+    opts.push(getopts::optflag(
+        "", "watch", "start by watching the AI play"));
+    opts.push(getopts::optopt(
+        "", "delay", "time between the moves of the AI, in milliseconds (500)", "MS"));
+    opts.push(getopts::optflag(
+        "", "hints", "show the advice of the AI on every board"));
+    opts.push(getopts::optopt(
+        "", "warn", "warn about moves the AI expects to score PERCENT less than \
+                     its recommendation", "PERCENT"));
+    opts.push(getopts::optopt(
+        "", "record", "append each game as a line of JSON to PATH", "PATH"));
+    opts
+}
+
+fn run(args: &[String]) -> Result<(), AiError> {
+    let opts = options();
+    let (config, matches) = match try!(config::parse_args(args, opts.as_slice())) {
+        Some(parsed) => parsed,
+        None => {
+            println!("{}", getopts::usage(
+                "Usage: play [OPTIONS]\n\n\
+                 Play 2048 in the terminal, or watch the AI play it. The search \
+                 options\nconfigure the AI, the game variant options the game.",
+                opts.as_slice()));
+            return Ok(());
+        }
+    };
+    let size = try!(config::parse_or(&matches, "size", 4u));
+    try!(check_size(size, size).map_err(|e| ConfigFailure(format!("{}", e))));
+    let seed = try!(config::parse_or(&matches, "seed", rand::task_rng().gen::<u32>()));
+    let delay_ms = try!(config::parse_or(&matches, "delay", 500u64));
+    let max_loss = match matches.opt_str("warn") {
+        Some(_) => Some(try!(config::parse_or(&matches, "warn", 0f64)) / 100.0),
+        None => None
+    };
+
+    let record_out = try!(record::open_record_file(matches.opt_str("record")));
+
+    let game = try!(config.new_game(size, size, seed));
+    let record = GameRecord::new("play", seed, &game);
+    let mut ui = Ui { ctx: config.context(),
+                      config: config,
+                      size: size,
+                      seed: seed,
+                      game: game,
+                      history: Vec::new(),
+                      watching: matches.opt_present("watch"),
+                      delay_ms: cmp::min(cmp::max(delay_ms, MIN_DELAY_MS), MAX_DELAY_MS),
+                      last_move: None,
+                      hint: None,
+                      hints: matches.opt_present("hints"),
+                      max_loss: max_loss,
+                      message: String::new(),
+                      record: record,
+                      record_out: record_out,
+                      recorded: false,
+                      moved_at: time::precise_time_ns() };
+    let mut term = try!(Terminal::open().map_err(|e| IoFailure(e)));
+    ui.run(&mut term)
+}
+
+fn main() {
+    let args = os::args();
+    match run(args.tail()) {
+        Ok(()) => (),
+        Err(e) => {
+            let _ = writeln!(&mut io::stderr(), "play: {}", e);
+            if e.exit_code() == EXIT_CONFIG {
+                let _ = writeln!(&mut io::stderr(), "play: try --help");
+            }
+            os::set_exit_status(e.exit_code());
+        }
+    }
+}
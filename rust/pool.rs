@@ -0,0 +1,195 @@
+use std::comm;
+use std::io::timer;
+use std::sync::atomics::{AtomicBool, SeqCst, INIT_ATOMIC_BOOL};
+use std::sync::deque::{BufferPool, Worker, Stealer, Data, Empty, Abort};
+use std::task;
+use sync::{Arc, Mutex};
+
+use logging;
+use logging::SharedSink;
+
+/// A unit of work for the pool. It gets the scope of the worker
+/// thread it runs on, so that it can split off more jobs.
+pub type Job = proc(&mut Scope):Send;
+
+/// Number of failed attempts to find a job before an idle worker
+/// starts sleeping between attempts.
+static IDLE_SPINS: uint = 64;
+
+struct Shared {
+    /// Jobs submitted from outside the pool.
+    injector: Mutex<Vec<Job>>,
+    stealers: Vec<Stealer<Job>>,
+    shutdown: AtomicBool
+}
+
+/// A fixed set of worker threads. Each worker keeps its own deque of
+/// jobs and takes jobs from the other workers when it runs out.
+pub struct Pool {
+    shared: Arc<Shared>,
+    threads: uint
+}
+
+/// The view of a worker thread on the pool, passed to each job.
+pub struct Scope {
+    index: uint,
+    worker: Worker<Job>,
+    shared: Arc<Shared>
+}
+
+impl Pool {
+    /// Start a pool with *threads* workers. The log records of the
+    /// jobs go to *sink* if given, as they would on the calling task.
+    pub fn new(threads: uint, sink: Option<SharedSink>) -> Pool {
+        assert!(threads >= 1);
+        let buffers = BufferPool::new();
+        let mut workers = Vec::new();
+        let mut stealers = Vec::new();
+        for _ in range(0, threads) {
+            let (worker, stealer) = buffers.deque();
+            workers.push(worker);
+            stealers.push(stealer);
+        }
+
+        let shared = Arc::new(Shared { injector: Mutex::new(Vec::new()),
+                                       stealers: stealers,
+                                       shutdown: INIT_ATOMIC_BOOL });
+        for (index, worker) in workers.move_iter().enumerate() {
+            let scope = Scope { index: index,
+                                worker: worker,
+                                shared: shared.clone() };
+            let sink = sink.clone();
+            task::spawn(proc() {
+                match sink {
+                    Some(ref sink) => logging::install(sink),
+                    None => ()
+                }
+                let mut scope = scope;
+                scope.work();
+            });
+        }
+        Pool { shared: shared, threads: threads }
+    }
+
+    pub fn threads(&self) -> uint {
+        self.threads
+    }
+
+    /// Run *f* on one of the workers and wait for its result.
+    pub fn run<T: Send>(&self, f: proc(&mut Scope):Send -> T) -> T {
+        let (tx, rx) = comm::channel();
+        let job: Job = proc(scope: &mut Scope) {
+            tx.send(f(scope));
+        };
+        {
+            let mut injector = self.shared.injector.lock();
+            injector.push(job);
+        }
+        rx.recv()
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, SeqCst);
+    }
+}
+
+impl Scope {
+    /// Make *job* available to this worker and any idle worker.
+    pub fn spawn(&mut self, job: Job) {
+        self.worker.push(job);
+    }
+
+    fn find_job(&mut self) -> Option<Job> {
+        match self.worker.pop() {
+            Some(job) => return Some(job),
+            None => ()
+        }
+        let count = self.shared.stealers.len();
+        for i in range(1, count) {
+            let victim = (self.index + i) % count;
+            loop {
+                match self.shared.stealers.get(victim).steal() {
+                    Data(job) => return Some(job),
+                    Empty => break,
+                    Abort => ()
+                }
+            }
+        }
+        let mut injector = self.shared.injector.lock();
+        injector.pop()
+    }
+
+    /// Run one pending job. Returns false if there was none.
+    pub fn run_one(&mut self) -> bool {
+        match self.find_job() {
+            Some(job) => {
+                job(self);
+                true
+            }
+            None => false
+        }
+    }
+
+    /// Run pending jobs until *done* returns true. This is how a job
+    /// waits for the jobs it split off: its own jobs are on top of
+    /// its deque, so it usually runs them itself, unless other
+    /// workers stole them.
+    pub fn wait_until(&mut self, done: || -> bool) {
+        while !done() {
+            if !self.run_one() {
+                task::deschedule();
+            }
+        }
+    }
+
+    fn work(&mut self) {
+        let mut idle = 0u;
+        while !self.shared.shutdown.load(SeqCst) {
+            if self.run_one() {
+                idle = 0;
+            } else if idle < IDLE_SPINS {
+                idle += 1;
+                task::deschedule();
+            } else {
+                timer::sleep(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::comm;
+    use super::{Pool, Scope};
+
+    fn sum(scope: &mut Scope, from: uint, to: uint) -> uint {
+        if to - from <= 4 {
+            return range(from, to).fold(0, |a, b| a + b);
+        }
+        let mid = (from + to) / 2;
+        let (tx, rx) = comm::channel();
+        scope.spawn(proc(scope: &mut Scope) {
+            tx.send(sum(scope, mid, to));
+        });
+        let left = sum(scope, from, mid);
+        let mut right = None;
+        scope.wait_until(|| {
+            if right.is_none() {
+                right = rx.try_recv().ok();
+            }
+            right.is_some()
+        });
+        left + right.unwrap()
+    }
+
+    #[test]
+    fn test_nested_jobs() {
+        for threads in range(1u, 5) {
+            let pool = Pool::new(threads, None);
+            let result = pool.run(proc(scope: &mut Scope) { sum(scope, 0, 1000) });
+            assert_eq!(result, 999 * 1000 / 2);
+        }
+    }
+}
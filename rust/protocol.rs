@@ -0,0 +1,522 @@
+use std::fmt;
+use std::io;
+use std::str;
+
+use board::{Board, BoardError, check_size};
+use error::{AiError, ProtocolFailure, IoFailure, SearchFailure};
+use search::{Decision, DepthPolicy, EvalContext, Move, NoMove};
+
+static REQUEST_SIZE: uint = 17;
+
+/// Extension byte announcing a board of another size than 4x4. The
+/// request then goes on with the width, the height and one byte per
+/// cell; the 16 board bytes before the extension byte are ignored.
+pub static EXT_SIZED_BOARD: u8 = 1;
+
+/// Extension byte of a request which configures the searcher instead
+/// of asking for a move. It is followed by the length of the options
+/// as two bytes, high byte first, and the options, separated by spaces
+/// as on the command line. Only the server mode of the AI understands
+/// it; the answer is CONFIGURE_OK or CONFIGURE_REJECTED.
+pub static EXT_CONFIGURE: u8 = 2;
+
+/// The answer to an EXT_CONFIGURE request whose options were applied.
+pub static CONFIGURE_OK: u8 = 0;
+/// The answer to an EXT_CONFIGURE request with invalid options; the
+/// configuration stays as it was.
+pub static CONFIGURE_REJECTED: u8 = 1;
+
+/// A request of the host.
+pub enum Request {
+    /// Asks for the move on the board.
+    MoveRequest(Board),
+    /// Carries options for the searcher, see EXT_CONFIGURE.
+    ConfigureRequest(String)
+}
+
+/// Violations of the pipe protocol by the host.
+pub enum ProtocolError {
+    /// The input ended in the middle of a request, after the given
+    /// number of bytes out of the expected number.
+    TruncatedRequest(uint, uint),
+    /// The request carried a board which does not pass validation.
+    InvalidBoard(BoardError),
+    /// The extension byte announces a protocol extension this AI does
+    /// not know.
+    UnsupportedExtension(u8),
+    /// The options of an EXT_CONFIGURE request are not valid UTF-8.
+    InvalidOptions
+}
+
+impl fmt::Show for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TruncatedRequest(len, expected) =>
+                write!(f.buf, "input ended after {} of {} request bytes",
+                       len, expected),
+            InvalidBoard(ref e) => write!(f.buf, "invalid board: {}", e),
+            UnsupportedExtension(ext) =>
+                write!(f.buf, "unsupported extension byte {}", ext),
+            InvalidOptions => write!(f.buf, "options are not valid UTF-8")
+        }
+    }
+}
+
+/// How a protocol session ended without an error.
+#[deriving(Eq, Show)]
+pub enum Outcome {
+    /// The host closed the input between two requests.
+    InputClosed,
+    /// The searcher found no valid move on the last board.
+    Resigned
+}
+
+/// Fill *buf* from *src*. Returns the number of bytes read, which is
+/// less than the size of the buffer only if the input ended.
+fn read_fully<FileT: Reader>(src: &mut FileT, buf: &mut [u8]) -> io::IoResult<uint>
+{
+    let mut len = 0;
+    while len < buf.len() {
+        match src.read(buf.mut_slice_from(len)) {
+            Ok(n) => len += n,
+            Err(ref e) if e.kind == io::EndOfFile => break,
+            Err(e) => return Err(e)
+        }
+    }
+    Ok(len)
+}
+
+/// Read one request (16 board bytes and the extension byte, possibly
+/// followed by a sized board) and return the validated board, or None
+/// if the input ended before the request.
+pub fn read_request<FileT: Reader>(src: &mut FileT) -> Result<Option<Board>, AiError>
+{
+    match try!(read_any(src, false)) {
+        Some(MoveRequest(board)) => Ok(Some(board)),
+        Some(ConfigureRequest(_)) => unreachable!(),
+        None => Ok(None)
+    }
+}
+
+/// Read one request like read_request, but accept EXT_CONFIGURE
+/// requests as well.
+pub fn read_message<FileT: Reader>(src: &mut FileT) -> Result<Option<Request>, AiError>
+{
+    read_any(src, true)
+}
+
+fn read_any<FileT: Reader>(src: &mut FileT,
+                           configure: bool) -> Result<Option<Request>, AiError>
+{
+    let mut raw = [0u8, ..REQUEST_SIZE];
+    let len = try!(read_fully(src, raw.as_mut_slice()).map_err(|e| IoFailure(e)));
+    if len == 0 {
+        return Ok(None);
+    } else if len < REQUEST_SIZE {
+        return Err(ProtocolFailure(TruncatedRequest(len, REQUEST_SIZE)));
+    }
+
+    let extension = raw[16];
+    let board = if extension == 0 {
+        Board::from_raw(&Vec::from_slice(raw.slice_to(16)))
+    } else if extension == EXT_SIZED_BOARD {
+        try!(read_sized_board(src))
+    } else if extension == EXT_CONFIGURE && configure {
+        return Ok(Some(ConfigureRequest(try!(read_options(src)))));
+    } else {
+        return Err(ProtocolFailure(UnsupportedExtension(extension)));
+    };
+    match board {
+        Ok(board) => Ok(Some(MoveRequest(board))),
+        Err(e) => Err(ProtocolFailure(InvalidBoard(e)))
+    }
+}
+
+/// Read the part of a request after the EXT_CONFIGURE extension byte.
+fn read_options<FileT: Reader>(src: &mut FileT) -> Result<String, AiError>
+{
+    let mut size = [0u8, ..2];
+    let len = try!(read_fully(src, size.as_mut_slice()).map_err(|e| IoFailure(e)));
+    if len < 2 {
+        return Err(ProtocolFailure(TruncatedRequest(REQUEST_SIZE + len,
+                                                    REQUEST_SIZE + 2)));
+    }
+    let mut options = Vec::from_elem(((size[0] as uint) << 8) | (size[1] as uint), 0u8);
+    let len = try!(read_fully(src, options.as_mut_slice()).map_err(|e| IoFailure(e)));
+    if len < options.len() {
+        return Err(ProtocolFailure(TruncatedRequest(
+            REQUEST_SIZE + 2 + len, REQUEST_SIZE + 2 + options.len())));
+    }
+    match str::from_utf8(options.as_slice()) {
+        Some(options) => Ok(String::from_str(options)),
+        None => Err(ProtocolFailure(InvalidOptions))
+    }
+}
+
+/// Read the part of a request after the EXT_SIZED_BOARD extension
+/// byte. The size is checked before the cells are read, so that a
+/// bogus size does not make us wait for bytes which never come.
+fn read_sized_board<FileT: Reader>(src: &mut FileT) -> Result<Result<Board, BoardError>, AiError>
+{
+    let mut size = [0u8, ..2];
+    let len = try!(read_fully(src, size.as_mut_slice()).map_err(|e| IoFailure(e)));
+    if len < 2 {
+        return Err(ProtocolFailure(TruncatedRequest(REQUEST_SIZE + len,
+                                                    REQUEST_SIZE + 2)));
+    }
+    let (width, height) = (size[0] as uint, size[1] as uint);
+    match check_size(width, height) {
+        Ok(()) => (),
+        Err(e) => return Ok(Err(e))
+    }
+
+    let mut cells = Vec::from_elem(width * height, 0u8);
+    let len = try!(read_fully(src, cells.as_mut_slice()).map_err(|e| IoFailure(e)));
+    if len < cells.len() {
+        return Err(ProtocolFailure(TruncatedRequest(
+            REQUEST_SIZE + 2 + len, REQUEST_SIZE + 2 + cells.len())));
+    }
+    Ok(Board::from_raw_sized(width, height, &cells))
+}
+
+/// Write the request for *board*, the host side of read_request.
+/// Boards other than 4x4 are sent with the EXT_SIZED_BOARD extension.
+pub fn write_request<W: Writer>(board: &Board, out: &mut W) -> io::IoResult<()> {
+    let mut raw = Vec::with_capacity(REQUEST_SIZE + 2 + board.cells());
+    let sized = board.width() != 4 || board.height() != 4;
+    if sized {
+        raw.push_all([0u8, ..16]);
+        raw.push(EXT_SIZED_BOARD);
+        raw.push(board.width() as u8);
+        raw.push(board.height() as u8);
+    }
+    for y in range(0u, board.height()) {
+        for x in range(0u, board.width()) {
+            raw.push(board.get(x, y));
+        }
+    }
+    if !sized {
+        raw.push(0);
+    }
+    try!(out.write(raw.as_slice()));
+    out.flush()
+}
+
+/// Answer requests from *input* on *output* until the input ends or
+/// the searcher finds no move anymore. Each request is answered as by
+/// answer.
+pub fn serve<R: Reader, W: Writer>(ctx: &mut EvalContext,
+                                   policy: &DepthPolicy,
+                                   input: &mut R,
+                                   output: &mut W,
+                                   on_decision: |&Decision|) -> Result<Outcome, AiError>
+{
+    loop {
+        let board = match try!(read_request(input)) {
+            Some(board) => board,
+            None => return Ok(InputClosed)
+        };
+        if !try!(answer(ctx, policy, &board, output, |d| on_decision(d))) {
+            return Ok(Resigned);
+        }
+    }
+}
+
+/// Search *board* and write the move to *output*. The search depth is
+/// chosen by *policy*, and the blockers of the rules of *ctx* are
+/// placed on the board. The decision of the searcher is passed to
+/// *on_decision* before the response is written. Returns false if
+/// the searcher found no move, in which case nothing is written.
+pub fn answer<W: Writer>(ctx: &mut EvalContext,
+                         policy: &DepthPolicy,
+                         board: &Board,
+                         output: &mut W,
+                         on_decision: |&Decision|) -> Result<bool, AiError>
+{
+    let mut board = board.clone();
+    // the protocol has no way to transfer blockers, so they are
+    // taken from the rules the AI was started with
+    for cell in ctx.rules.blockers.iter() {
+        let (x, y) = *cell;
+        if x < board.width() && y < board.height() {
+            board.set_blocker(x, y);
+        }
+    }
+
+    debug!("received board:\n{}", board);
+
+    ctx.max_depth = policy.depth_for(&board);
+
+    let decision = try!(ctx.eval(&board).map_err(|e| SearchFailure(e)));
+    on_decision(&decision);
+    match decision.best {
+        Move(_, move) => {
+            try!(output.write_u8(move as u8).map_err(|e| IoFailure(e)));
+            try!(output.flush().map_err(|e| IoFailure(e)));
+            Ok(true)
+        }
+        NoMove => Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use board::{MAX_EXPONENT, TileOutOfRange, UnsupportedSize, max_tile};
+    use error::{ProtocolFailure, IoFailure};
+    use search::{DepthPolicy, EvalContext};
+    use board::Board;
+    use super::{read_request, read_message, write_request, serve, InputClosed, Resigned};
+    use super::{MoveRequest, ConfigureRequest, EXT_SIZED_BOARD, EXT_CONFIGURE};
+    use super::{TruncatedRequest, InvalidBoard, UnsupportedExtension, InvalidOptions};
+
+    fn request(board: &[u8], extension: u8) -> Vec<u8> {
+        let mut result = Vec::from_slice(board);
+        result.push(extension);
+        result
+    }
+
+    fn sized_request(width: u8, height: u8, cells: &[u8]) -> Vec<u8> {
+        let mut result = request(Vec::from_elem(16, 0u8).as_slice(), EXT_SIZED_BOARD);
+        result.push(width);
+        result.push(height);
+        result.push_all(cells);
+        result
+    }
+
+    #[test]
+    fn test_read_request() {
+        let raw = Vec::from_fn(16, |i| (i % 3) as u8);
+        let mut src = io::MemReader::new(request(raw.as_slice(), 0));
+        let board = read_request(&mut src).ok().unwrap().unwrap();
+        assert_eq!(board.get_row(0).as_slice(), [0u8, 1, 2, 0].as_slice());
+    }
+
+    #[test]
+    fn test_read_request_rejects_large_tiles() {
+        let mut raw = Vec::from_elem(16, 1u8);
+        *raw.get_mut(3) = 200;
+        let mut src = io::MemReader::new(request(raw.as_slice(), 0));
+        match read_request(&mut src) {
+            Err(ProtocolFailure(InvalidBoard(TileOutOfRange(3, 200)))) => (),
+            other => fail!("unexpected result: {}", other.err())
+        }
+
+        *raw.get_mut(3) = max_tile(4, 4);
+        let mut src = io::MemReader::new(request(raw.as_slice(), 0));
+        assert!(read_request(&mut src).is_ok());
+    }
+
+    #[test]
+    fn test_read_request_rejects_oversized_exponents() {
+        // 65 would fit the chain argument of max_tile on 8x8, but not
+        // into a u64, let alone a Score
+        let mut cells = Vec::from_elem(64, 0u8);
+        *cells.get_mut(7) = MAX_EXPONENT + 1;
+        let mut src = io::MemReader::new(sized_request(8, 8, cells.as_slice()));
+        match read_request(&mut src) {
+            Err(ProtocolFailure(InvalidBoard(TileOutOfRange(7, tile))))
+                if tile == MAX_EXPONENT + 1 => (),
+            other => fail!("unexpected result: {}", other.err())
+        }
+
+        *cells.get_mut(7) = MAX_EXPONENT;
+        let mut src = io::MemReader::new(sized_request(8, 8, cells.as_slice()));
+        assert!(read_request(&mut src).is_ok());
+    }
+
+    #[test]
+    fn test_read_request_rejects_extensions() {
+        let raw = Vec::from_elem(16, 0u8);
+        let mut src = io::MemReader::new(request(raw.as_slice(), 2));
+        match read_request(&mut src) {
+            Err(ProtocolFailure(UnsupportedExtension(2))) => (),
+            other => fail!("unexpected result: {}", other.err())
+        }
+    }
+
+    #[test]
+    fn test_read_request_end_of_input() {
+        let mut src = io::MemReader::new(Vec::new());
+        match read_request(&mut src) {
+            Ok(None) => (),
+            other => fail!("unexpected result: {}", other.err())
+        }
+        for len in range(1u, 17) {
+            let mut src = io::MemReader::new(Vec::from_elem(len, 0u8));
+            match read_request(&mut src) {
+                Err(ProtocolFailure(TruncatedRequest(n, 17))) if n == len => (),
+                other => fail!("unexpected result for {} bytes: {}",
+                               len, other.err())
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_request_sized_board() {
+        let cells = Vec::from_fn(15, |i| (i % 4) as u8);
+        let mut src = io::MemReader::new(sized_request(5, 3, cells.as_slice()));
+        let board = read_request(&mut src).ok().unwrap().unwrap();
+        assert_eq!(board.width(), 5);
+        assert_eq!(board.height(), 3);
+        assert_eq!(board.get_row(1).as_slice(), [1u8, 2, 3, 0, 1].as_slice());
+    }
+
+    #[test]
+    fn test_read_request_sized_board_errors() {
+        let mut src = io::MemReader::new(sized_request(9, 3, &[]));
+        match read_request(&mut src) {
+            Err(ProtocolFailure(InvalidBoard(UnsupportedSize(9, 3)))) => (),
+            other => fail!("unexpected result: {}", other.err())
+        }
+
+        let mut src = io::MemReader::new(sized_request(3, 3, &[0u8, 0, 0]));
+        match read_request(&mut src) {
+            Err(ProtocolFailure(TruncatedRequest(22, 28))) => (),
+            other => fail!("unexpected result: {}", other.err())
+        }
+
+        let mut stream = sized_request(3, 3, &[]);
+        stream.pop();
+        let mut src = io::MemReader::new(stream);
+        match read_request(&mut src) {
+            Err(ProtocolFailure(TruncatedRequest(18, 19))) => (),
+            other => fail!("unexpected result: {}", other.err())
+        }
+    }
+
+    fn configure_request(options: &[u8]) -> Vec<u8> {
+        let mut result = request(Vec::from_elem(16, 0u8).as_slice(), EXT_CONFIGURE);
+        result.push((options.len() >> 8) as u8);
+        result.push(options.len() as u8);
+        result.push_all(options);
+        result
+    }
+
+    #[test]
+    fn test_read_message() {
+        let mut stream = configure_request("--depth 3".as_bytes());
+        stream.push_all(request(Vec::from_elem(16, 1u8).as_slice(), 0).as_slice());
+        let mut src = io::MemReader::new(stream);
+        match read_message(&mut src) {
+            Ok(Some(ConfigureRequest(ref options))) if options.as_slice() == "--depth 3" => (),
+            _ => fail!("expected a configure request")
+        }
+        match read_message(&mut src) {
+            Ok(Some(MoveRequest(board))) => assert_eq!(board.get(3, 3), 1),
+            _ => fail!("expected a move request")
+        }
+        match read_message(&mut src) {
+            Ok(None) => (),
+            _ => fail!("expected the end of the input")
+        }
+
+        let long = Vec::from_elem(300, 'x' as u8);
+        let mut src = io::MemReader::new(configure_request(long.as_slice()));
+        match read_message(&mut src) {
+            Ok(Some(ConfigureRequest(ref options))) if options.len() == 300 => (),
+            _ => fail!("expected a configure request")
+        }
+
+        let mut src = io::MemReader::new(configure_request(&[0xffu8, 0xfe]));
+        match read_message(&mut src) {
+            Err(ProtocolFailure(InvalidOptions)) => (),
+            other => fail!("unexpected result: {}", other.err())
+        }
+
+        let mut stream = configure_request("--depth 3".as_bytes());
+        stream.pop();
+        let mut src = io::MemReader::new(stream);
+        match read_message(&mut src) {
+            Err(ProtocolFailure(TruncatedRequest(27, 28))) => (),
+            other => fail!("unexpected result: {}", other.err())
+        }
+
+        // the AI on stdin/stdout does not take options
+        let mut src = io::MemReader::new(configure_request("--depth 3".as_bytes()));
+        match read_request(&mut src) {
+            Err(ProtocolFailure(UnsupportedExtension(2))) => (),
+            other => fail!("unexpected result: {}", other.err())
+        }
+    }
+
+    #[test]
+    fn test_write_request() {
+        let raw = Vec::from_fn(16, |i| (i % 5) as u8);
+        let board = Board::from_raw(&raw).ok().unwrap();
+        let mut out = io::MemWriter::new();
+        write_request(&board, &mut out).unwrap();
+        assert_eq!(out.unwrap(), request(raw.as_slice(), 0));
+
+        let cells = Vec::from_fn(15, |i| (i % 4) as u8);
+        let board = Board::from_raw_sized(5, 3, &cells).ok().unwrap();
+        let mut out = io::MemWriter::new();
+        write_request(&board, &mut out).unwrap();
+        let stream = out.unwrap();
+        assert_eq!(stream, sized_request(5, 3, cells.as_slice()));
+        let mut src = io::MemReader::new(stream);
+        assert!(read_request(&mut src).ok().unwrap().unwrap() == board);
+    }
+
+    #[test]
+    fn test_serve_answers_each_request() {
+        let mut ctx = EvalContext::new(1, 1.0, 0.6, 2);
+        let policy = DepthPolicy::new(1, 1, 1);
+        let mut stream = request(&[1u8, 1, 0, 0,
+                                   0, 0, 0, 0,
+                                   0, 0, 0, 0,
+                                   0, 0, 0, 0], 0);
+        stream.push_all(request(&[0u8, 0, 0, 1,
+                                  0, 0, 0, 1,
+                                  0, 0, 0, 0,
+                                  0, 0, 0, 0], 0).as_slice());
+        stream.push_all(sized_request(5, 5, &[0u8, 0, 0, 0, 1,
+                                              0, 0, 0, 0, 0,
+                                              0, 0, 0, 0, 1,
+                                              0, 0, 0, 0, 0,
+                                              0, 0, 0, 0, 0]).as_slice());
+        let mut input = io::MemReader::new(stream);
+        let mut output = io::MemWriter::new();
+        match serve(&mut ctx, &policy, &mut input, &mut output, |_| ()) {
+            Ok(InputClosed) => (),
+            other => fail!("unexpected result: {}", other)
+        }
+        let responses = output.unwrap();
+        assert_eq!(responses.len(), 3);
+        assert!(responses.iter().all(|dir| *dir < 4));
+    }
+
+    #[test]
+    fn test_serve_resigns() {
+        let mut ctx = EvalContext::new(1, 1.0, 0.6, 2);
+        let policy = DepthPolicy::new(1, 1, 1);
+        let stream = request(&[1u8, 2, 1, 2,
+                               2, 1, 2, 1,
+                               1, 2, 1, 2,
+                               2, 1, 2, 1], 0);
+        let mut input = io::MemReader::new(stream);
+        let mut output = io::MemWriter::new();
+        match serve(&mut ctx, &policy, &mut input, &mut output, |_| ()) {
+            Ok(Resigned) => (),
+            other => fail!("unexpected result: {}", other)
+        }
+        assert_eq!(output.unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_serve_reports_write_errors() {
+        let mut ctx = EvalContext::new(1, 1.0, 0.6, 2);
+        let policy = DepthPolicy::new(1, 1, 1);
+        let stream = request(&[1u8, 1, 0, 0,
+                               0, 0, 0, 0,
+                               0, 0, 0, 0,
+                               0, 0, 0, 0], 0);
+        let mut input = io::MemReader::new(stream);
+        let mut buf = [0u8, ..0];
+        let mut output = io::BufWriter::new(buf.as_mut_slice());
+        match serve(&mut ctx, &policy, &mut input, &mut output, |_| ()) {
+            Err(IoFailure(_)) => (),
+            other => fail!("unexpected result: {}", other)
+        }
+    }
+}
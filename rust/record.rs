@@ -0,0 +1,383 @@
+use std::fmt;
+use std::io;
+use std::io::process::{ProcessExit, ExitStatus, ExitSignal};
+use serialize::json::Json;
+
+use board::{Board, Direction, Up, Down, Left, Right};
+use config::Config;
+use error::{AiError, IoFailure, ConfigFailure};
+use game::Game;
+use json;
+
+static ALL_DIRECTIONS: [Direction, ..4] = [Up, Down, Left, Right];
+
+/// Why a hosted game ended.
+#[deriving(Clone)]
+pub enum GameEnd {
+    /// No move changes the board anymore.
+    NoMoveLeft,
+    /// The target tile of the rules was built.
+    TargetReached,
+    /// The AI exited, or closed its output, before the game was over.
+    AiExited(ProcessExit),
+    /// The AI did not answer within the time limit and was killed.
+    TimedOut,
+    /// The AI answered with a byte which is no direction code.
+    InvalidResponse(u8),
+    /// The AI picked a move which does not change the board.
+    InvalidMove(Direction),
+    /// Talking to the AI failed.
+    PipeFailure(String),
+    /// The searcher, playing in-process, gave up with an error.
+    SearchFailed(String),
+    /// The player left the game before it was over.
+    Unfinished
+}
+
+impl GameEnd {
+    /// A short name for the end, as used in records and summaries.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            NoMoveLeft => "game-over",
+            TargetReached => "won",
+            AiExited(_) => "exited",
+            TimedOut => "timeout",
+            InvalidResponse(_) => "invalid-response",
+            InvalidMove(_) => "invalid-move",
+            PipeFailure(_) => "pipe-failure",
+            SearchFailed(_) => "search-failure",
+            Unfinished => "unfinished"
+        }
+    }
+
+    /// Whether the game ended by the rules rather than by a fault of
+    /// the AI.
+    pub fn is_regular(&self) -> bool {
+        match *self {
+            NoMoveLeft | TargetReached => true,
+            _ => false
+        }
+    }
+}
+
+impl fmt::Show for GameEnd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NoMoveLeft => write!(f.buf, "no move left"),
+            TargetReached => write!(f.buf, "target tile reached"),
+            AiExited(status) => write!(f.buf, "AI exited ({})", status),
+            TimedOut => write!(f.buf, "AI timed out"),
+            InvalidResponse(code) =>
+                write!(f.buf, "invalid response {} from the AI", code),
+            InvalidMove(dir) =>
+                write!(f.buf, "AI picked {}, which does not change the board", dir),
+            PipeFailure(ref msg) => write!(f.buf, "pipe failure: {}", msg),
+            SearchFailed(ref msg) => write!(f.buf, "search failure: {}", msg),
+            Unfinished => write!(f.buf, "left unfinished")
+        }
+    }
+}
+
+/// A game played by a hosted AI. The spawns follow from the seed and
+/// the rules, so the game is repeated exactly by replaying the moves
+/// under the rules it was played with.
+#[deriving(Clone)]
+pub struct GameRecord {
+    /// The command line of the AI.
+    pub ai: String,
+    pub seed: u32,
+    pub moves: Vec<Direction>,
+    /// How long the AI took for each move, in nanoseconds.
+    pub move_times_ns: Vec<u64>,
+    /// The board at the end of the game.
+    pub board: Board,
+    pub score: u64,
+    pub end: GameEnd
+}
+
+impl GameRecord {
+    /// Start the record of *game*, which has not been played yet.
+    pub fn new(ai: &str, seed: u32, game: &Game) -> GameRecord {
+        GameRecord { ai: String::from_str(ai),
+                     seed: seed,
+                     moves: Vec::new(),
+                     move_times_ns: Vec::new(),
+                     board: game.board().clone(),
+                     score: 0,
+                     end: NoMoveLeft }
+    }
+
+    /// Add a move played on the game, which took the AI *time_ns*.
+    pub fn push(&mut self, dir: Direction, time_ns: u64, game: &Game) {
+        self.moves.push(dir);
+        self.move_times_ns.push(time_ns);
+        self.board = game.board().clone();
+        self.score = game.score();
+    }
+
+    pub fn max_tile(&self) -> u8 {
+        self.board.highest_tile()
+    }
+
+    /// The mean time the AI took per move, in milliseconds.
+    pub fn mean_move_ms(&self) -> f64 {
+        if self.move_times_ns.len() == 0 {
+            return 0.0;
+        }
+        let total = self.move_times_ns.iter().fold(0u64, |a, b| a + *b);
+        total as f64 / self.move_times_ns.len() as f64 * 1e-6
+    }
+
+    /// Play the moves of the record on a new game under the rules of
+    /// *config*. Fails if a move is invalid, which means the game was
+    /// played under other rules.
+    pub fn replay(&self, config: &Config) -> Result<Game, AiError> {
+        let mut game = try!(config.new_game(self.board.width(), self.board.height(),
+                                            self.seed));
+        for (i, dir) in self.moves.iter().enumerate() {
+            if game.play(*dir).is_none() {
+                return Err(ConfigFailure(format!(
+                    "move {} of the record is invalid, was it played under \
+                     other rules?", i + 1)));
+            }
+        }
+        Ok(game)
+    }
+
+    /// Write the record as one line of JSON. Moves are given as the
+    /// direction codes of the AI protocol.
+    pub fn write_json<W: Writer>(&self, out: &mut W) -> io::IoResult<()> {
+        let moves = self.moves.iter().map(|dir| format!("{}", *dir as u8))
+            .collect::<Vec<String>>();
+        let times = self.move_times_ns.iter()
+            .map(|ns| format!("{:.3f}", *ns as f64 * 1e-6))
+            .collect::<Vec<String>>();
+        let mut board = Vec::new();
+        for y in range(0u, self.board.height()) {
+            for cell in self.board.get_row(y).iter() {
+                board.push(format!("{}", *cell));
+            }
+        }
+        let detail = match self.end {
+            AiExited(status) => format!(",\"detail\":{}",
+                                        json::quote(format!("{}", status).as_slice())),
+            InvalidResponse(code) => format!(",\"detail\":{}", code),
+            InvalidMove(dir) => format!(",\"detail\":{}", dir as u8),
+            PipeFailure(ref msg) | SearchFailed(ref msg) =>
+                format!(",\"detail\":{}", json::quote(msg.as_slice())),
+            _ => String::new()
+        };
+        writeln!(out, "\\{\"ai\":{},\"seed\":{},\"width\":{},\"height\":{},\
+                       \"score\":{},\"max_tile\":{},\"end\":{}{},\
+                       \"moves\":[{}],\"move_ms\":[{}],\"board\":[{}]\\}",
+                 json::quote(self.ai.as_slice()), self.seed,
+                 self.board.width(), self.board.height(),
+                 self.score, self.max_tile(), json::quote(self.end.name()), detail,
+                 moves.connect(","), times.connect(","), board.connect(","))
+    }
+
+    /// Read a record from a line written by write_json.
+    pub fn from_json(line: &str) -> Result<GameRecord, AiError> {
+        let record = try!(::serialize::json::from_str(line).map_err(
+            |e| invalid_record(format!("{}", e))));
+        let width = try!(number_field(&record, "width")) as uint;
+        let height = try!(number_field(&record, "height")) as uint;
+        let cells = try!(numbers_field(&record, "board")).iter()
+            .map(|cell| *cell as u8)
+            .collect::<Vec<u8>>();
+        let board = try!(Board::from_raw_sized(width, height, &cells).map_err(
+            |e| invalid_record(format!("{}", e))));
+        let mut moves = Vec::new();
+        for code in try!(numbers_field(&record, "moves")).iter() {
+            moves.push(try!(direction(*code)));
+        }
+        let move_times_ns = try!(numbers_field(&record, "move_ms")).iter()
+            .map(|ms| (*ms * 1e6) as u64)
+            .collect::<Vec<u64>>();
+        Ok(GameRecord { ai: String::from_str(try!(string_field(&record, "ai"))),
+                        seed: try!(number_field(&record, "seed")) as u32,
+                        moves: moves,
+                        move_times_ns: move_times_ns,
+                        board: board,
+                        score: try!(number_field(&record, "score")) as u64,
+                        end: try!(read_end(&record)) })
+    }
+}
+
+/// Open the record file at *path* for appending, if there is one.
+pub fn open_record_file(path: Option<String>) -> Result<Option<io::File>, AiError> {
+    match path {
+        Some(path) => io::File::open_mode(&Path::new(path.as_slice()), io::Append, io::Write)
+            .map(|file| Some(file)).map_err(|e| IoFailure(e)),
+        None => Ok(None)
+    }
+}
+
+fn invalid_record(msg: String) -> AiError {
+    ConfigFailure(format!("invalid game record: {}", msg))
+}
+
+fn field<'a>(record: &'a Json, key: &str) -> Result<&'a Json, AiError> {
+    match record.find(&String::from_str(key)) {
+        Some(value) => Ok(value),
+        None => Err(invalid_record(format!("no {}", key)))
+    }
+}
+
+fn number_field(record: &Json, key: &str) -> Result<f64, AiError> {
+    match try!(field(record, key)).as_number() {
+        Some(number) => Ok(number),
+        None => Err(invalid_record(format!("{} is not a number", key)))
+    }
+}
+
+fn numbers_field(record: &Json, key: &str) -> Result<Vec<f64>, AiError> {
+    let list = match try!(field(record, key)).as_list() {
+        Some(list) => list,
+        None => return Err(invalid_record(format!("{} is not a list", key)))
+    };
+    let mut numbers = Vec::with_capacity(list.len());
+    for item in list.iter() {
+        match item.as_number() {
+            Some(number) => numbers.push(number),
+            None => return Err(invalid_record(format!(
+                "{} holds something else than numbers", key)))
+        }
+    }
+    Ok(numbers)
+}
+
+fn string_field<'a>(record: &'a Json, key: &str) -> Result<&'a str, AiError> {
+    match try!(field(record, key)).as_string() {
+        Some(string) => Ok(string),
+        None => Err(invalid_record(format!("{} is not a string", key)))
+    }
+}
+
+fn direction(code: f64) -> Result<Direction, AiError> {
+    if code >= 0.0 && (code as uint) < ALL_DIRECTIONS.len() {
+        Ok(ALL_DIRECTIONS[code as uint])
+    } else {
+        Err(invalid_record(format!("{} is no direction code", code)))
+    }
+}
+
+/// Parse an exit status as ProcessExit shows it.
+fn parse_exit(s: &str) -> Option<ProcessExit> {
+    if s.starts_with("exit code: ") {
+        from_str::<int>(s.slice_from(11)).map(|code| ExitStatus(code))
+    } else if s.starts_with("signal: ") {
+        from_str::<int>(s.slice_from(8)).map(|signal| ExitSignal(signal))
+    } else {
+        None
+    }
+}
+
+fn read_end(record: &Json) -> Result<GameEnd, AiError> {
+    let name = try!(string_field(record, "end"));
+    let end = match name {
+        "game-over" => NoMoveLeft,
+        "won" => TargetReached,
+        "timeout" => TimedOut,
+        "unfinished" => Unfinished,
+        "exited" => match parse_exit(try!(string_field(record, "detail"))) {
+            Some(status) => AiExited(status),
+            None => return Err(invalid_record(format!("invalid exit status")))
+        },
+        "invalid-response" => InvalidResponse(try!(number_field(record, "detail")) as u8),
+        "invalid-move" => InvalidMove(try!(direction(try!(number_field(record, "detail"))))),
+        "pipe-failure" => PipeFailure(String::from_str(try!(string_field(record, "detail")))),
+        "search-failure" =>
+            SearchFailed(String::from_str(try!(string_field(record, "detail")))),
+        _ => return Err(invalid_record(format!("unknown end {}", name)))
+    };
+    Ok(end)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::str;
+    use board::{Up, Down, Left, Right};
+    use config::Config;
+    use super::{GameRecord, InvalidMove, AiExited};
+    use std::io::process::ExitSignal;
+
+    #[test]
+    fn test_replay() {
+        let config = Config::default();
+        let mut game = config.new_game(4, 4, 9).ok().unwrap();
+        let mut record = GameRecord::new("test-ai", 9, &game);
+        let mut i = 0u;
+        let mut invalid = None;
+        while invalid.is_none() {
+            let dir = [Up, Left, Down, Right][i % 4];
+            if game.play(dir).is_some() {
+                record.push(dir, 1000000, &game);
+            } else if record.moves.len() >= 20 {
+                invalid = Some(dir);
+            } else {
+                i += 1;
+            }
+        }
+        let replayed = record.replay(&config).ok().unwrap();
+        assert!(replayed.board() == game.board());
+        assert_eq!(replayed.score(), record.score);
+        assert_eq!(record.mean_move_ms(), 1.0);
+
+        record.moves.push(invalid.unwrap());
+        assert!(record.replay(&config).is_err());
+    }
+
+    #[test]
+    fn test_write_json() {
+        let config = Config::default();
+        let mut game = config.new_game(3, 3, 1).ok().unwrap();
+        let mut record = GameRecord::new("ai --depth 2", 1, &game);
+        let dir = [Up, Down, Left, Right].iter().map(|d| *d)
+            .find(|d| game.play(*d).is_some()).unwrap();
+        record.push(dir, 2500000, &game);
+        record.end = InvalidMove(Up);
+        let mut out = io::MemWriter::new();
+        record.write_json(&mut out).unwrap();
+        let data = out.unwrap();
+        let line = str::from_utf8(data.as_slice()).unwrap();
+        assert!(line.starts_with(
+            "{\"ai\":\"ai --depth 2\",\"seed\":1,\"width\":3,\"height\":3,"), "{}", line);
+        assert!(line.contains("\"end\":\"invalid-move\",\"detail\":0,"), "{}", line);
+        assert!(line.contains(
+            format!("\"moves\":[{}],\"move_ms\":[2.500]", dir as u8).as_slice()), "{}", line);
+        assert!(line.ends_with("]}\n"), "{}", line);
+
+        let read = GameRecord::from_json(line).ok().unwrap();
+        assert_eq!(read.ai, record.ai);
+        assert_eq!(read.seed, 1);
+        assert!(read.board == record.board);
+        assert_eq!(read.moves.len(), 1);
+        assert!(*read.moves.get(0) as uint == dir as uint);
+        assert_eq!(read.move_times_ns, vec![2500000u64]);
+        match read.end {
+            InvalidMove(Up) => (),
+            end => fail!("unexpected end: {}", end)
+        }
+    }
+
+    #[test]
+    fn test_from_json() {
+        let line = "{\"ai\":\"sh\",\"seed\":5,\"width\":3,\"height\":3,\"score\":0,\
+                    \"max_tile\":1,\"end\":\"exited\",\"detail\":\"signal: 9\",\
+                    \"moves\":[],\"move_ms\":[],\"board\":[0,1,0,0,0,0,0,1,0]}";
+        let record = GameRecord::from_json(line).ok().unwrap();
+        match record.end {
+            AiExited(ExitSignal(9)) => (),
+            end => fail!("unexpected end: {}", end)
+        }
+        assert_eq!(record.max_tile(), 1);
+
+        assert!(GameRecord::from_json("{}").is_err());
+        assert!(GameRecord::from_json("not json").is_err());
+        assert!(GameRecord::from_json(line.replace("[0,1,0", "[0,1,40").as_slice()).is_err());
+        assert!(GameRecord::from_json(line.replace("\"moves\":[]",
+                                                   "\"moves\":[4]").as_slice()).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+extern crate getopts;
+extern crate g2048;
+
+use std::io;
+use std::os;
+
+use g2048::board::{Board, check_size};
+use g2048::config;
+use g2048::config::Config;
+use g2048::drawing::{draw_board, write_svg, write_game_apng};
+use g2048::error::{AiError, IoFailure, ConfigFailure, SearchFailure, EXIT_CONFIG};
+use g2048::hint::Hint;
+use g2048::record::GameRecord;
+
+fn options() -> Vec<getopts::OptGroup> {
+    let mut opts = config::options();
+    opts.push(getopts::optopt(
+        "", "board", "the cells of the board as comma separated exponents, \
+                      row by row, # for blockers", "CELLS"));
+    opts.push(getopts::optopt(
+        "", "size", "width and height of the board (4)", "N"));
+    opts.push(getopts::optflag(
+        "", "annotate", "show the scores of the moves below the board"));
+    opts.push(getopts::optopt(
+        "", "record", "animate a recorded game from PATH", "PATH"));
+    opts.push(getopts::optopt(
+        "", "game", "animate the game on line N of the record file (1)", "N"));
+    opts.push(getopts::optopt(
+        "", "first", "first move to animate, counted from one (1)", "N"));
+    opts.push(getopts::optopt(
+        "", "last", "last move to animate (the end of the game)", "N"));
+    opts.push(getopts::optopt(
+        "", "delay", "time each board is shown in animations, in milliseconds (200)",
+        "MS"));
+    opts
+}
+
+/// Parse the cells of --board, row by row.
+fn parse_board(cells: &str, size: uint) -> Result<Board, AiError> {
+    let invalid = || ConfigFailure(format!("invalid value for --board: {}", cells));
+    let mut tiles = Vec::new();
+    let mut blockers = Vec::new();
+    for (i, cell) in cells.split(',').enumerate() {
+        match cell.trim() {
+            "#" => {
+                tiles.push(0u8);
+                blockers.push(i);
+            }
+            tile => match from_str::<u8>(tile) {
+                Some(tile) => tiles.push(tile),
+                None => return Err(invalid())
+            }
+        }
+    }
+    let mut board = try!(Board::from_raw_sized(size, size, &tiles).map_err(
+        |e| ConfigFailure(format!("invalid value for --board: {}", e))));
+    for i in blockers.iter() {
+        board.set_blocker(*i % size, *i / size);
+    }
+    Ok(board)
+}
+
+/// Read the record on line *line* of *path*.
+fn read_record(path: &str, line: uint) -> Result<GameRecord, AiError> {
+    let data = try!(io::File::open(&Path::new(path)).and_then(|mut file| file.read_to_str())
+                    .map_err(|e| IoFailure(e)));
+    match data.as_slice().lines().nth(line - 1) {
+        Some(text) if text.trim().len() > 0 => GameRecord::from_json(text).map_err(
+            |e| ConfigFailure(format!("{}, line {}: {}", path, line, e))),
+        _ => Err(ConfigFailure(format!("{} has no game on line {}", path, line)))
+    }
+}
+
+fn render_game(matches: &getopts::Matches, config: &Config, path: &str,
+               output: &Path) -> Result<(), AiError> {
+    let line = try!(config::parse_or(matches, "game", 1u));
+    if line == 0 {
+        return Err(ConfigFailure(format!("the lines of the record file count from 1")));
+    }
+    let record = try!(read_record(path, line));
+    let first = try!(config::parse_or(matches, "first", 1u));
+    let last = try!(config::parse_or(matches, "last", record.moves.len()));
+    if first == 0 {
+        return Err(ConfigFailure(format!("the moves count from 1")));
+    }
+    let delay_ms = try!(config::parse_or(matches, "delay", 200u16));
+    let mut out = try!(io::File::create(output).map_err(|e| IoFailure(e)));
+    write_game_apng(&record, config, first - 1, last, delay_ms, &mut out)
+}
+
+fn render_board(matches: &getopts::Matches, config: &Config, svg: bool,
+                output: &Path) -> Result<(), AiError> {
+    let size = try!(config::parse_or(matches, "size", 4u));
+    try!(check_size(size, size).map_err(|e| ConfigFailure(format!("{}", e))));
+    let board = match matches.opt_str("board") {
+        Some(cells) => try!(parse_board(cells.as_slice(), size)),
+        None => return Err(ConfigFailure(format!("either --board or --record is needed")))
+    };
+    let hint = if matches.opt_present("annotate") {
+        let mut ctx = config.context();
+        Some(try!(Hint::search(&mut ctx, &config.policy, &board).map_err(
+            |e| SearchFailure(e))))
+    } else {
+        None
+    };
+    let mut out = try!(io::File::create(output).map_err(|e| IoFailure(e)));
+    let written = if svg {
+        write_svg(&board, hint.as_ref(), &mut out)
+    } else {
+        draw_board(&board, hint.as_ref()).write_png(&mut out)
+    };
+    written.map_err(|e| IoFailure(e))
+}
+
+fn run(args: &[String]) -> Result<(), AiError> {
+    let opts = options();
+    let matches = try!(getopts::getopts(args, opts.as_slice()).map_err(
+        |e| ConfigFailure(e.to_err_msg())));
+    if matches.opt_present("help") {
+        println!("{}", getopts::usage(
+            "Usage: render [OPTIONS] OUTPUT\n\n\
+             Draw the board of --board in the colours of the original game, \
+             as SVG or PNG\nby the extension of OUTPUT; --annotate asks the \
+             searcher configured by the\nsearch options for the scores of the \
+             moves. With --record, animate a recorded\ngame as an animated PNG \
+             instead, replayed under the rules of the game variant\noptions.",
+            opts.as_slice()));
+        return Ok(());
+    }
+    // parse_args takes no free arguments, but OUTPUT is one
+    let mut config = Config::default();
+    try!(config.update(&matches));
+    let output = match matches.free.as_slice() {
+        [ref output] => Path::new(output.as_slice()),
+        _ => return Err(ConfigFailure(format!("exactly one output file is needed")))
+    };
+    let svg = match output.extension_str() {
+        Some("svg") => true,
+        Some("png") => false,
+        _ => return Err(ConfigFailure(format!(
+            "the output must be a .svg or .png file: {}", output.display())))
+    };
+    match matches.opt_str("record") {
+        Some(_) if svg => Err(ConfigFailure(format!("animations can only be written as PNG"))),
+        Some(path) => render_game(&matches, &config, path.as_slice(), &output),
+        None => render_board(&matches, &config, svg, &output)
+    }
+}
+
+fn main() {
+    let args = os::args();
+    match run(args.tail()) {
+        Ok(()) => (),
+        Err(e) => {
+            let _ = writeln!(&mut io::stderr(), "render: {}", e);
+            if e.exit_code() == EXIT_CONFIG {
+                let _ = writeln!(&mut io::stderr(), "render: try --help");
+            }
+            os::set_exit_status(e.exit_code());
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use std::fmt;
+
+use board::{Board, MAX_EXPONENT, max_tile};
+
+/// How many times its value the spawned tiles must be able to grow
+/// before reaching MAX_EXPONENT, as a power of two. Building a tile
+/// 2^20 times the spawned one takes about a million spawns, more than
+/// any game gets to.
+static SPAWN_HEADROOM: u8 = 20;
+
+/// How the tiles after a move are placed.
+#[deriving(Clone, Show, Eq)]
+pub enum SpawnMode {
+    /// On a random empty cell; the tile is picked at random as well.
+    RandomSpawns,
+    /// On the empty cell and with the tile which leave the player the
+    /// worst position.
+    AdversarialSpawns
+}
+
+/// The rules of a game variant. Rules::default() describes the
+/// original game.
+#[deriving(Clone)]
+pub struct Rules {
+    /// Exponent of the tile which usually spawns.
+    pub low_tile: u8,
+    /// Exponent of the tile which spawns with high_probability
+    /// instead.
+    pub high_tile: u8,
+    pub high_probability: f64,
+    /// Number of tiles spawned after each move.
+    pub spawns_per_move: uint,
+    /// Number of tiles spawned on the empty board.
+    pub start_tiles: uint,
+    /// Cells which are blockers for the whole game, see
+    /// Board::set_blocker.
+    pub blockers: Vec<(uint, uint)>,
+    /// Exponent of the tile which wins and ends the game.
+    pub target: Option<u8>,
+    pub spawn_mode: SpawnMode
+}
+
+/// Reasons for rules to be unusable on a board.
+#[deriving(Eq, Clone)]
+pub enum RulesError {
+    /// The spawn tiles are zero, the high tile is not above the low
+    /// tile, or it is too large for the board or to be built upon.
+    InvalidSpawnTiles(u8, u8),
+    /// The probability of the high tile is outside of 0 to 1.
+    InvalidProbability(f64),
+    /// No tiles spawn after a move.
+    NoSpawns,
+    /// A blocker lies outside of the board.
+    BlockerOutsideBoard(uint, uint),
+    /// The target tile cannot be built on the board.
+    UnreachableTarget(u8)
+}
+
+impl fmt::Show for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InvalidSpawnTiles(low, high) =>
+                write!(f.buf, "invalid spawn tiles {} and {}", low, high),
+            InvalidProbability(p) =>
+                write!(f.buf, "spawn probability {} is not in [0, 1]", p),
+            NoSpawns =>
+                write!(f.buf, "at least one tile has to spawn per move"),
+            BlockerOutsideBoard(x, y) =>
+                write!(f.buf, "blocker at {},{} is outside of the board", x, y),
+            UnreachableTarget(tile) =>
+                write!(f.buf, "target tile {} cannot be built on the board", tile)
+        }
+    }
+}
+
+impl Rules {
+    pub fn default() -> Rules {
+        Rules { low_tile: 1,
+                high_tile: 2,
+                high_probability: 0.1,
+                spawns_per_move: 1,
+                start_tiles: 2,
+                blockers: Vec::new(),
+                target: None,
+                spawn_mode: RandomSpawns }
+    }
+
+    /// Check that the rules can be played on a board of the given
+    /// size.
+    pub fn check(&self, width: uint, height: uint) -> Result<(), RulesError> {
+        if self.low_tile == 0 || self.high_tile <= self.low_tile ||
+           self.high_tile > max_tile(width, height) ||
+           self.high_tile > MAX_EXPONENT - SPAWN_HEADROOM
+        {
+            return Err(InvalidSpawnTiles(self.low_tile, self.high_tile));
+        }
+        if !(self.high_probability >= 0.0 && self.high_probability <= 1.0) {
+            return Err(InvalidProbability(self.high_probability));
+        }
+        if self.spawns_per_move < 1 {
+            return Err(NoSpawns);
+        }
+        for cell in self.blockers.iter() {
+            let (x, y) = *cell;
+            if x >= width || y >= height {
+                return Err(BlockerOutsideBoard(x, y));
+            }
+        }
+        match self.target {
+            Some(tile) if tile <= self.low_tile || tile > max_tile(width, height) =>
+                return Err(UnreachableTarget(tile)),
+            _ => ()
+        }
+        Ok(())
+    }
+
+    /// The tiles which can spawn, and their number: the tiles with a
+    /// probability above zero.
+    pub fn spawn_tiles(&self) -> ([u8, ..2], uint) {
+        if self.high_probability <= 0.0 {
+            ([self.low_tile, 0], 1)
+        } else if self.high_probability >= 1.0 {
+            ([self.high_tile, 0], 1)
+        } else {
+            ([self.low_tile, self.high_tile], 2)
+        }
+    }
+
+    /// The probability of *tile* to spawn.
+    pub fn spawn_probability(&self, tile: u8) -> f64 {
+        if tile == self.high_tile {
+            self.high_probability
+        } else if tile == self.low_tile {
+            1.0 - self.high_probability
+        } else {
+            0.0
+        }
+    }
+
+    /// Whether the search weights the spawned tiles by their
+    /// probability. The original game is searched with both tiles
+    /// weighted the same, as the AI always has; variants which set
+    /// their own spawn tiles or probability are searched by the
+    /// expectation under them.
+    pub fn weights_spawns(&self) -> bool {
+        let original = Rules::default();
+        self.low_tile != original.low_tile || self.high_tile != original.high_tile ||
+            self.high_probability != original.high_probability
+    }
+
+    /// The weight of a child with *tile* spawned in the search, see
+    /// weights_spawns.
+    pub fn spawn_weight(&self, tile: u8) -> f64 {
+        if self.weights_spawns() {
+            self.spawn_probability(tile)
+        } else {
+            1.0
+        }
+    }
+
+    /// Whether *board* holds the target tile, which ends the game.
+    pub fn reached_target(&self, board: &Board) -> bool {
+        let target = match self.target {
+            Some(target) => target,
+            None => return false
+        };
+        for y in range(0u, board.height()) {
+            for x in range(0u, board.width()) {
+                if board.get(x, y) >= target {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// An empty board of the given size with the blockers of the
+    /// rules.
+    pub fn empty_board(&self, width: uint, height: uint) -> Board {
+        let mut board = Board::new(width, height);
+        for cell in self.blockers.iter() {
+            let (x, y) = *cell;
+            board.set_blocker(x, y);
+        }
+        board
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use board::Board;
+    use super::{Rules, InvalidSpawnTiles, InvalidProbability, NoSpawns};
+    use super::{BlockerOutsideBoard, UnreachableTarget};
+
+    #[test]
+    fn test_check() {
+        let rules = Rules::default();
+        assert!(rules.check(4, 4).is_ok());
+
+        let mut r = rules.clone();
+        r.high_tile = 1;
+        assert_eq!(r.check(4, 4), Err(InvalidSpawnTiles(1, 1)));
+        let mut r = rules.clone();
+        r.low_tile = 38;
+        r.high_tile = 39;
+        assert_eq!(r.check(8, 8), Err(InvalidSpawnTiles(38, 39)));
+        r.target = Some(41);
+        r.low_tile = 1;
+        r.high_tile = 2;
+        assert_eq!(r.check(8, 8), Err(UnreachableTarget(41)));
+        let mut r = rules.clone();
+        r.high_probability = 1.5;
+        assert_eq!(r.check(4, 4), Err(InvalidProbability(1.5)));
+        let mut r = rules.clone();
+        r.spawns_per_move = 0;
+        assert_eq!(r.check(4, 4), Err(NoSpawns));
+        let mut r = rules.clone();
+        r.blockers.push((3, 1));
+        assert!(r.check(4, 4).is_ok());
+        assert_eq!(r.check(3, 3), Err(BlockerOutsideBoard(3, 1)));
+        let mut r = rules.clone();
+        r.target = Some(11);
+        assert!(r.check(4, 4).is_ok());
+        assert_eq!(r.check(3, 3), Err(UnreachableTarget(11)));
+    }
+
+    #[test]
+    fn test_spawn_tiles() {
+        let mut rules = Rules::default();
+        let (tiles, count) = rules.spawn_tiles();
+        assert_eq!(tiles.slice_to(count), [1u8, 2].as_slice());
+        assert_eq!(rules.spawn_probability(1), 0.9);
+        rules.high_probability = 0.0;
+        let (tiles, count) = rules.spawn_tiles();
+        assert_eq!(tiles.slice_to(count), [1u8].as_slice());
+        rules.high_probability = 1.0;
+        let (tiles, count) = rules.spawn_tiles();
+        assert_eq!(tiles.slice_to(count), [2u8].as_slice());
+    }
+
+    #[test]
+    fn test_spawn_weight() {
+        let mut rules = Rules::default();
+        assert!(!rules.weights_spawns());
+        assert_eq!(rules.spawn_weight(1), 1.0);
+        assert_eq!(rules.spawn_weight(2), 1.0);
+        rules.high_probability = 0.25;
+        assert!(rules.weights_spawns());
+        assert_eq!(rules.spawn_weight(1), 0.75);
+        assert_eq!(rules.spawn_weight(2), 0.25);
+        let mut rules = Rules::default();
+        rules.high_tile = 3;
+        assert_eq!(rules.spawn_weight(3), 0.1);
+    }
+
+    #[test]
+    fn test_target_and_blockers() {
+        let mut rules = Rules::default();
+        rules.target = Some(3);
+        rules.blockers.push((1, 2));
+        let mut board = rules.empty_board(4, 4);
+        assert!(board.is_blocker(1, 2));
+        assert!(!rules.reached_target(&board));
+        board.set_tile(0, 0, 3);
+        assert!(rules.reached_target(&board));
+        assert!(!Rules::default().reached_target(&board));
+        assert!(board != Board::new(4, 4).place_tile(0, 0, 3));
+    }
+}
@@ -0,0 +1,1389 @@
+use std::cmp;
+use std::comm;
+use std::fmt;
+use std::num;
+use rand::{Rng, SeedableRng, XorShiftRng};
+use sync::Arc;
+use time;
+
+use board::{Board, Direction, Score, MAX_CELLS, MAX_EXPONENT};
+use board::{Up, Down, Left, Right};
+use logging::SharedSink;
+use pool::{Pool, Scope};
+use rules::{Rules, SpawnMode, RandomSpawns, AdversarialSpawns};
+use trace::{Trace, RootNode, MoveNode, SpawnNode};
+use trace::{NodeValue, Scored, GameOverLeaf, DepthLimit};
+
+pub static GAME_OVER_SCORE: Score = -2048;
+/// The value of the spawns below a move which builds the target tile
+/// of the rules and thereby wins.
+pub static WIN_SCORE: Score = 2048;
+
+/// Bound of the alpha-beta window in minimax mode, far beyond any
+/// score and any tile value up to MAX_EXPONENT.
+static SCORE_BOUND: Score = 1 << (MAX_EXPONENT as uint + 8);
+
+/// Shuffle the spawn positions *dest* on *board*. The generator is
+/// seeded from the board and the depth, so that the same positions
+/// are tried whichever thread gets to a board, and a parallel search
+/// comes to the same result as a sequential one.
+fn shuffle<T>(dest: &mut [T], board: &Board, depth: uint)
+{
+    let mut hash = (depth * 256 + board.width() * 16 + board.height()) as u64;
+    for y in range(0u, board.height()) {
+        for x in range(0u, board.width()) {
+            hash = hash * 31 + board.get(x, y) as u64;
+        }
+    }
+    let mut rng: XorShiftRng = SeedableRng::from_seed(
+        [hash as u32, (hash >> 32) as u32, 0x2048, 0x2049]);
+
+    let mut i: uint = 0;
+    while i + 1 < dest.len() {
+        let j = rng.gen_range(i+1, dest.len());
+        dest.swap(i, j);
+        i += 1;
+    }
+}
+
+/// The spawn positions tried below a move. They are kept in a fixed
+/// buffer, so that expanding a move does not allocate. All *free*
+/// cells are kept in shuffled order, of which the first *len* are
+/// tried.
+struct SpawnCells {
+    cells: [(uint, uint), ..MAX_CELLS],
+    len: uint,
+    free: uint
+}
+
+impl SpawnCells {
+    fn as_slice<'a>(&'a self) -> &'a [(uint, uint)] {
+        self.cells.slice_to(self.len)
+    }
+}
+
+/// The spread of the outcomes below a move: the variance of the
+/// values of the boards reached at the horizon, and the probability
+/// that the game ends before it. Both are taken over the spawns as the
+/// search weights them, with the best move picked on each board.
+#[deriving(Clone, Show)]
+pub struct Risk {
+    pub variance: f64,
+    pub game_over: f64
+}
+
+impl Risk {
+    /// The risk of an outcome which is certain.
+    pub fn none() -> Risk {
+        Risk { variance: 0.0, game_over: 0.0 }
+    }
+
+    pub fn stddev(&self) -> f64 {
+        self.variance.sqrt()
+    }
+}
+
+/// The values of the spawn boards below a move. Each child is
+/// weighted as Rules::spawn_weight says, so the sums are expectations
+/// once divided by the total weight.
+struct ChildScores {
+    /// The plain sum of the scores, averaged when the rules do not
+    /// weight the spawns.
+    sum: Score,
+    count: uint,
+    weight: f64,
+    total: f64,
+    worst: Score,
+    /// Sums over the children for the risk of the move.
+    squares: f64,
+    variance: f64,
+    game_over: f64,
+    worst_risk: Risk
+}
+
+impl ChildScores {
+    fn new() -> ChildScores {
+        ChildScores { sum: 0, count: 0, weight: 0.0, total: 0.0, worst: 0,
+                      squares: 0.0, variance: 0.0, game_over: 0.0,
+                      worst_risk: Risk::none() }
+    }
+
+    fn add(&mut self, score: Score, risk: Risk, weight: f64) {
+        if self.count == 0 || score < self.worst {
+            self.worst = score;
+            self.worst_risk = risk;
+        }
+        self.sum += score;
+        self.count += 1;
+        self.weight += weight;
+        self.total += weight * score as f64;
+        self.squares += weight * (score as f64) * (score as f64);
+        self.variance += weight * risk.variance;
+        self.game_over += weight * risk.game_over;
+    }
+
+    /// The expected value of the children.
+    fn mean(&self) -> f64 {
+        self.total / self.weight
+    }
+
+    /// The risk of the move above the children. The expectation of the
+    /// children is valued, so its variance is the expected variance of
+    /// the children plus the variance of their values; under
+    /// adversarial spawns, the worst child is all there is.
+    fn risk(&self, mode: SpawnMode) -> Risk {
+        if self.count == 0 || self.weight <= 0.0 {
+            return Risk::none();
+        }
+        match mode {
+            RandomSpawns => {
+                let mean = self.mean();
+                let spread = (self.squares / self.weight - mean * mean).max(0.0);
+                Risk { variance: self.variance / self.weight + spread,
+                       game_over: self.game_over / self.weight }
+            }
+            AdversarialSpawns => self.worst_risk
+        }
+    }
+}
+
+/// How the search values the spawns below a move.
+#[deriving(Clone, Show, Eq)]
+pub enum SearchMode {
+    /// Average over a sample of the spawn positions.
+    Expectimax,
+    /// Assume the worst spawn, trying all positions and pruning with
+    /// alpha-beta. This mode always searches on the calling thread.
+    Minimax
+}
+
+#[deriving(Clone)]
+pub struct EvalContext {
+    pub max_depth: uint,
+    pub min_fill: f32,
+    pub min_fill_decay_per_level: f32,
+    pub min_new_nodes: uint,
+    /// Record the search tree down to this depth in each Decision;
+    /// zero disables recording.
+    pub trace_depth: uint,
+    /// Fill in SearchStats::time_per_depth_ns. This reads the clock
+    /// twice per move expanded, so it is only worth it if the
+    /// statistics are logged.
+    pub time_depths: bool,
+    /// Worker threads for the search, see set_threads.
+    pub pool: Option<Arc<Pool>>,
+    pub split_depth: uint,
+    /// The rules the spawns below each move follow.
+    pub rules: Rules,
+    pub mode: SearchMode,
+    /// How much the choice of the move on the root board is penalised
+    /// per standard deviation of its outcomes; zero picks the highest
+    /// average.
+    pub risk_aversion: f64,
+    /// Only pick moves on the root board whose probability to end the
+    /// game within the horizon is at most this. If no move qualifies,
+    /// the one least likely to end the game is picked.
+    pub max_game_over: Option<f64>
+}
+
+#[deriving(Clone, Show)]
+pub enum MoveEvalResult {
+    Valid(Score),
+    InvalidMove
+}
+
+#[deriving(Clone, Show)]
+pub enum BestMove {
+    Move(Score, Direction),
+    NoMove
+}
+
+#[deriving(Clone)]
+pub enum IntermediateBestMove  {
+    Found(Score, Direction, Risk),
+    DepthExceeded,
+    GameOver
+}
+
+static ALL_DIRECTIONS: [Direction, ..4] = [Up, Down, Left, Right];
+
+/// Counters collected while searching, similar to the TreeStats of
+/// the C++ AI. Depths are counted from one (the moves on the board
+/// passed to eval).
+#[deriving(Clone)]
+pub struct SearchStats {
+    /// Number of valid moves expanded, per depth.
+    pub nodes_per_depth: Vec<uint>,
+    /// Number of boards with a spawned tile which were searched.
+    pub chance_nodes: uint,
+    /// Number of boards reached without any valid move.
+    pub game_over_leaves: uint,
+    /// Number of boards at which the search was cut off by max_depth.
+    pub depth_exceeded_leaves: uint,
+    /// Time spent evaluating the moves at each depth, including their
+    /// subtrees. Subtrees searched in parallel are summed up, so this
+    /// is CPU time rather than wall clock time. Zero unless
+    /// EvalContext::time_depths is set.
+    pub time_per_depth_ns: Vec<u64>
+}
+
+impl SearchStats {
+    pub fn new() -> SearchStats {
+        SearchStats { nodes_per_depth: Vec::new(),
+                      chance_nodes: 0,
+                      game_over_leaves: 0,
+                      depth_exceeded_leaves: 0,
+                      time_per_depth_ns: Vec::new() }
+    }
+
+    fn grow(&mut self, depth: uint) {
+        while self.nodes_per_depth.len() < depth {
+            self.nodes_per_depth.push(0);
+            self.time_per_depth_ns.push(0);
+        }
+    }
+
+    fn record_node(&mut self, depth: uint) {
+        self.grow(depth);
+        *self.nodes_per_depth.get_mut(depth-1) += 1;
+    }
+
+    fn record_time(&mut self, depth: uint, elapsed_ns: u64) {
+        self.grow(depth);
+        *self.time_per_depth_ns.get_mut(depth-1) += elapsed_ns;
+    }
+
+    /// Total number of valid moves expanded.
+    pub fn nodes(&self) -> uint {
+        self.nodes_per_depth.iter().fold(0, |prev, n| prev + *n)
+    }
+
+    /// The deepest level at which any move was expanded.
+    pub fn effective_depth(&self) -> uint {
+        match self.nodes_per_depth.iter().rposition(|n| *n > 0) {
+            Some(idx) => idx + 1,
+            None => 0
+        }
+    }
+
+    /// Average number of moves expanded below each move which was
+    /// not at the deepest level.
+    pub fn branching_factor(&self) -> f64 {
+        let depth = self.effective_depth();
+        if depth < 2 {
+            return 0.0;
+        }
+        let parents = self.nodes_per_depth.slice_to(depth-1).iter()
+            .fold(0, |prev, n| prev + *n);
+        let children = self.nodes_per_depth.slice(1, depth).iter()
+            .fold(0, |prev, n| prev + *n);
+        children as f64 / parents as f64
+    }
+
+    /// Add the counters of a search on a subtree.
+    pub fn merge(&mut self, other: &SearchStats) {
+        self.grow(other.nodes_per_depth.len());
+        for (i, n) in other.nodes_per_depth.iter().enumerate() {
+            *self.nodes_per_depth.get_mut(i) += *n;
+        }
+        for (i, t) in other.time_per_depth_ns.iter().enumerate() {
+            *self.time_per_depth_ns.get_mut(i) += *t;
+        }
+        self.chance_nodes += other.chance_nodes;
+        self.game_over_leaves += other.game_over_leaves;
+        self.depth_exceeded_leaves += other.depth_exceeded_leaves;
+    }
+}
+
+impl fmt::Show for SearchStats {
+    /// Format the counters as ``key=value`` pairs; per-depth values
+    /// are separated by commas, starting at depth one.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f.buf, "nodes={} chance_nodes={} game_over_leaves={} \
+                            depth_exceeded_leaves={} branching_factor={:.2f} \
+                            effective_depth={} nodes_per_depth=",
+                    self.nodes(), self.chance_nodes, self.game_over_leaves,
+                    self.depth_exceeded_leaves, self.branching_factor(),
+                    self.effective_depth()));
+        for (i, n) in self.nodes_per_depth.iter().enumerate() {
+            if i > 0 {
+                try!(write!(f.buf, ","));
+            }
+            try!(write!(f.buf, "{}", *n));
+        }
+        try!(write!(f.buf, " ms_per_depth="));
+        for (i, t) in self.time_per_depth_ns.iter().enumerate() {
+            if i > 0 {
+                try!(write!(f.buf, ","));
+            }
+            try!(write!(f.buf, "{:.3f}", *t as f64 / 1e6));
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of a search on one board.
+pub struct Decision {
+    pub board: Board,
+    pub best: BestMove,
+    /// The evaluation of each move, indexed by the Direction value.
+    pub scores: [MoveEvalResult, ..4],
+    /// The risk of each move, indexed like the scores.
+    pub risks: [Risk, ..4],
+    pub depth: uint,
+    pub stats: SearchStats,
+    pub elapsed_ns: u64,
+    /// The explored tree, if the context has a trace_depth.
+    pub trace: Option<Trace>
+}
+
+impl fmt::Show for Decision {
+    /// Format the decision as ``key=value`` pairs. The board is given
+    /// row by row, rows separated by slashes.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.best {
+            Move(score, dir) => try!(write!(f.buf, "move={} score={}", dir, score)),
+            NoMove => try!(write!(f.buf, "move=none score=-"))
+        }
+        try!(write!(f.buf, " scores="));
+        for (i, eval) in self.scores.iter().enumerate() {
+            if i > 0 {
+                try!(write!(f.buf, ","));
+            }
+            match *eval {
+                Valid(score) => try!(write!(f.buf, "{}", score)),
+                InvalidMove => try!(write!(f.buf, "-"))
+            }
+        }
+        try!(write!(f.buf, " game_over="));
+        for (i, eval) in self.scores.iter().enumerate() {
+            if i > 0 {
+                try!(write!(f.buf, ","));
+            }
+            match *eval {
+                Valid(_) => try!(write!(f.buf, "{:.3f}", self.risks[i].game_over)),
+                InvalidMove => try!(write!(f.buf, "-"))
+            }
+        }
+        try!(write!(f.buf, " stddev="));
+        for (i, eval) in self.scores.iter().enumerate() {
+            if i > 0 {
+                try!(write!(f.buf, ","));
+            }
+            match *eval {
+                Valid(_) => try!(write!(f.buf, "{:.1f}", self.risks[i].stddev())),
+                InvalidMove => try!(write!(f.buf, "-"))
+            }
+        }
+        try!(write!(f.buf, " depth={} nodes={} elapsed_ms={:.3f} board=",
+                    self.depth, self.stats.nodes(),
+                    self.elapsed_ns as f64 / 1e6));
+        for y in range(0u, self.board.height()) {
+            if y > 0 {
+                try!(write!(f.buf, "/"));
+            }
+            for x in range(0u, self.board.width()) {
+                if x > 0 {
+                    try!(write!(f.buf, ","));
+                }
+                try!(write!(f.buf, "{}", self.board.get(x, y)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The contribution of a spawn board to the value of the move above.
+fn child_score(result: &IntermediateBestMove) -> Score {
+    match *result {
+        Found(score, _, _) => score,
+        GameOver => GAME_OVER_SCORE,
+        DepthExceeded => 0
+    }
+}
+
+/// The risk a spawn board adds to the move above: a board without
+/// moves ends the game, one beyond the depth limit is taken as safe.
+fn child_risk(result: &IntermediateBestMove) -> Risk {
+    match *result {
+        Found(_, _, risk) => risk,
+        GameOver => Risk { variance: 0.0, game_over: 1.0 },
+        DepthExceeded => Risk::none()
+    }
+}
+
+/// The value of a spawn node in a recorded trace.
+fn spawn_value(result: &IntermediateBestMove) -> NodeValue {
+    match *result {
+        Found(score, _, _) => Scored(score),
+        GameOver => GameOverLeaf,
+        DepthExceeded => DepthLimit
+    }
+}
+
+/// Fold the evaluation of *move* into the best move found so far.
+/// On equal scores, the earlier move is kept.
+fn better_move(result: IntermediateBestMove,
+               eval: MoveEvalResult,
+               risk: Risk,
+               move: Direction) -> IntermediateBestMove {
+    match eval {
+        Valid(score) => match result {
+            Found(found_score, _, _) if found_score >= score
+                => result,
+            _ => Found(score, move, risk)
+        },
+        InvalidMove => result
+    }
+}
+
+/// Reasons for a search to end without a result.
+pub enum SearchError {
+    /// The search was started with a max_depth of zero, so not even
+    /// the moves on the root board were evaluated.
+    RootDepthExceeded
+}
+
+impl fmt::Show for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RootDepthExceeded =>
+                write!(f.buf, "max_depth is zero, root board not evaluated")
+        }
+    }
+}
+
+/// Picks the search depth for each board from the amount of occupied
+/// cells and the total tile value: crowded boards are searched deeper,
+/// nearly empty ones shallower.
+#[deriving(Clone)]
+pub struct DepthPolicy {
+    pub min_depth: uint,
+    pub default_depth: uint,
+    pub max_depth: uint
+}
+
+impl DepthPolicy {
+    pub fn new(min_depth: uint,
+               default_depth: uint,
+               max_depth: uint) -> DepthPolicy {
+        assert!(min_depth >= 1);
+        DepthPolicy { min_depth: min_depth,
+                      default_depth: default_depth,
+                      max_depth: max_depth }
+    }
+
+    pub fn depth_for(&self, board: &Board) -> uint {
+        let value = board.total_value_exp();
+        // the thresholds below were tuned for 4x4 boards, so scale
+        // the number of occupied cells to 16 cells
+        let nonzeros = board.total_nonzeros() * 16 / board.cells();
+
+        // high value condition: a total of more than 128 definetly requires
+        // attention, independent from the amount of free fields
+        let high_value = value >= 128;
+
+        match (high_value, nonzeros) {
+            (true, nonzeros) if nonzeros >= 10 =>
+                (self.default_depth + self.max_depth) / 2,
+            (true, nonzeros) if nonzeros >= 12 =>
+                self.max_depth,
+            (true, _) => self.default_depth,
+            (false, nonzeros) if nonzeros >= 10 =>
+                (self.default_depth + self.max_depth) / 2,
+            (false, nonzeros) if nonzeros >= 14 =>
+                self.max_depth,
+            (false, nonzeros) if nonzeros < 4 =>
+                self.min_depth,
+            (false, nonzeros) if nonzeros < 6 =>
+                (self.min_depth + self.min_depth + self.default_depth) / 3,
+            (false, _) => self.default_depth
+        }
+    }
+}
+
+impl EvalContext {
+
+    pub fn new(max_depth: uint,
+               min_fill: f32,
+               min_fill_decay_per_level: f32,
+               min_new_nodes: uint) -> EvalContext {
+        assert!(max_depth >= 1);
+        EvalContext { max_depth: max_depth,
+                      min_fill: min_fill,
+                      min_fill_decay_per_level: min_fill_decay_per_level,
+                      min_new_nodes : min_new_nodes,
+                      trace_depth: 0,
+                      time_depths: false,
+                      pool: None,
+                      split_depth: 0,
+                      rules: Rules::default(),
+                      mode: Expectimax,
+                      risk_aversion: 0.0,
+                      max_game_over: None }
+    }
+
+    /// Search on *threads* worker threads. The spawn boards below the
+    /// moves down to *split_depth* are searched as separate jobs,
+    /// deeper levels stay on the thread which got there. With a single
+    /// thread, the search runs on the calling thread. The workers log
+    /// to *sink* if given.
+    pub fn set_threads(&mut self, threads: uint, split_depth: uint,
+                       sink: Option<SharedSink>) {
+        self.pool = if threads > 1 {
+            Some(Arc::new(Pool::new(threads, sink)))
+        } else {
+            None
+        };
+        self.split_depth = split_depth;
+    }
+
+    /// Apply *dir* to *curr_board*. Returns the new board, the merge
+    /// score and the spawn positions to try on it, or None if the move
+    /// is invalid.
+    fn expand(&self, curr_board: &Board, dir: Direction,
+              depth: uint) -> Option<(Board, Score, SpawnCells)>
+    {
+        let (new_board, move_score) = curr_board.shifted_board(dir);
+        if depth == 1 {
+            debug!("move {} results in board\n{}", dir, new_board);
+        }
+        if new_board == *curr_board {
+            return None;
+        }
+
+        let mut options = SpawnCells { cells: [(0, 0), ..MAX_CELLS], len: 0, free: 0 };
+        if self.rules.reached_target(&new_board) {
+            // the game ends with the move, no tile spawns
+            return Some((new_board, move_score, options));
+        }
+        let free = new_board.empty_cells(&mut options.cells);
+        options.free = free;
+        shuffle(options.cells.mut_slice_to(free), &new_board, depth);
+
+        let fill = self.min_fill * num::pow(self.min_fill_decay_per_level,
+                                            (depth-1));
+
+        let to_fill = cmp::min(
+            cmp::max(
+                ((fill * new_board.cells() as f32).round() as uint),
+                self.min_new_nodes),
+            free);
+        options.len = to_fill;
+
+        Some((new_board, move_score, options))
+    }
+
+    /// Fold the scores of the *children* spawn boards below a move
+    /// into the value of the move: their average, weighted by the
+    /// probabilities of the spawned tiles if the rules ask for it, or
+    /// the worst of them if the rules spawn adversarially.
+    fn combine(&self, new_board: &Board, dir: Direction, depth: uint,
+               move_score: Score,
+               children: &ChildScores,
+               node: Option<uint>,
+               trace: &mut Trace) -> (MoveEvalResult, Risk)
+    {
+        let child_average = if children.count == 0 {
+            // only a move which builds the target tile has no spawns
+            WIN_SCORE
+        } else {
+            match self.rules.spawn_mode {
+                RandomSpawns if !self.rules.weights_spawns() =>
+                    ((children.sum as f32) / (children.count as f32)).round() as Score,
+                RandomSpawns => children.mean().round() as Score,
+                AdversarialSpawns => children.worst
+            }
+        };
+        let total_score = move_score + new_board.gradient_score() + child_average;
+
+        if depth == 1 {
+            debug!("move {}: merge score {}, gradient score {}, total score {}",
+                   dir, move_score, new_board.gradient_score(), total_score);
+        }
+
+        trace.set_partial(node, move_score, new_board.gradient_score(),
+                          child_average);
+        trace.set_value(node, Scored(total_score));
+        trace.leave(node);
+        (Valid(total_score), children.risk(self.rules.spawn_mode))
+    }
+
+    /// Place the spawns of child *i* of a move on *board*: *tile* on
+    /// its cell and, if the rules spawn several tiles per move, the
+    /// low tile on the cells following it in the shuffled order. A
+    /// *tile* of zero removes them again.
+    fn set_spawns(&self, board: &mut Board, options: &SpawnCells,
+                  i: uint, tile: u8) {
+        let (x, y) = options.cells[i];
+        board.set_tile(x, y, tile);
+        let spawns = cmp::min(self.rules.spawns_per_move, options.free);
+        for k in range(1u, spawns) {
+            let (x, y) = options.cells[(i + k) % options.free];
+            board.set_tile(x, y, if tile == 0 { 0 } else { self.rules.low_tile });
+        }
+    }
+
+    /// The time to measure a move from, if time_depths is set.
+    fn start_timer(&self) -> Option<u64> {
+        if self.time_depths {
+            Some(time::precise_time_ns())
+        } else {
+            None
+        }
+    }
+
+    /// Add the time since *start* to the moves at *depth*.
+    fn stop_timer(&self, stats: &mut SearchStats, depth: uint,
+                  start: Option<u64>) {
+        match start {
+            Some(start) => stats.record_time(depth, time::precise_time_ns() - start),
+            None => ()
+        }
+    }
+
+    pub fn eval_move(&self, curr_board: &Board,
+                     dir: Direction, depth: uint,
+                     stats: &mut SearchStats,
+                     trace: &mut Trace) -> (MoveEvalResult, Risk)
+    {
+        let (mut new_board, move_score, options) =
+            match self.expand(curr_board, dir, depth) {
+                Some(expanded) => expanded,
+                None => return (InvalidMove, Risk::none())
+            };
+        let start = self.start_timer();
+        stats.record_node(depth);
+        let node = trace.enter(depth, &new_board, MoveNode(dir));
+
+        let (tiles, ntiles) = self.rules.spawn_tiles();
+        let mut children = ChildScores::new();
+        for (i, option) in options.as_slice().iter().enumerate() {
+            let &(x, y) = option;
+            for tilev in tiles.slice_to(ntiles).iter() {
+                stats.chance_nodes += 1;
+                self.set_spawns(&mut new_board, &options, i, *tilev);
+                let spawn = trace.enter(depth, &new_board,
+                                        SpawnNode(x, y, *tilev));
+                let result = self.eval_moves(&new_board, depth+1, stats, trace);
+                trace.set_value(spawn, spawn_value(&result));
+                trace.leave(spawn);
+                children.add(child_score(&result), child_risk(&result),
+                             self.rules.spawn_weight(*tilev));
+                self.set_spawns(&mut new_board, &options, i, 0);
+            }
+        }
+
+        let result = self.combine(&new_board, dir, depth, move_score,
+                                  &children, node, trace);
+        self.stop_timer(stats, depth, start);
+        result
+    }
+
+    /// Like eval_move, but search each spawn board as a job on the
+    /// pool. The results are folded in spawn order, so they do not
+    /// depend on which job finishes first.
+    fn eval_move_parallel(&self, curr_board: &Board,
+                          dir: Direction, depth: uint,
+                          stats: &mut SearchStats,
+                          trace: &mut Trace,
+                          scope: &mut Scope) -> (MoveEvalResult, Risk)
+    {
+        let (new_board, move_score, options) =
+            match self.expand(curr_board, dir, depth) {
+                Some(expanded) => expanded,
+                None => return (InvalidMove, Risk::none())
+            };
+        let start = self.start_timer();
+        stats.record_node(depth);
+        let node = trace.enter(depth, &new_board, MoveNode(dir));
+
+        let (tx, rx) = comm::channel();
+        let limit = trace.limit();
+        let (tiles, ntiles) = self.rules.spawn_tiles();
+        let mut weights = Vec::new();
+        for (i, option) in options.as_slice().iter().enumerate() {
+            let &(x, y) = option;
+            for tilev in tiles.slice_to(ntiles).iter() {
+                stats.chance_nodes += 1;
+                let mut child_board = new_board.clone();
+                self.set_spawns(&mut child_board, &options, i, *tilev);
+                let tilev = *tilev;
+                let index = weights.len();
+                let ctx = self.clone();
+                let tx = tx.clone();
+                scope.spawn(proc(scope: &mut Scope) {
+                    let mut child_stats = SearchStats::new();
+                    // the spawn node becomes the root of the partial
+                    // trace, which is grafted below the move node
+                    let mut child_trace = Trace::new(limit);
+                    let spawn = child_trace.enter(depth, &child_board,
+                                                  SpawnNode(x, y, tilev));
+                    let result = ctx.eval_moves_parallel(
+                        &child_board, depth+1,
+                        &mut child_stats, &mut child_trace, scope);
+                    child_trace.set_value(spawn, spawn_value(&result));
+                    child_trace.leave(spawn);
+                    tx.send((index, result, child_stats, child_trace));
+                });
+                weights.push(self.rules.spawn_weight(tilev));
+            }
+        }
+
+        let mut done = Vec::from_fn(weights.len(), |_| None);
+        let mut pending = weights.len();
+        scope.wait_until(|| {
+            loop {
+                match rx.try_recv() {
+                    Ok((index, result, child_stats, child_trace)) => {
+                        *done.get_mut(index) = Some((result, child_stats, child_trace));
+                        pending -= 1;
+                    }
+                    Err(_) => break
+                }
+            }
+            pending == 0
+        });
+
+        let mut children = ChildScores::new();
+        for (slot, weight) in done.move_iter().zip(weights.iter()) {
+            let (result, child_stats, child_trace) = slot.unwrap();
+            stats.merge(&child_stats);
+            trace.graft(node, child_trace);
+            children.add(child_score(&result), child_risk(&result), *weight);
+        }
+
+        let result = self.combine(&new_board, dir, depth, move_score,
+                                  &children, node, trace);
+        self.stop_timer(stats, depth, start);
+        result
+    }
+
+    /// Evaluate *dir* on the pool if *depth* is above the split depth,
+    /// and on the current thread otherwise.
+    fn eval_move_split(&self, board: &Board,
+                       dir: Direction, depth: uint,
+                       stats: &mut SearchStats,
+                       trace: &mut Trace,
+                       scope: &mut Scope) -> (MoveEvalResult, Risk)
+    {
+        if depth <= self.split_depth {
+            self.eval_move_parallel(board, dir, depth, stats, trace, scope)
+        } else {
+            self.eval_move(board, dir, depth, stats, trace)
+        }
+    }
+
+    fn eval_moves_with(&self, depth: uint,
+                       stats: &mut SearchStats,
+                       trace: &mut Trace,
+                       eval_move: |Direction, &mut SearchStats, &mut Trace| -> (MoveEvalResult, Risk))
+                       -> IntermediateBestMove
+    {
+        if depth > self.max_depth {
+            stats.depth_exceeded_leaves += 1;
+            return DepthExceeded;
+        }
+
+        let mut result: IntermediateBestMove = GameOver;
+        for move in ALL_DIRECTIONS.iter() {
+            let (eval, risk) = eval_move(*move, stats, trace);
+            result = better_move(result, eval, risk, *move);
+        }
+        match result {
+            GameOver => stats.game_over_leaves += 1,
+            _ => ()
+        }
+        result
+    }
+
+    pub fn eval_moves(&self, board: &Board, depth: uint,
+                      stats: &mut SearchStats,
+                      trace: &mut Trace) -> IntermediateBestMove
+    {
+        self.eval_moves_with(depth, stats, trace, |dir, stats, trace| {
+            self.eval_move(board, dir, depth, stats, trace)
+        })
+    }
+
+    fn eval_moves_parallel(&self, board: &Board, depth: uint,
+                           stats: &mut SearchStats,
+                           trace: &mut Trace,
+                           scope: &mut Scope) -> IntermediateBestMove
+    {
+        self.eval_moves_with(depth, stats, trace, |dir, stats, trace| {
+            self.eval_move_split(board, dir, depth, stats, trace, &mut *scope)
+        })
+    }
+
+    /// Evaluate *dir* on *curr_board* in minimax mode, assuming the
+    /// worst spawns, within the window *alpha* to *beta*. Values
+    /// outside of the window are bounds: the search stops as soon as
+    /// it is clear that the value is outside. The risk is that of the
+    /// line of worst spawns, which has no variance.
+    fn minimax_move(&self, curr_board: &Board,
+                    dir: Direction, depth: uint,
+                    alpha: Score, beta: Score,
+                    stats: &mut SearchStats,
+                    trace: &mut Trace) -> (MoveEvalResult, Risk)
+    {
+        let (new_board, move_score) = curr_board.shifted_board(dir);
+        if new_board == *curr_board {
+            return (InvalidMove, Risk::none());
+        }
+        let start = self.start_timer();
+        stats.record_node(depth);
+        let node = trace.enter(depth, &new_board, MoveNode(dir));
+
+        let gradient_score = new_board.gradient_score();
+        let base = move_score + gradient_score;
+        let (worst, risk) = if self.rules.reached_target(&new_board) {
+            (WIN_SCORE, Risk::none())
+        } else {
+            self.minimax_spawns(&new_board, depth, alpha - base, beta - base,
+                                stats, trace)
+        };
+
+        trace.set_partial(node, move_score, gradient_score, worst);
+        trace.set_value(node, Scored(base + worst));
+        trace.leave(node);
+        self.stop_timer(stats, depth, start);
+        (Valid(base + worst), risk)
+    }
+
+    /// The value of the worst spawn on *board*, the board after a move
+    /// at *depth*, and the risk below it. All empty cells are tried,
+    /// until one is found at or below *alpha*, as the player will not
+    /// make the move then.
+    fn minimax_spawns(&self, board: &Board, depth: uint,
+                      alpha: Score, beta: Score,
+                      stats: &mut SearchStats,
+                      trace: &mut Trace) -> (Score, Risk)
+    {
+        let mut options = SpawnCells { cells: [(0, 0), ..MAX_CELLS], len: 0, free: 0 };
+        options.free = board.empty_cells(&mut options.cells);
+        options.len = options.free;
+        let (tiles, ntiles) = self.rules.spawn_tiles();
+
+        let mut board = board.clone();
+        let mut worst = beta;
+        let mut risk = Risk::none();
+        for i in range(0u, options.len) {
+            let (x, y) = options.cells[i];
+            for tilev in tiles.slice_to(ntiles).iter() {
+                stats.chance_nodes += 1;
+                self.set_spawns(&mut board, &options, i, *tilev);
+                let spawn = trace.enter(depth, &board, SpawnNode(x, y, *tilev));
+                let result = self.minimax_moves(&board, depth+1, alpha, worst,
+                                                stats, trace);
+                trace.set_value(spawn, spawn_value(&result));
+                trace.leave(spawn);
+                self.set_spawns(&mut board, &options, i, 0);
+                if child_score(&result) < worst {
+                    worst = child_score(&result);
+                    risk = child_risk(&result);
+                }
+                if worst <= alpha {
+                    return (worst, risk);
+                }
+            }
+        }
+        (worst, risk)
+    }
+
+    /// The best move on *board* at *depth* in minimax mode. The search
+    /// stops as soon as a move at or above *beta* is found, as the
+    /// spawn leading to *board* will not be picked then.
+    fn minimax_moves(&self, board: &Board, depth: uint,
+                     alpha: Score, beta: Score,
+                     stats: &mut SearchStats,
+                     trace: &mut Trace) -> IntermediateBestMove
+    {
+        if depth > self.max_depth {
+            stats.depth_exceeded_leaves += 1;
+            return DepthExceeded;
+        }
+
+        let mut alpha = alpha;
+        let mut result: IntermediateBestMove = GameOver;
+        for move in ALL_DIRECTIONS.iter() {
+            let (eval, risk) = self.minimax_move(board, *move, depth, alpha, beta,
+                                                 stats, trace);
+            result = better_move(result, eval, risk, *move);
+            match eval {
+                Valid(score) if score >= beta => return result,
+                Valid(score) => alpha = cmp::max(alpha, score),
+                InvalidMove => ()
+            }
+        }
+        match result {
+            GameOver => stats.game_over_leaves += 1,
+            _ => ()
+        }
+        result
+    }
+
+    /// Pick the spawn on *board*, the board after a move, which leaves
+    /// the player the lowest minimax value, searching max_depth moves
+    /// ahead. With several spawns per move, this picks one of them.
+    /// None if the board has no empty cell.
+    pub fn worst_spawn(&self, board: &Board) -> Option<(uint, uint, u8)> {
+        let mut cells = [(0u, 0u), ..MAX_CELLS];
+        let free = board.empty_cells(&mut cells);
+        let (tiles, ntiles) = self.rules.spawn_tiles();
+        let mut stats = SearchStats::new();
+        let mut trace = Trace::new(0);
+
+        let mut worst: Option<(Score, uint, uint, u8)> = None;
+        for cell in cells.slice_to(free).iter() {
+            let (x, y) = *cell;
+            for tile in tiles.slice_to(ntiles).iter() {
+                let beta = match worst {
+                    Some((score, _, _, _)) => score,
+                    None => SCORE_BOUND
+                };
+                let result = self.minimax_moves(&board.place_tile(x, y, *tile), 1,
+                                                -SCORE_BOUND, beta,
+                                                &mut stats, &mut trace);
+                let value = child_score(&result);
+                if value < beta {
+                    worst = Some((value, x, y, *tile));
+                }
+            }
+        }
+        worst.map(|(_, x, y, tile)| (x, y, tile))
+    }
+
+    /// Pick the move on the root board from the *scores* and *risks*
+    /// of all moves: the highest score less risk_aversion times the
+    /// standard deviation, among the moves within max_game_over. On
+    /// equal values, the earlier move is kept.
+    fn select_move(&self, scores: &[MoveEvalResult, ..4],
+                   risks: &[Risk, ..4]) -> IntermediateBestMove {
+        let mut safest = 1.0f64;
+        for (eval, risk) in scores.iter().zip(risks.iter()) {
+            match *eval {
+                Valid(_) => safest = safest.min(risk.game_over),
+                InvalidMove => ()
+            }
+        }
+        // if no move is within the limit, the safest ones are left
+        let limit = self.max_game_over.unwrap_or(1.0).max(safest);
+
+        let mut result: IntermediateBestMove = GameOver;
+        let mut best_utility = 0.0;
+        for move in ALL_DIRECTIONS.iter() {
+            let risk = risks[*move as uint];
+            match scores[*move as uint] {
+                Valid(score) if risk.game_over <= limit => {
+                    let utility = score as f64 - self.risk_aversion * risk.stddev();
+                    match result {
+                        Found(..) if best_utility >= utility => (),
+                        _ => {
+                            result = Found(score, *move, risk);
+                            best_utility = utility;
+                        }
+                    }
+                }
+                _ => ()
+            }
+        }
+        result
+    }
+
+    /// Evaluate the moves on the root board with *eval_move*.
+    fn eval_root(&self, board: &Board,
+                 eval_move: |Direction, &mut SearchStats, &mut Trace| -> (MoveEvalResult, Risk))
+                 -> ([MoveEvalResult, ..4], [Risk, ..4], IntermediateBestMove,
+                     SearchStats, Trace)
+    {
+        let mut stats = SearchStats::new();
+        // size the per-depth counters up front, so that they are not
+        // grown in the middle of the search
+        stats.grow(self.max_depth);
+        let mut trace = Trace::new(self.trace_depth);
+        let root = trace.enter(0, board, RootNode);
+        let mut scores = [InvalidMove, ..4];
+        let mut risks = [Risk::none(), ..4];
+        for move in ALL_DIRECTIONS.iter() {
+            let (eval, risk) = eval_move(*move, &mut stats, &mut trace);
+            scores[*move as uint] = eval;
+            risks[*move as uint] = risk;
+        }
+        let result = self.select_move(&scores, &risks);
+        trace.set_value(root, spawn_value(&result));
+        trace.leave(root);
+        (scores, risks, result, stats, trace)
+    }
+
+    /// Evaluate all moves on *board* and pick the best one. Unlike
+    /// eval_moves, this keeps the scores of all moves and collects
+    /// statistics about the search.
+    pub fn eval(&self, board: &Board) -> Result<Decision, SearchError> {
+        if self.max_depth < 1 {
+            return Err(RootDepthExceeded);
+        }
+
+        let start = time::precise_time_ns();
+        let (scores, risks, result, mut stats, trace) = match self.pool {
+            // each move on the root board gets the full window, so
+            // that the scores of all moves are exact
+            _ if self.mode == Minimax => self.eval_root(board, |dir, stats, trace| {
+                self.minimax_move(board, dir, 1, -SCORE_BOUND, SCORE_BOUND,
+                                  stats, trace)
+            }),
+            Some(ref pool) => {
+                let ctx = self.clone();
+                let board = board.clone();
+                pool.run(proc(scope: &mut Scope) {
+                    ctx.eval_root(&board, |dir, stats, trace| {
+                        ctx.eval_move_split(&board, dir, 1, stats, trace, &mut *scope)
+                    })
+                })
+            }
+            None => self.eval_root(board, |dir, stats, trace| {
+                self.eval_move(board, dir, 1, stats, trace)
+            })
+        };
+
+        let best = match result {
+            Found(score, dir, _) => Move(score, dir),
+            DepthExceeded => return Err(RootDepthExceeded),
+            GameOver => {
+                stats.game_over_leaves += 1;
+                NoMove
+            }
+        };
+
+        Ok(Decision { board: board.clone(),
+                      best: best,
+                      scores: scores,
+                      risks: risks,
+                      depth: self.max_depth,
+                      stats: stats,
+                      elapsed_ns: time::precise_time_ns() - start,
+                      trace: if self.trace_depth > 0 { Some(trace) } else { None } })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cmp;
+    use rand::{Rng, SeedableRng, XorShiftRng};
+    use board::{Board, Direction, Score, Up, Down, Left, Right};
+    use trace::{RootNode, MoveNode, SpawnNode, Scored};
+    use rules::{RandomSpawns, AdversarialSpawns};
+    use super::{EvalContext, SearchStats, Move, Minimax, Valid, InvalidMove};
+    use super::{Risk, ChildScores, GameOver, child_risk};
+    use super::GAME_OVER_SCORE;
+
+    /// Minimax without pruning: the value of the best move on *board*,
+    /// each move valued by its worst spawn.
+    fn reference_minimax(board: &Board, depth: uint, max_depth: uint) -> Score {
+        if depth > max_depth {
+            return 0;
+        }
+        let mut best = None;
+        for dir in [Up, Down, Left, Right].iter() {
+            let value = match reference_move(board, *dir, depth, max_depth) {
+                Some(value) => value,
+                None => continue
+            };
+            best = Some(match best {
+                Some(b) => cmp::max(b, value),
+                None => value
+            });
+        }
+        best.unwrap_or(GAME_OVER_SCORE)
+    }
+
+    fn reference_move(board: &Board, dir: Direction,
+                      depth: uint, max_depth: uint) -> Option<Score> {
+        let (new_board, move_score) = board.shifted_board(dir);
+        if new_board == *board {
+            return None;
+        }
+        let mut worst = None;
+        for y in range(0u, new_board.height()) {
+            for x in range(0u, new_board.width()) {
+                if new_board.get(x, y) != 0 {
+                    continue;
+                }
+                for tile in [1u8, 2].iter() {
+                    let value = reference_minimax(&new_board.place_tile(x, y, *tile),
+                                                  depth + 1, max_depth);
+                    worst = Some(match worst {
+                        Some(w) => cmp::min(w, value),
+                        None => value
+                    });
+                }
+            }
+        }
+        Some(move_score + new_board.gradient_score() + worst.unwrap())
+    }
+
+    #[test]
+    fn test_search_stats() {
+        let raw = vec![1u8, 1, 0, 0,
+                       0, 2, 0, 0,
+                       0, 0, 0, 0,
+                       0, 0, 0, 3];
+        let board = Board::from_raw(&raw).unwrap();
+        let mut ctx = EvalContext::new(2, 1.0, 0.6, 2);
+        let untimed = ctx.eval(&board).unwrap();
+        assert_eq!(untimed.stats.time_per_depth_ns, vec![0, 0]);
+        ctx.time_depths = true;
+        let decision = ctx.eval(&board).unwrap();
+        let stats = &decision.stats;
+        assert_eq!(stats.nodes_per_depth.len(), 2);
+        assert_eq!(stats.time_per_depth_ns.len(), 2);
+        assert!(*stats.time_per_depth_ns.get(0) > 0);
+        assert_eq!(stats.nodes_per_depth, untimed.stats.nodes_per_depth);
+        assert_eq!(*stats.nodes_per_depth.get(0), 4);
+        assert_eq!(stats.effective_depth(), 2);
+        assert_eq!(stats.nodes(),
+                   *stats.nodes_per_depth.get(0) + *stats.nodes_per_depth.get(1));
+        // spawns below depth two are cut off by the depth limit, the
+        // others are searched one level deeper
+        assert!(stats.depth_exceeded_leaves > 0);
+        assert!(stats.depth_exceeded_leaves < stats.chance_nodes);
+        assert_eq!(stats.game_over_leaves, 0);
+        assert!(stats.branching_factor() > 0.0);
+    }
+
+    #[test]
+    fn test_search_stats_merge() {
+        let mut a = SearchStats::new();
+        a.nodes_per_depth = vec![1, 2];
+        a.time_per_depth_ns = vec![10, 20];
+        a.chance_nodes = 3;
+        let mut b = SearchStats::new();
+        b.nodes_per_depth = vec![0, 1, 4];
+        b.time_per_depth_ns = vec![0, 5, 7];
+        b.game_over_leaves = 2;
+        b.depth_exceeded_leaves = 1;
+        a.merge(&b);
+        assert_eq!(a.nodes_per_depth, vec![1, 3, 4]);
+        assert_eq!(a.time_per_depth_ns, vec![10, 25, 7]);
+        assert_eq!(a.nodes(), 8);
+        assert_eq!(a.effective_depth(), 3);
+        assert_eq!(a.chance_nodes, 3);
+        assert_eq!(a.game_over_leaves, 2);
+        assert_eq!(a.depth_exceeded_leaves, 1);
+        assert_eq!(a.branching_factor(), 7.0 / 4.0);
+    }
+
+    #[test]
+    fn test_trace() {
+        let raw = vec![1u8, 1, 0, 0,
+                       0, 2, 0, 0,
+                       0, 0, 0, 0,
+                       0, 0, 0, 3];
+        let board = Board::from_raw(&raw).unwrap();
+        let mut ctx = EvalContext::new(2, 1.0, 0.6, 2);
+        assert!(ctx.eval(&board).unwrap().trace.is_none());
+
+        ctx.trace_depth = 1;
+        let decision = ctx.eval(&board).unwrap();
+        let trace = decision.trace.unwrap();
+        let root = trace.nodes.get(0);
+        match (&root.kind, &root.value) {
+            (&RootNode, &Scored(score)) => match decision.best {
+                Move(best, _) => assert_eq!(score, best),
+                _ => fail!("no move found")
+            },
+            _ => fail!("unexpected root node")
+        }
+        let moves = trace.nodes.iter().filter(|n| match n.kind {
+            MoveNode(_) => true,
+            _ => false
+        }).count();
+        let spawns = trace.nodes.iter().filter(|n| match n.kind {
+            SpawnNode(..) => true,
+            _ => false
+        }).count();
+        assert_eq!(moves, *decision.stats.nodes_per_depth.get(0));
+        assert_eq!(trace.nodes.len(), 1 + moves + spawns);
+        for node in trace.nodes.iter().skip(1) {
+            assert_eq!(node.depth, 1);
+            match node.value {
+                Scored(_) => (),
+                _ => fail!("node without value: {}", node.kind)
+            }
+            assert!(node.parent.unwrap() < trace.nodes.len());
+        }
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        let mut rng: XorShiftRng = SeedableRng::from_seed([1, 2, 3, 4]);
+        let sequential = EvalContext::new(3, 1.0, 0.6, 2);
+        let mut parallel = sequential.clone();
+        parallel.set_threads(4, 2, None);
+        parallel.trace_depth = 2;
+        let mut sequential = sequential;
+        sequential.trace_depth = 2;
+
+        for _ in range(0u, 20) {
+            let raw = Vec::from_fn(16, |_| {
+                if rng.gen_weighted_bool(2) { 0 } else { rng.gen_range(1u8, 8) }
+            });
+            let board = Board::from_raw(&raw).unwrap();
+            let a = sequential.eval(&board).unwrap();
+            let b = parallel.eval(&board).unwrap();
+            assert_eq!(format!("{}", a.best), format!("{}", b.best));
+            for (x, y) in a.scores.iter().zip(b.scores.iter()) {
+                assert_eq!(format!("{}", *x), format!("{}", *y));
+            }
+            assert_eq!(a.stats.nodes_per_depth, b.stats.nodes_per_depth);
+            assert_eq!(a.stats.chance_nodes, b.stats.chance_nodes);
+            assert_eq!(a.stats.game_over_leaves, b.stats.game_over_leaves);
+            assert_eq!(a.stats.depth_exceeded_leaves, b.stats.depth_exceeded_leaves);
+            let (ta, tb) = (a.trace.unwrap(), b.trace.unwrap());
+            assert_eq!(ta.nodes.len(), tb.nodes.len());
+            for (x, y) in ta.nodes.iter().zip(tb.nodes.iter()) {
+                assert_eq!(x.parent, y.parent);
+                assert_eq!(format!("{}", x.value), format!("{}", y.value));
+            }
+        }
+    }
+
+    #[test]
+    fn test_minimax_matches_reference() {
+        let mut rng: XorShiftRng = SeedableRng::from_seed([4, 3, 2, 1]);
+        let mut ctx = EvalContext::new(2, 1.0, 0.6, 2);
+        ctx.mode = Minimax;
+        for _ in range(0u, 10) {
+            let raw = Vec::from_fn(9, |_| {
+                if rng.gen_weighted_bool(2) { 0 } else { rng.gen_range(1u8, 6) }
+            });
+            let board = Board::from_raw_sized(3, 3, &raw).unwrap();
+            let decision = ctx.eval(&board).unwrap();
+            for (i, dir) in [Up, Down, Left, Right].iter().enumerate() {
+                match (decision.scores[i], reference_move(&board, *dir, 1, 2)) {
+                    (Valid(score), Some(expected)) => assert_eq!(score, expected),
+                    (InvalidMove, None) => (),
+                    (eval, expected) => fail!("{}: {} != {}", board, eval, expected)
+                }
+            }
+            match decision.best {
+                Move(score, _) => assert_eq!(score, reference_minimax(&board, 1, 2)),
+                _ => assert_eq!(reference_minimax(&board, 1, 2), GAME_OVER_SCORE)
+            }
+        }
+    }
+
+    #[test]
+    fn test_worst_spawn() {
+        // a 2 in the free corner leaves no move, which is worse than
+        // the 4, which can be merged
+        let board = Board::from_raw_sized(3, 3, &vec![1u8, 2, 1,
+                                                      2, 1, 2,
+                                                      1, 2, 0]).unwrap();
+        let mut ctx = EvalContext::new(1, 1.0, 0.6, 2);
+        ctx.mode = Minimax;
+        assert_eq!(ctx.worst_spawn(&board), Some((2, 2, 1)));
+        assert_eq!(ctx.worst_spawn(&board.place_tile(2, 2, 3)), None);
+    }
+
+    #[test]
+    fn test_child_risk() {
+        let mut children = ChildScores::new();
+        children.add(10, Risk::none(), 0.5);
+        children.add(30, Risk { variance: 4.0, game_over: 1.0 }, 0.5);
+        let risk = children.risk(RandomSpawns);
+        assert_eq!(risk.variance, 4.0 / 2.0 + 100.0);
+        assert_eq!(risk.game_over, 0.5);
+        // a child three times as likely counts three times as much
+        let mut weighted = ChildScores::new();
+        weighted.add(10, Risk::none(), 0.75);
+        weighted.add(30, Risk { variance: 4.0, game_over: 1.0 }, 0.25);
+        assert_eq!(weighted.mean(), 15.0);
+        let risk = weighted.risk(RandomSpawns);
+        assert_eq!(risk.game_over, 0.25);
+        assert_eq!(risk.variance, 4.0 * 0.25 + 75.0);
+        let risk = children.risk(AdversarialSpawns);
+        assert_eq!(risk.variance, 0.0);
+        assert_eq!(risk.game_over, 0.0);
+        assert_eq!(child_risk(&GameOver).game_over, 1.0);
+        assert_eq!(ChildScores::new().risk(RandomSpawns).game_over, 0.0);
+    }
+
+    #[test]
+    fn test_spawn_probability_weights_children() {
+        let board = Board::from_raw(&vec![1u8, 2, 3, 0,
+                                          0, 1, 0, 0,
+                                          0, 0, 2, 0,
+                                          0, 0, 0, 1]).unwrap();
+        let mut low = EvalContext::new(2, 1.0, 0.6, 2);
+        low.rules.high_probability = 0.2;
+        let mut high = low.clone();
+        high.rules.high_probability = 0.9;
+        let (a, b) = (low.eval(&board).unwrap(), high.eval(&board).unwrap());
+        let differs = range(0u, 4).any(|i| match (a.scores[i], b.scores[i]) {
+            (Valid(x), Valid(y)) => x != y,
+            _ => false
+        });
+        assert!(differs);
+    }
+
+    #[test]
+    fn test_risk_aware_selection() {
+        let mut rng: XorShiftRng = SeedableRng::from_seed([5, 6, 7, 8]);
+        let neutral = EvalContext::new(2, 1.0, 0.6, 2);
+        let mut survival = neutral.clone();
+        survival.max_game_over = Some(0.0);
+        let mut averse = neutral.clone();
+        averse.risk_aversion = 1e9;
+
+        for _ in range(0u, 20) {
+            let raw = Vec::from_fn(9, |_| {
+                if rng.gen_weighted_bool(3) { 0 } else { rng.gen_range(1u8, 6) }
+            });
+            let board = Board::from_raw_sized(3, 3, &raw).unwrap();
+            let a = neutral.eval(&board).unwrap();
+            let valid: Vec<uint> = range(0u, 4).filter(|i| match a.scores[*i] {
+                Valid(_) => true,
+                InvalidMove => false
+            }).collect();
+            for i in valid.iter() {
+                let risk = a.risks[*i];
+                assert!(risk.game_over >= 0.0 && risk.game_over <= 1.0);
+                assert!(risk.variance >= 0.0);
+            }
+            let best = match a.best {
+                Move(_, dir) => dir as uint,
+                _ => {
+                    assert!(valid.is_empty());
+                    continue
+                }
+            };
+
+            // without a constraint, the highest score is picked
+            for i in valid.iter() {
+                match (a.scores[best], a.scores[*i]) {
+                    (Valid(x), Valid(y)) => assert!(x >= y),
+                    _ => fail!("invalid move picked")
+                }
+            }
+
+            // the survival constraint falls back to the safest move
+            let b = survival.eval(&board).unwrap();
+            let safest = valid.iter().map(|i| b.risks[*i].game_over)
+                .fold(1.0, |a: f64, b| a.min(b));
+            match b.best {
+                Move(_, dir) => assert_eq!(b.risks[dir as uint].game_over, safest),
+                _ => fail!("no move picked")
+            }
+
+            // an extreme aversion picks the least spread outcome
+            let c = averse.eval(&board).unwrap();
+            let steadiest = valid.iter().map(|i| c.risks[*i].stddev())
+                .fold(1e300, |a: f64, b| a.min(b));
+            match c.best {
+                Move(_, dir) => assert_eq!(c.risks[dir as uint].stddev(), steadiest),
+                _ => fail!("no move picked")
+            }
+        }
+    }
+}
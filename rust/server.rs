@@ -0,0 +1,289 @@
+use std::io::{Acceptor, Listener, Stream};
+use std::io::net::ip::{SocketAddr, Ipv4Addr};
+use std::io::net::tcp::TcpListener;
+use std::io::net::unix::UnixListener;
+use std::task;
+use getopts;
+use log;
+use sync::{Arc, Semaphore};
+
+use config;
+use config::Config;
+use error::{AiError, IoFailure, ConfigFailure};
+use logging;
+use logging::SharedSink;
+use pool::Pool;
+use protocol::{read_message, answer, MoveRequest, ConfigureRequest};
+use protocol::{CONFIGURE_OK, CONFIGURE_REJECTED, Outcome, InputClosed, Resigned};
+use search::EvalContext;
+
+/// Where a server listens.
+pub enum Address {
+    /// A Unix domain socket at the path.
+    UnixAddress(Path),
+    /// A TCP port on the loopback interface.
+    TcpAddress(u16)
+}
+
+impl Address {
+    /// Parse ``unix:PATH`` or ``tcp:PORT``.
+    pub fn parse(s: &str) -> Option<Address> {
+        if s.starts_with("unix:") && s.len() > 5 {
+            Some(UnixAddress(Path::new(s.slice_from(5))))
+        } else if s.starts_with("tcp:") {
+            from_str::<u16>(s.slice_from(4)).map(|port| TcpAddress(port))
+        } else {
+            None
+        }
+    }
+}
+
+/// Serves the pipe protocol to many hosts at once, one session per
+/// connection. Each session has a searcher of its own, but they share
+/// the worker threads, and only a bounded number of searches runs at a
+/// time; the others wait for their turn.
+pub struct Server {
+    /// The configuration each session starts with.
+    config: Config,
+    pool: Option<Arc<Pool>>,
+    searches: Semaphore,
+    sink: SharedSink,
+    log_stats: bool
+}
+
+impl Server {
+    /// Create a server whose sessions start with *config*, of which at
+    /// most *max_searches* search at the same time. The threads of
+    /// *config* are shared by all sessions. Decisions are logged to
+    /// *sink* like those of the AI on stdin/stdout, with the search
+    /// statistics if *log_stats* is set.
+    pub fn new(config: Config, max_searches: uint, sink: SharedSink,
+               log_stats: bool) -> Server {
+        assert!(max_searches >= 1);
+        let pool = if config.threads > 1 {
+            Some(Arc::new(Pool::new(config.threads, Some(sink.clone()))))
+        } else {
+            None
+        };
+        Server { config: config,
+                 pool: pool,
+                 searches: Semaphore::new(max_searches as int),
+                 sink: sink,
+                 log_stats: log_stats }
+    }
+
+    /// Listen on *address* and serve each connection on a task of its
+    /// own. Only returns if listening fails.
+    pub fn run(self, address: &Address) -> Result<(), AiError> {
+        let server = Arc::new(self);
+        match *address {
+            UnixAddress(ref path) => {
+                let acceptor = try!(UnixListener::bind(path).listen()
+                                    .map_err(|e| IoFailure(e)));
+                info!("listening on {}", path.display());
+                accept(server, acceptor)
+            }
+            TcpAddress(port) => {
+                let addr = SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: port };
+                let acceptor = try!(TcpListener::bind(addr).listen()
+                                    .map_err(|e| IoFailure(e)));
+                info!("listening on {}", addr);
+                accept(server, acceptor)
+            }
+        }
+    }
+
+    fn context(&self, config: &Config) -> EvalContext {
+        let mut single = config.clone();
+        single.threads = 1;
+        let mut ctx = single.context();
+        ctx.pool = self.pool.clone();
+        ctx.time_depths = self.log_stats;
+        ctx
+    }
+
+    /// Answer the requests of one session from *input* on *output*
+    /// until the input ends or the searcher finds no move anymore.
+    /// EXT_CONFIGURE requests set the options of the session, on top of
+    /// the configuration of the server; the number of threads is the
+    /// one of the server, though.
+    pub fn session<R: Reader, W: Writer>(&self, input: &mut R,
+                                         output: &mut W) -> Result<Outcome, AiError> {
+        let mut config = self.config.clone();
+        let mut ctx = self.context(&config);
+        loop {
+            match try!(read_message(input)) {
+                None => return Ok(InputClosed),
+                Some(ConfigureRequest(options)) => {
+                    let status = match configure(&self.config, options.as_slice()) {
+                        Ok(new_config) => {
+                            config = new_config;
+                            ctx = self.context(&config);
+                            CONFIGURE_OK
+                        }
+                        Err(e) => {
+                            info!("rejected options \"{}\": {}", options, e);
+                            CONFIGURE_REJECTED
+                        }
+                    };
+                    try!(output.write_u8(status).map_err(|e| IoFailure(e)));
+                    try!(output.flush().map_err(|e| IoFailure(e)));
+                }
+                Some(MoveRequest(board)) => {
+                    let sink = &self.sink;
+                    let log_stats = self.log_stats;
+                    let policy = &config.policy;
+                    let answered = try!(self.searches.access(|| {
+                        answer(&mut ctx, policy, &board, output, |decision| {
+                            if log_enabled!(log::INFO) {
+                                let mut sink = sink.lock();
+                                sink.decision(decision);
+                                if log_stats {
+                                    sink.stats(&decision.stats);
+                                }
+                            }
+                        })
+                    }));
+                    if !answered {
+                        return Ok(Resigned);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn accept<S: Stream + Clone + Send, A: Acceptor<S>>(server: Arc<Server>,
+                                                   mut acceptor: A) -> Result<(), AiError> {
+    let mut sessions = 0u;
+    loop {
+        let stream = try!(acceptor.accept().map_err(|e| IoFailure(e)));
+        sessions += 1;
+        let id = sessions;
+        let server = server.clone();
+        task::spawn(proc() {
+            // loggers are per task, see logging::install
+            logging::install(&server.sink);
+            let mut input = stream.clone();
+            let mut output = stream;
+            info!("session {} started", id);
+            match server.session(&mut input, &mut output) {
+                Ok(outcome) => info!("session {} ended: {}", id, outcome),
+                Err(e) => error!("session {} failed: {}", id, e)
+            }
+        });
+    }
+}
+
+/// Apply the command line *options* on top of *base*. A session may
+/// not search deeper than the server would, so that one session cannot
+/// hold a search slot for much longer than the others.
+fn configure(base: &Config, options: &str) -> Result<Config, AiError> {
+    let args = options.words().map(|arg| String::from_str(arg)).collect::<Vec<String>>();
+    let matches = try!(getopts::getopts(args.as_slice(), config::options().as_slice())
+                       .map_err(|e| ConfigFailure(e.to_err_msg())));
+    if matches.free.len() > 0 {
+        return Err(ConfigFailure(format!(
+            "unexpected argument: {}", matches.free.get(0))));
+    }
+    let mut config = base.clone();
+    try!(config.update(&matches));
+    if config.policy.max_depth > base.policy.max_depth {
+        return Err(ConfigFailure(format!(
+            "max-depth {} is above the {} of the server",
+            config.policy.max_depth, base.policy.max_depth)));
+    }
+    if config.adversary_depth > base.adversary_depth {
+        return Err(ConfigFailure(format!(
+            "adversary-depth {} is above the {} of the server",
+            config.adversary_depth, base.adversary_depth)));
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use config::Config;
+    use logging::{LogSink, TextFormat};
+    use protocol::{write_request, InputClosed, Resigned, EXT_CONFIGURE};
+    use protocol::{CONFIGURE_OK, CONFIGURE_REJECTED};
+    use board::Board;
+    use super::{Server, Address, UnixAddress, TcpAddress};
+
+    fn server() -> Server {
+        let mut config = Config::default();
+        config.threads = 1;
+        let sink = LogSink::new(box io::util::NullWriter as Box<Writer:Send>, TextFormat);
+        Server::new(config, 1, sink.shared(), false)
+    }
+
+    fn configure(stream: &mut Vec<u8>, options: &str) {
+        stream.push_all(Vec::from_elem(16, 0u8).as_slice());
+        stream.push(EXT_CONFIGURE);
+        stream.push(0);
+        stream.push(options.len() as u8);
+        stream.push_all(options.as_bytes());
+    }
+
+    fn board(cells: &[u8]) -> Board {
+        Board::from_raw(&Vec::from_slice(cells)).ok().unwrap()
+    }
+
+    #[test]
+    fn test_address() {
+        match Address::parse("unix:/tmp/ai.sock") {
+            Some(UnixAddress(path)) => assert!(path == Path::new("/tmp/ai.sock")),
+            _ => fail!("expected a unix address")
+        }
+        match Address::parse("tcp:7048") {
+            Some(TcpAddress(7048)) => (),
+            _ => fail!("expected a tcp address")
+        }
+        assert!(Address::parse("tcp:http").is_none());
+        assert!(Address::parse("unix:").is_none());
+        assert!(Address::parse("/tmp/ai.sock").is_none());
+    }
+
+    #[test]
+    fn test_session() {
+        let mut stream = Vec::new();
+        configure(&mut stream, "--depth 2 --min-depth 1 --max-depth 2");
+        configure(&mut stream, "--depth nine");
+        configure(&mut stream, "--depth 40 --max-depth 40");
+        let mut out = io::MemWriter::new();
+        write_request(&board(&[1u8, 1, 0, 0,
+                               0, 0, 0, 0,
+                               0, 0, 0, 0,
+                               0, 0, 0, 0]), &mut out).unwrap();
+        stream.push_all(out.unwrap().as_slice());
+
+        let mut input = io::MemReader::new(stream);
+        let mut output = io::MemWriter::new();
+        match server().session(&mut input, &mut output) {
+            Ok(InputClosed) => (),
+            other => fail!("unexpected result: {}", other)
+        }
+        let responses = output.unwrap();
+        assert_eq!(responses.len(), 4);
+        assert_eq!(*responses.get(0), CONFIGURE_OK);
+        assert_eq!(*responses.get(1), CONFIGURE_REJECTED);
+        assert_eq!(*responses.get(2), CONFIGURE_REJECTED);
+        assert!(*responses.get(3) < 4);
+    }
+
+    #[test]
+    fn test_session_resigns() {
+        let mut out = io::MemWriter::new();
+        write_request(&board(&[1u8, 2, 1, 2,
+                               2, 1, 2, 1,
+                               1, 2, 1, 2,
+                               2, 1, 2, 1]), &mut out).unwrap();
+        let mut input = io::MemReader::new(out.unwrap());
+        let mut output = io::MemWriter::new();
+        match server().session(&mut input, &mut output) {
+            Ok(Resigned) => (),
+            other => fail!("unexpected result: {}", other)
+        }
+    }
+}
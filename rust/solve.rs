@@ -0,0 +1,162 @@
+extern crate getopts;
+extern crate rand;
+extern crate g2048;
+
+use std::cmp;
+use std::io;
+use std::os;
+use rand::Rng;
+
+use g2048::board::MIN_SIZE;
+use g2048::config;
+use g2048::error::{AiError, ConfigFailure, SearchFailure, SolverFailure, EXIT_CONFIG};
+use g2048::search::{Move, NoMove};
+use g2048::solver::{Solver, Objective, ReachTile, ExpectedScore, MAX_SOLVER_CELLS};
+
+/// The largest size on which whole games can be solved; larger boards
+/// need a horizon.
+static MAX_UNBOUNDED_SIZE: uint = 3;
+
+/// How far the moves of the heuristic search fell short of the exact
+/// values over all positions of the games played.
+struct Summary {
+    positions: uint,
+    optimal: uint,
+    total_loss: f64,
+    max_loss: f64,
+    total_start_value: f64
+}
+
+/// Let the heuristic search play the game with *seed* and compare
+/// each of its moves with the exact values of all moves.
+fn play(config: &config::Config,
+        solver: &mut Solver,
+        size: uint,
+        seed: u32,
+        summary: &mut Summary) -> Result<(), AiError> {
+    let mut ctx = config.context();
+    let mut game = try!(config.new_game(size, size, seed));
+    summary.total_start_value += try!(solver.value(game.board()).map_err(
+        |e| SolverFailure(e)));
+
+    while !game.is_over() {
+        let board = game.board().clone();
+        ctx.max_depth = config.policy.depth_for(&board);
+        let dir = match ctx.eval(&board) {
+            Ok(decision) => match decision.best {
+                Move(_, dir) => dir,
+                NoMove => return Ok(())
+            },
+            Err(e) => return Err(SearchFailure(e))
+        };
+        let values = try!(solver.move_values(&board).map_err(|e| SolverFailure(e)));
+        let best = values.iter().filter_map(|v| *v).fold(0.0, |a: f64, b| a.max(b));
+        let chosen = values[dir as uint].unwrap_or(0.0);
+        let loss = best - chosen;
+        summary.positions += 1;
+        if loss < 1e-9 {
+            summary.optimal += 1;
+        }
+        summary.total_loss += loss;
+        summary.max_loss = summary.max_loss.max(loss);
+
+        game.play(dir);
+    }
+    Ok(())
+}
+
+fn options() -> Vec<getopts::OptGroup> {
+    let mut opts = config::options();
+    opts.push(getopts::optopt(
+        "", "size", "width and height of the board (3)", "N"));
+    opts.push(getopts::optopt(
+        "", "goal", "maximise the probability to build 2^N (8)", "N"));
+    opts.push(getopts::optflag(
+        "", "score", "maximise the expected score instead"));
+    opts.push(getopts::optopt(
+        "", "horizon", "look ahead N moves instead of to the end of the game \
+                        (required on boards larger than 3x3)", "N"));
+    opts.push(getopts::optopt(
+        "", "games", "number of games to play (10)", "N"));
+    opts.push(getopts::optopt(
+        "", "seed", "seed for the spawns (random)", "N"));
+    opts
+}
+
+fn run(args: &[String]) -> Result<(), AiError> {
+    let opts = options();
+    let (config, matches) = match try!(config::parse_args(args, opts.as_slice())) {
+        Some(parsed) => parsed,
+        None => {
+            println!("{}", getopts::usage(
+                "Usage: solve [OPTIONS]\n\n\
+                 Play games with the heuristic search on a small board and \
+                 compare its moves\nwith the exact values computed by the \
+                 solver.",
+                opts.as_slice()));
+            return Ok(());
+        }
+    };
+
+    let size = try!(config::parse_or(&matches, "size", 3u));
+    if size < MIN_SIZE || size * size > MAX_SOLVER_CELLS {
+        return Err(ConfigFailure(format!("unsupported board size: {}", size)));
+    }
+    if config.rules.spawns_per_move > 1 {
+        return Err(ConfigFailure(format!("the solver handles one spawn per move, not {}",
+                                         config.rules.spawns_per_move)));
+    }
+    let objective: Objective = if matches.opt_present("score") {
+        ExpectedScore
+    } else {
+        ReachTile(try!(config::parse_or(&matches, "goal", 8u8)))
+    };
+    let horizon = match matches.opt_str("horizon") {
+        Some(_) => Some(try!(config::parse_or(&matches, "horizon", 0u))),
+        None => None
+    };
+    if size > MAX_UNBOUNDED_SIZE && horizon.is_none() {
+        return Err(ConfigFailure(format!(
+            "whole games cannot be solved on {}x{} boards, give a --horizon", size, size)));
+    }
+    let games = try!(config::parse_or(&matches, "games", 10u));
+    let seed = try!(config::parse_or(&matches, "seed", rand::task_rng().gen::<u32>()));
+
+    let mut solver = Solver::with_rules(objective, horizon, config.rules.clone());
+    let mut summary = Summary { positions: 0,
+                                optimal: 0,
+                                total_loss: 0.0,
+                                max_loss: 0.0,
+                                total_start_value: 0.0 };
+    for i in range(0, games) {
+        try!(play(&config, &mut solver, size, seed + i as u32, &mut summary));
+    }
+
+    println!("objective={} horizon={} size={} games={} seed={}",
+             objective,
+             match horizon { Some(n) => format!("{}", n), None => String::from_str("none") },
+             size, games, seed);
+    println!("positions={} optimal_moves={} ({:.1f}%) mean_loss={:.6f} max_loss={:.6f}",
+             summary.positions, summary.optimal,
+             100.0 * summary.optimal as f64 / cmp::max(summary.positions, 1) as f64,
+             summary.total_loss / cmp::max(summary.positions, 1) as f64,
+             summary.max_loss);
+    println!("mean_start_value={:.6f} solved_positions={}",
+             summary.total_start_value / cmp::max(games, 1) as f64,
+             solver.positions());
+    Ok(())
+}
+
+fn main() {
+    let args = os::args();
+    match run(args.tail()) {
+        Ok(()) => (),
+        Err(e) => {
+            let _ = writeln!(&mut io::stderr(), "solve: {}", e);
+            if e.exit_code() == EXIT_CONFIG {
+                let _ = writeln!(&mut io::stderr(), "solve: try --help");
+            }
+            os::set_exit_status(e.exit_code());
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use collections::HashMap;
+use std::fmt;
+
+use board::{Board, Direction, Up, Down, Left, Right, MAX_CELLS};
+use rules::{Rules, RandomSpawns, AdversarialSpawns};
+
+/// The largest board the solver accepts. Positions are memoised under
+/// a key holding five bits per cell in two words.
+pub static MAX_SOLVER_CELLS: uint = 24;
+
+static CELLS_PER_WORD: uint = 12;
+static BLOCKER_KEY: u64 = 31;
+
+static ALL_DIRECTIONS: [Direction, ..4] = [Up, Down, Left, Right];
+
+/// The quantity the solver maximises.
+#[deriving(Clone, Show)]
+pub enum Objective {
+    /// The probability that a tile with the given exponent appears on
+    /// the board.
+    ReachTile(u8),
+    /// The expected sum of the values of the tiles built by merges,
+    /// which is the score the game displays.
+    ExpectedScore
+}
+
+/// Reasons for the solver to reject a position.
+pub enum SolverError {
+    /// The board has more cells than MAX_SOLVER_CELLS; carries the
+    /// number of cells.
+    TooManyCells(uint),
+    /// The rules spawn more than one tile per move; carries their
+    /// number.
+    MultipleSpawns(uint)
+}
+
+impl fmt::Show for SolverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TooManyCells(cells) =>
+                write!(f.buf, "board has {} cells, the solver handles at most {}",
+                       cells, MAX_SOLVER_CELLS),
+            MultipleSpawns(spawns) =>
+                write!(f.buf, "the solver handles one spawn per move, not {}", spawns)
+        }
+    }
+}
+
+/// Packed board contents, size and remaining moves of a position.
+/// Blockers are packed as BLOCKER_KEY, which is above any tile.
+type Key = (u64, u64, uint, uint, uint);
+
+/// Computes exact expectimax values. After each move, a tile spawns
+/// on one of the empty cells, each cell being equally likely, with
+/// the tile probabilities of the rules; under adversarial spawns, the
+/// worst cell and tile are taken instead. The player picks the move
+/// with the highest expected value. A game which reaches the target
+/// tile of the rules ends there.
+///
+/// The values of all positions visited are kept, so solving the
+/// positions of one game one after another shares most of the work.
+/// On 3x3 boards, whole games can be solved; on 4x4 boards, this is
+/// only feasible with a horizon of a few moves.
+pub struct Solver {
+    objective: Objective,
+    horizon: Option<uint>,
+    rules: Rules,
+    memo: HashMap<Key, f64>
+}
+
+impl Solver {
+    /// Create a solver for *objective*, which looks ahead *horizon*
+    /// moves, or until the game ends if *horizon* is None. Values are
+    /// then relative to the horizon: the probability to reach the
+    /// tile, or the expected score gained, within that many moves.
+    pub fn new(objective: Objective, horizon: Option<uint>) -> Solver {
+        Solver::with_rules(objective, horizon, Rules::default())
+    }
+
+    /// Create a solver for a game variant. Only variants with one
+    /// spawn per move are supported, which is checked along with each
+    /// board.
+    pub fn with_rules(objective: Objective, horizon: Option<uint>,
+                      rules: Rules) -> Solver {
+        Solver { objective: objective,
+                 horizon: horizon,
+                 rules: rules,
+                 memo: HashMap::new() }
+    }
+
+    pub fn objective(&self) -> Objective {
+        self.objective
+    }
+
+    /// The number of positions whose value is memoised.
+    pub fn positions(&self) -> uint {
+        self.memo.len()
+    }
+
+    /// The value of *board* with the player to move.
+    pub fn value(&mut self, board: &Board) -> Result<f64, SolverError> {
+        try!(self.check_board(board));
+        let horizon = self.horizon;
+        Ok(self.position_value(board, horizon))
+    }
+
+    /// The value of each move on *board*, indexed by the Direction
+    /// value; None for moves which do not change the board. The move
+    /// counts towards the horizon, so with a horizon of zero all
+    /// values are None.
+    pub fn move_values(&mut self, board: &Board) -> Result<[Option<f64>, ..4], SolverError> {
+        try!(self.check_board(board));
+        let mut values = [None, ..4];
+        let remaining = match self.horizon {
+            Some(0) => return Ok(values),
+            Some(n) => Some(n - 1),
+            None => None
+        };
+        for dir in ALL_DIRECTIONS.iter() {
+            values[*dir as uint] = self.move_value(board, *dir, remaining);
+        }
+        Ok(values)
+    }
+
+    fn check_board(&self, board: &Board) -> Result<(), SolverError> {
+        if board.cells() > MAX_SOLVER_CELLS {
+            return Err(TooManyCells(board.cells()));
+        }
+        if self.rules.spawns_per_move != 1 {
+            return Err(MultipleSpawns(self.rules.spawns_per_move));
+        }
+        Ok(())
+    }
+
+    /// The value of a position, after a spawn, with *remaining* moves
+    /// left.
+    fn position_value(&mut self, board: &Board, remaining: Option<uint>) -> f64 {
+        match self.objective {
+            ReachTile(tile) if board.highest_tile() >= tile => return 1.0,
+            _ => ()
+        }
+        if self.rules.reached_target(board) {
+            return 0.0;
+        }
+        let next = match remaining {
+            Some(0) => return 0.0,
+            Some(n) => Some(n - 1),
+            None => None
+        };
+
+        let key = position_key(board, remaining);
+        match self.memo.find(&key) {
+            Some(value) => return *value,
+            None => ()
+        }
+
+        // a position without moves is lost and gains nothing more,
+        // which is zero for both objectives
+        let mut best = 0.0;
+        for dir in ALL_DIRECTIONS.iter() {
+            match self.move_value(board, *dir, next) {
+                Some(value) if value > best => best = value,
+                _ => ()
+            }
+        }
+        self.memo.insert(key, best);
+        best
+    }
+
+    /// The value of playing *dir* on *board*, with *remaining* moves
+    /// left after it, or None if the move does not change the board.
+    fn move_value(&mut self, board: &Board, dir: Direction,
+                  remaining: Option<uint>) -> Option<f64> {
+        let (new_board, points) = board.shifted_with_points(dir);
+        if new_board == *board {
+            return None;
+        }
+
+        // a valid move always leaves at least one empty cell
+        let mut cells = [(0u, 0u), ..MAX_CELLS];
+        let free = new_board.empty_cells(&mut cells);
+        let (tiles, ntiles) = self.rules.spawn_tiles();
+        let mut total = 0.0;
+        let mut worst: Option<f64> = None;
+        for cell in cells.slice_to(free).iter() {
+            let (x, y) = *cell;
+            for tile in tiles.slice_to(ntiles).iter() {
+                let value = self.position_value(&new_board.place_tile(x, y, *tile),
+                                                remaining);
+                total += self.rules.spawn_probability(*tile) * value;
+                worst = match worst {
+                    Some(w) if w <= value => Some(w),
+                    _ => Some(value)
+                };
+            }
+        }
+        let expected = match self.rules.spawn_mode {
+            RandomSpawns => total / free as f64,
+            AdversarialSpawns => worst.unwrap()
+        };
+
+        Some(match self.objective {
+            ReachTile(_) => expected,
+            ExpectedScore => points as f64 + expected
+        })
+    }
+}
+
+fn position_key(board: &Board, remaining: Option<uint>) -> Key {
+    let mut words = [0u64, 0u64];
+    let mut i = 0u;
+    for y in range(0u, board.height()) {
+        for x in range(0u, board.width()) {
+            let cell = if board.is_blocker(x, y) {
+                BLOCKER_KEY
+            } else {
+                board.get(x, y) as u64
+            };
+            words[i / CELLS_PER_WORD] |= cell << (5 * (i % CELLS_PER_WORD));
+            i += 1;
+        }
+    }
+    (words[0], words[1], board.width(), board.height(), remaining.unwrap_or(0))
+}
+
+#[cfg(test)]
+mod test {
+    use board::{Board, Up, Left};
+    use rules::{Rules, AdversarialSpawns};
+    use super::{Solver, ReachTile, ExpectedScore, TooManyCells, MultipleSpawns};
+
+    fn board(cells: &[u8]) -> Board {
+        Board::from_raw_sized(3, 3, &Vec::from_slice(cells)).unwrap()
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_reach_tile() {
+        // within one move, a single 2 can only be joined by a 4 from
+        // the spawn
+        let b = board([1, 0, 0,
+                       0, 0, 0,
+                       0, 0, 0]);
+        assert_close(Solver::new(ReachTile(2), Some(0)).value(&b).ok().unwrap(), 0.0);
+        assert_close(Solver::new(ReachTile(2), Some(1)).value(&b).ok().unwrap(), 0.1);
+        assert_close(Solver::new(ReachTile(1), Some(0)).value(&b).ok().unwrap(), 1.0);
+
+        // two 2s next to each other merge for sure
+        let b = board([1, 1, 0,
+                       0, 0, 0,
+                       0, 0, 0]);
+        let mut solver = Solver::new(ReachTile(2), Some(1));
+        assert_close(solver.value(&b).ok().unwrap(), 1.0);
+        let values = solver.move_values(&b).ok().unwrap();
+        assert_close(values[Left as uint].unwrap(), 1.0);
+        assert_close(values[Up as uint].unwrap_or(-1.0), -1.0);
+
+        // more moves never lower the probability
+        let mut last = 0.0;
+        for horizon in range(1u, 6) {
+            let value = Solver::new(ReachTile(4), Some(horizon)).value(&b).ok().unwrap();
+            assert!(value >= last && value <= 1.0);
+            last = value;
+        }
+    }
+
+    #[test]
+    fn test_expected_score() {
+        let b = board([1, 1, 0,
+                       0, 0, 0,
+                       0, 0, 0]);
+        assert_close(Solver::new(ExpectedScore, Some(1)).value(&b).ok().unwrap(), 4.0);
+
+        // no move left
+        let b = board([1, 2, 1,
+                       2, 1, 2,
+                       1, 2, 1]);
+        let mut solver = Solver::new(ExpectedScore, None);
+        assert_close(solver.value(&b).ok().unwrap(), 0.0);
+        assert_eq!(solver.move_values(&b).ok().unwrap().iter()
+                   .filter(|v| v.is_some()).count(), 0);
+    }
+
+    #[test]
+    fn test_best_move_value() {
+        // the value of a position is the value of its best move
+        let b = board([3, 2, 1,
+                       2, 3, 2,
+                       1, 2, 0]);
+        let mut solver = Solver::new(ExpectedScore, Some(6));
+        let value = solver.value(&b).ok().unwrap();
+        assert!(value > 0.0);
+        assert!(solver.positions() > 0);
+        let values = solver.move_values(&b).ok().unwrap();
+        let best = values.iter().filter_map(|v| *v).fold(0.0, |a: f64, b| a.max(b));
+        assert_close(best, value);
+    }
+
+    #[test]
+    fn test_rules() {
+        let b = board([1, 0, 0,
+                       0, 0, 0,
+                       0, 0, 0]);
+        let mut rules = Rules::default();
+        rules.high_probability = 0.5;
+        let mut solver = Solver::with_rules(ReachTile(2), Some(1), rules.clone());
+        assert_close(solver.value(&b).ok().unwrap(), 0.5);
+
+        // the adversary never spawns the 4
+        rules.spawn_mode = AdversarialSpawns;
+        let mut solver = Solver::with_rules(ReachTile(2), Some(1), rules.clone());
+        assert_close(solver.value(&b).ok().unwrap(), 0.0);
+
+        // the game ends at the target, so there is nothing to score
+        let b = board([1, 1, 0,
+                       0, 0, 0,
+                       0, 0, 0]);
+        let mut rules = Rules::default();
+        rules.target = Some(1);
+        let mut solver = Solver::with_rules(ExpectedScore, Some(3), rules.clone());
+        assert_close(solver.value(&b).ok().unwrap(), 0.0);
+
+        rules.target = None;
+        rules.spawns_per_move = 2;
+        match Solver::with_rules(ExpectedScore, Some(1), rules).value(&b) {
+            Err(MultipleSpawns(2)) => (),
+            _ => fail!("expected MultipleSpawns")
+        }
+    }
+
+    #[test]
+    fn test_too_many_cells() {
+        let b = Board::new(5, 5);
+        match Solver::new(ExpectedScore, Some(1)).value(&b) {
+            Err(TooManyCells(25)) => (),
+            _ => fail!("expected TooManyCells")
+        }
+    }
+}
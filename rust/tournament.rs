@@ -0,0 +1,347 @@
+extern crate getopts;
+extern crate rand;
+extern crate sync;
+extern crate time;
+extern crate g2048;
+
+use std::cmp;
+use std::cmp::{Less, Equal, Greater};
+use std::comm;
+use std::io;
+use std::os;
+use std::rt;
+use std::task;
+use rand::Rng;
+use sync::{Arc, Mutex};
+
+use g2048::board::check_size;
+use g2048::config;
+use g2048::config::Config;
+use g2048::error::{AiError, IoFailure, ConfigFailure, EXIT_CONFIG};
+use g2048::pipe::host_game;
+use g2048::record;
+use g2048::record::{GameRecord, NoMoveLeft, TargetReached, SearchFailed};
+use g2048::search::{Move, NoMove};
+
+/// Prefix of the entrants which are the searcher of this crate,
+/// configured with the options after it.
+static SEARCHER_PREFIX: &'static str = "search:";
+
+#[deriving(Clone)]
+enum Entrant {
+    /// An AI speaking the pipe protocol, started with this command line
+    /// for each game.
+    PipeEntrant(Vec<String>),
+    /// The searcher, playing in-process.
+    SearcherEntrant(Config)
+}
+
+/// The results of an entrant over the games played so far.
+struct Standing {
+    name: String,
+    scores: Vec<u64>,
+    max_tiles: Vec<u8>,
+    /// Games which did not end by the rules, including those which
+    /// could not be played at all.
+    failures: uint,
+    moves: uint,
+    time_ns: u64
+}
+
+impl Standing {
+    fn new(name: &str) -> Standing {
+        Standing { name: String::from_str(name),
+                   scores: Vec::new(),
+                   max_tiles: Vec::new(),
+                   failures: 0,
+                   moves: 0,
+                   time_ns: 0 }
+    }
+
+    fn add(&mut self, record: &GameRecord) {
+        self.scores.push(record.score);
+        self.max_tiles.push(record.max_tile());
+        if !record.end.is_regular() {
+            self.failures += 1;
+        }
+        self.moves += record.moves.len();
+        self.time_ns += record.move_times_ns.iter().fold(0u64, |a, b| a + *b);
+    }
+
+    /// Count a game which could not be played at all as a game with
+    /// no score and no tile, so that an entrant does not rank by the
+    /// few games it managed to play.
+    fn add_failure(&mut self) {
+        self.scores.push(0);
+        self.max_tiles.push(0);
+        self.failures += 1;
+    }
+
+    fn mean_score(&self) -> f64 {
+        let total = self.scores.iter().fold(0u64, |a, b| a + *b);
+        total as f64 / cmp::max(self.scores.len(), 1) as f64
+    }
+
+    fn median_score(&self) -> u64 {
+        if self.scores.len() == 0 {
+            return 0;
+        }
+        let mut scores = self.scores.clone();
+        scores.sort();
+        *scores.get(scores.len() / 2)
+    }
+
+    /// The share of the games in which *tile* or a larger tile was
+    /// built.
+    fn tile_rate(&self, tile: u8) -> f64 {
+        let reached = self.max_tiles.iter().filter(|t| **t >= tile).count();
+        reached as f64 / cmp::max(self.max_tiles.len(), 1) as f64
+    }
+
+    fn mean_move_ms(&self) -> f64 {
+        self.time_ns as f64 / cmp::max(self.moves, 1) as f64 * 1e-6
+    }
+}
+
+/// Parse the options of a searcher entrant. The rules are those of
+/// the tournament; the searcher runs on one thread unless told
+/// otherwise, as the games are played in parallel already.
+fn parse_searcher(spec: &str, rules: &Config) -> Result<Config, AiError> {
+    let args = spec.words().map(|arg| String::from_str(arg)).collect::<Vec<String>>();
+    let opts = config::options();
+    let (mut config, matches) = match try!(config::parse_args(args.as_slice(),
+                                                              opts.as_slice())) {
+        Some(parsed) => parsed,
+        None => return Err(ConfigFailure(format!(
+            "help is not available for entrants, see ./ai --help")))
+    };
+    for opt in config::rules_options().iter() {
+        if matches.opt_present(opt.long_name.as_slice()) {
+            return Err(ConfigFailure(format!(
+                "--{} applies to all entrants and has to be given before them",
+                opt.long_name)));
+        }
+    }
+    if !matches.opt_present("threads") {
+        config.threads = 1;
+    }
+    config.rules = rules.rules.clone();
+    config.adversary_depth = rules.adversary_depth;
+    Ok(config)
+}
+
+fn parse_entrant(spec: &str, rules: &Config) -> Result<Entrant, AiError> {
+    if spec.starts_with(SEARCHER_PREFIX) {
+        let config = try!(parse_searcher(spec.slice_from(SEARCHER_PREFIX.len()), rules));
+        return Ok(SearcherEntrant(config));
+    }
+    let cmd = spec.words().map(|arg| String::from_str(arg)).collect::<Vec<String>>();
+    if cmd.len() == 0 {
+        return Err(ConfigFailure(format!("empty AI command")));
+    }
+    Ok(PipeEntrant(cmd))
+}
+
+/// Let the searcher play the game of *seed*. Its moves are timed like
+/// those of a hosted AI, but there is no time limit.
+fn play_searcher(name: &str, config: &Config, size: uint,
+                 seed: u32) -> Result<GameRecord, AiError> {
+    let mut ctx = config.context();
+    let mut game = try!(config.new_game(size, size, seed));
+    let mut record = GameRecord::new(name, seed, &game);
+    record.end = if game.won() { TargetReached } else { NoMoveLeft };
+    while !game.is_over() {
+        let board = game.board().clone();
+        ctx.max_depth = config.policy.depth_for(&board);
+        let start = time::precise_time_ns();
+        let dir = match ctx.eval(&board) {
+            Ok(decision) => match decision.best {
+                Move(_, dir) => dir,
+                NoMove => break
+            },
+            Err(e) => {
+                record.end = SearchFailed(format!("{}", e));
+                return Ok(record);
+            }
+        };
+        let elapsed = time::precise_time_ns() - start;
+        game.play(dir);
+        record.push(dir, elapsed, &game);
+        record.end = if game.won() { TargetReached } else { NoMoveLeft };
+    }
+    Ok(record)
+}
+
+/// Play the game of *seed* with *entrant* under *rules*. Searchers got
+/// the rules in parse_searcher, protocol AIs get them appended to their
+/// command line by host_game.
+fn play(name: &str, entrant: &Entrant, rules: &Config, size: uint, seed: u32,
+        timeout_ms: u64) -> Result<GameRecord, AiError> {
+    match *entrant {
+        PipeEntrant(ref cmd) => host_game(cmd.as_slice(), rules, size, size, seed, timeout_ms),
+        SearcherEntrant(ref config) => play_searcher(name, config, size, seed)
+    }
+}
+
+fn options() -> Vec<getopts::OptGroup> {
+    let mut opts = config::rules_options();
+    opts.push(getopts::optopt(
+        "", "size", "width and height of the board (4)", "N"));
+    opts.push(getopts::optopt(
+        "", "games", "number of games per entrant (10)", "N"));
+    opts.push(getopts::optopt(
+        "", "seed", "seed of the first game, the others count up (random)", "N"));
+    opts.push(getopts::optopt(
+        "", "timeout", "time an AI gets per move, in milliseconds (10000)", "MS"));
+    opts.push(getopts::optopt(
+        "", "jobs", "number of games played at the same time (one per core)", "N"));
+    opts.push(getopts::optopt(
+        "", "record", "append each game as a line of JSON to PATH", "PATH"));
+    opts.push(getopts::optflag("h", "help", "show this help"));
+    opts
+}
+
+fn print_leaderboard(standings: &mut Vec<Standing>) {
+    standings.sort_by(|a, b| {
+        if a.mean_score() > b.mean_score() {
+            Less
+        } else if a.mean_score() < b.mean_score() {
+            Greater
+        } else {
+            Equal
+        }
+    });
+    // rates for the largest tile anyone built and the two below
+    let top = standings.iter()
+        .map(|s| s.max_tiles.iter().fold(3u8, |a, b| cmp::max(a, *b)))
+        .fold(3u8, |a, b| cmp::max(a, b));
+    let tiles = [top - 2, top - 1, top];
+    println!("{:>4} {:>9} {:>9} {:>9} {:>6} {:>6} {:>6} {:>5} {:>9}  {}",
+             "rank", "mean", "median", "max",
+             1u64 << tiles[0] as uint, 1u64 << tiles[1] as uint, 1u64 << tiles[2] as uint,
+             "fail", "ms/move", "entrant");
+    for (i, standing) in standings.iter().enumerate() {
+        let max = standing.scores.iter().fold(0u64, |a, b| cmp::max(a, *b));
+        println!("{:>4} {:>9.1f} {:>9} {:>9} {:>5.0f}% {:>5.0f}% {:>5.0f}% {:>5} {:>9.2f}  {}",
+                 i + 1, standing.mean_score(), standing.median_score(), max,
+                 100.0 * standing.tile_rate(tiles[0]),
+                 100.0 * standing.tile_rate(tiles[1]),
+                 100.0 * standing.tile_rate(tiles[2]),
+                 standing.failures, standing.mean_move_ms(), standing.name);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), AiError> {
+    let opts = options();
+    let matches = try!(getopts::getopts(args, opts.as_slice()).map_err(
+        |e| ConfigFailure(e.to_err_msg())));
+    if matches.opt_present("help") {
+        println!("{}", getopts::usage(
+            "Usage: tournament [OPTIONS] ENTRANT...\n\n\
+             Play the same games with each entrant and print a leaderboard. \
+             An entrant is\nthe command line of an AI speaking the pipe \
+             protocol, e.g. \"../cpp/2049\", or\n\"search:\" followed by \
+             options of the Rust searcher, e.g. \"search:--depth 3\",\nwhich \
+             plays in-process.",
+            opts.as_slice()));
+        return Ok(());
+    }
+    if matches.free.len() == 0 {
+        return Err(ConfigFailure(format!("no entrants given")));
+    }
+
+    let mut rules = Config::default();
+    try!(rules.update_rules(&matches));
+    try!(rules.validate());
+    let mut entrants = Vec::new();
+    for spec in matches.free.iter() {
+        entrants.push((spec.clone(), try!(parse_entrant(spec.as_slice(), &rules))));
+    }
+    let size = try!(config::parse_or(&matches, "size", 4u));
+    try!(check_size(size, size).map_err(|e| ConfigFailure(format!("{}", e))));
+    let games = try!(config::parse_or(&matches, "games", 10u));
+    let seed = try!(config::parse_or(&matches, "seed", rand::task_rng().gen::<u32>()));
+    let timeout_ms = try!(config::parse_or(&matches, "timeout", 10000u64));
+    let jobs = try!(config::parse_or(&matches, "jobs", rt::default_sched_threads()));
+    if jobs < 1 {
+        return Err(ConfigFailure(format!("jobs must be at least 1")));
+    }
+    let mut record_out = try!(record::open_record_file(matches.opt_str("record")));
+
+    // the games are taken from the end, so the first seeds are played
+    // first, by all entrants
+    let mut queue = Vec::new();
+    for i in range(0, games).rev() {
+        for entrant in range(0, entrants.len()).rev() {
+            queue.push((entrant, seed + i as u32));
+        }
+    }
+    let total = queue.len();
+    let queue = Arc::new(Mutex::new(queue));
+    let entrants = Arc::new(entrants);
+    let (tx, rx) = comm::channel();
+    for _ in range(0, cmp::min(jobs, total)) {
+        let queue = queue.clone();
+        let entrants = entrants.clone();
+        let rules = rules.clone();
+        let tx = tx.clone();
+        task::spawn(proc() {
+            loop {
+                let job = {
+                    let mut queue = queue.lock();
+                    queue.pop()
+                };
+                let (entrant, game_seed) = match job {
+                    Some(job) => job,
+                    None => break
+                };
+                let (ref name, ref kind) = *entrants.get(entrant);
+                let result = play(name.as_slice(), kind, &rules, size, game_seed,
+                                  timeout_ms).map_err(|e| format!("{}", e));
+                tx.send((entrant, game_seed, result));
+            }
+        });
+    }
+    drop(tx);
+
+    let mut standings = entrants.iter().map(|&(ref name, _)| Standing::new(name.as_slice()))
+        .collect::<Vec<Standing>>();
+    for _ in range(0, total) {
+        let (entrant, game_seed, result) = rx.recv();
+        let standing = standings.get_mut(entrant);
+        match result {
+            Ok(record) => {
+                standing.add(&record);
+                match record_out {
+                    Some(ref mut out) =>
+                        try!(record.write_json(out).map_err(|e| IoFailure(e))),
+                    None => ()
+                }
+            }
+            Err(msg) => {
+                let _ = writeln!(&mut io::stderr(), "tournament: {} failed to play \
+                                                     the game of seed {}: {}",
+                                 standing.name, game_seed, msg);
+                standing.add_failure();
+            }
+        }
+    }
+
+    println!("games={} seed={} size={}", games, seed, size);
+    print_leaderboard(&mut standings);
+    Ok(())
+}
+
+fn main() {
+    let args = os::args();
+    match run(args.tail()) {
+        Ok(()) => (),
+        Err(e) => {
+            let _ = writeln!(&mut io::stderr(), "tournament: {}", e);
+            if e.exit_code() == EXIT_CONFIG {
+                let _ = writeln!(&mut io::stderr(), "tournament: try --help");
+            }
+            os::set_exit_status(e.exit_code());
+        }
+    }
+}
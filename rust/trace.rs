@@ -0,0 +1,301 @@
+use std::io;
+
+use board::{Board, Direction, Score};
+use json;
+
+/// What a node of the search tree stands for.
+#[deriving(Clone, Show)]
+pub enum NodeKind {
+    /// The board passed to the search.
+    RootNode,
+    /// A valid move on the parent board; the node board is the board
+    /// after the move, before a tile spawns.
+    MoveNode(Direction),
+    /// A tile of the given value spawned at (x, y) on the parent
+    /// board.
+    SpawnNode(uint, uint, u8)
+}
+
+/// The value the search assigned to a node.
+#[deriving(Clone, Show)]
+pub enum NodeValue {
+    Scored(Score),
+    /// No valid move on a spawn board.
+    GameOverLeaf,
+    /// A spawn board below the search depth, which is not evaluated.
+    DepthLimit,
+    /// The search has not yet assigned a value.
+    Pending
+}
+
+/// One node of a recorded search tree.
+#[deriving(Clone)]
+pub struct TraceNode {
+    /// Index of the parent node, None for the root.
+    pub parent: Option<uint>,
+    /// Depth of the move this node belongs to; zero for the root.
+    pub depth: uint,
+    pub board: Board,
+    pub kind: NodeKind,
+    /// The partial scores of move nodes: the score of the merges, the
+    /// gradient score of the board after the move and the average
+    /// value of the spawn children (the worst value under adversarial
+    /// spawns).
+    pub move_score: Score,
+    pub gradient_score: Score,
+    pub child_average: Score,
+    pub value: NodeValue
+}
+
+/// The part of a search tree explored down to a depth limit. Nodes
+/// are stored in the order the search entered them, so children
+/// always come after their parent.
+#[deriving(Clone)]
+pub struct Trace {
+    limit: uint,
+    pub nodes: Vec<TraceNode>,
+    current: Option<uint>
+}
+
+impl Trace {
+    /// Create a trace recording moves down to *limit*. A limit of
+    /// zero records nothing.
+    pub fn new(limit: uint) -> Trace {
+        Trace { limit: limit, nodes: Vec::new(), current: None }
+    }
+
+    pub fn limit(&self) -> uint {
+        self.limit
+    }
+
+    /// Record a node below the current one and make it the current
+    /// node. Returns its index, or None if the node is below the
+    /// depth limit and not recorded.
+    pub fn enter(&mut self, depth: uint, board: &Board, kind: NodeKind) -> Option<uint> {
+        if self.limit == 0 || depth > self.limit {
+            return None;
+        }
+        self.nodes.push(TraceNode { parent: self.current,
+                                    depth: depth,
+                                    board: board.clone(),
+                                    kind: kind,
+                                    move_score: 0,
+                                    gradient_score: 0,
+                                    child_average: 0,
+                                    value: Pending });
+        self.current = Some(self.nodes.len() - 1);
+        self.current
+    }
+
+    /// Make the parent of *node* the current node again.
+    pub fn leave(&mut self, node: Option<uint>) {
+        match node {
+            Some(idx) => self.current = self.nodes.get(idx).parent,
+            None => ()
+        }
+    }
+
+    pub fn set_value(&mut self, node: Option<uint>, value: NodeValue) {
+        match node {
+            Some(idx) => self.nodes.get_mut(idx).value = value,
+            None => ()
+        }
+    }
+
+    pub fn set_partial(&mut self, node: Option<uint>,
+                       move_score: Score,
+                       gradient_score: Score,
+                       child_average: Score) {
+        match node {
+            Some(idx) => {
+                let n = self.nodes.get_mut(idx);
+                n.move_score = move_score;
+                n.gradient_score = gradient_score;
+                n.child_average = child_average;
+            }
+            None => ()
+        }
+    }
+
+    /// Attach the nodes of *other*, which was recorded separately
+    /// (e.g. in another task), below *node*.
+    pub fn graft(&mut self, node: Option<uint>, other: Trace) {
+        let idx = match node {
+            Some(idx) => idx,
+            None => return
+        };
+        let offset = self.nodes.len();
+        for child in other.nodes.move_iter() {
+            let mut child = child;
+            child.parent = match child.parent {
+                Some(parent) => Some(parent + offset),
+                None => Some(idx)
+            };
+            self.nodes.push(child);
+        }
+    }
+
+    /// Write the tree as a Graphviz digraph named *name*.
+    pub fn write_dot<W: Writer>(&self, name: &str, out: &mut W) -> io::IoResult<()> {
+        try!(writeln!(out, "digraph {} \\{", json::quote(name)));
+        try!(writeln!(out, "  node [shape=box, fontname=monospace];"));
+        for (i, node) in self.nodes.iter().enumerate() {
+            let mut label = String::new();
+            label.push_str(format!("{}\n", node_title(node)).as_slice());
+            for y in range(0u, node.board.height()) {
+                for x in range(0u, node.board.width()) {
+                    label.push_str(format!("{:2} ", node.board.get(x, y)).as_slice());
+                }
+                label.push_str("\n");
+            }
+            match node.kind {
+                MoveNode(_) => label.push_str(format!(
+                    "merge {} gradient {} children {}\n",
+                    node.move_score, node.gradient_score,
+                    node.child_average).as_slice()),
+                _ => ()
+            }
+            label.push_str(format!("value {}", value_str(&node.value)).as_slice());
+            try!(writeln!(out, "  n{} [label={}];", i, json::quote(label.as_slice())));
+            match node.parent {
+                Some(parent) => try!(writeln!(out, "  n{} -> n{};", parent, i)),
+                None => ()
+            }
+        }
+        writeln!(out, "\\}")
+    }
+
+    /// Write the tree as a single line of JSON: an object holding the
+    /// flat list of nodes, each referring to its parent by index.
+    pub fn write_json<W: Writer>(&self, name: &str, out: &mut W) -> io::IoResult<()> {
+        let mut nodes = Vec::new();
+        for node in self.nodes.iter() {
+            let mut board = Vec::new();
+            for y in range(0u, node.board.height()) {
+                for cell in node.board.get_row(y).iter() {
+                    board.push(format!("{}", *cell));
+                }
+            }
+            let parent = match node.parent {
+                Some(parent) => format!("{}", parent),
+                None => String::from_str("null")
+            };
+            let kind = match node.kind {
+                RootNode => String::from_str("\"kind\":\"root\""),
+                MoveNode(dir) => format!(
+                    "\"kind\":\"move\",\"move\":{},\"move_score\":{},\
+                     \"gradient_score\":{},\"child_average\":{}",
+                    json::quote(format!("{}", dir).as_slice()),
+                    node.move_score, node.gradient_score, node.child_average),
+                SpawnNode(x, y, tile) => format!(
+                    "\"kind\":\"spawn\",\"x\":{},\"y\":{},\"tile\":{}",
+                    x, y, tile)
+            };
+            let value = match node.value {
+                Scored(score) => format!("{}", score),
+                _ => String::from_str("null")
+            };
+            nodes.push(format!(
+                "\\{\"parent\":{},\"depth\":{},\"width\":{},\"height\":{},\
+                 \"board\":[{}],{},\
+                 \"value\":{},\"outcome\":{}\\}",
+                parent, node.depth, node.board.width(), node.board.height(),
+                board.connect(","), kind, value,
+                json::quote(value_str(&node.value).as_slice())));
+        }
+        writeln!(out, "\\{\"name\":{},\"nodes\":[{}]\\}",
+                 json::quote(name), nodes.connect(","))
+    }
+}
+
+fn node_title(node: &TraceNode) -> String {
+    match node.kind {
+        RootNode => String::from_str("root"),
+        MoveNode(dir) => format!("{} (depth {})", dir, node.depth),
+        SpawnNode(x, y, tile) => format!("spawn {} at {},{}", tile, x, y)
+    }
+}
+
+fn value_str(value: &NodeValue) -> String {
+    match *value {
+        Scored(score) => format!("{}", score),
+        GameOverLeaf => String::from_str("game over"),
+        DepthLimit => String::from_str("depth limit"),
+        Pending => String::from_str("pending")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::str;
+    use board::{Board, Left};
+    use super::{Trace, RootNode, MoveNode, SpawnNode, Scored, DepthLimit};
+
+    fn trace() -> Trace {
+        let board = Board::from_raw(&Vec::from_elem(16, 0u8)).unwrap();
+        let mut trace = Trace::new(1);
+        let root = trace.enter(0, &board, RootNode);
+        let move = trace.enter(1, &board, MoveNode(Left));
+        let spawn = trace.enter(1, &board, SpawnNode(2, 3, 1));
+        assert!(trace.enter(2, &board, MoveNode(Left)).is_none());
+        trace.set_value(spawn, DepthLimit);
+        trace.leave(spawn);
+        trace.set_partial(move, 4, 10, 0);
+        trace.set_value(move, Scored(14));
+        trace.leave(move);
+        trace.set_value(root, Scored(14));
+        trace.leave(root);
+        trace
+    }
+
+    #[test]
+    fn test_trace_structure() {
+        let trace = trace();
+        assert_eq!(trace.nodes.len(), 3);
+        assert_eq!(trace.nodes.get(0).parent, None);
+        assert_eq!(trace.nodes.get(1).parent, Some(0));
+        assert_eq!(trace.nodes.get(2).parent, Some(1));
+        assert!(Trace::new(0).enter(0, &trace.nodes.get(0).board, RootNode).is_none());
+    }
+
+    #[test]
+    fn test_trace_graft() {
+        let mut trace = trace();
+        let other = trace.clone();
+        trace.graft(Some(2), other);
+        assert_eq!(trace.nodes.len(), 6);
+        assert_eq!(trace.nodes.get(3).parent, Some(2));
+        assert_eq!(trace.nodes.get(4).parent, Some(3));
+        assert_eq!(trace.nodes.get(5).parent, Some(4));
+    }
+
+    #[test]
+    fn test_trace_json() {
+        let mut out = io::MemWriter::new();
+        trace().write_json("t", &mut out).unwrap();
+        let data = out.unwrap();
+        let line = str::from_utf8(data.as_slice()).unwrap();
+        assert!(line.contains(
+            "{\"parent\":0,\"depth\":1,\"width\":4,\"height\":4,\
+             \"board\":[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],\
+             \"kind\":\"move\",\"move\":\"Left\",\"move_score\":4,\
+             \"gradient_score\":10,\"child_average\":0,\"value\":14,\
+             \"outcome\":\"14\"}"), "{}", line);
+        assert!(line.contains(
+            "\"kind\":\"spawn\",\"x\":2,\"y\":3,\"tile\":1,\"value\":null,\
+             \"outcome\":\"depth limit\"}"), "{}", line);
+    }
+
+    #[test]
+    fn test_trace_dot() {
+        let mut out = io::MemWriter::new();
+        trace().write_dot("t", &mut out).unwrap();
+        let data = out.unwrap();
+        let text = str::from_utf8(data.as_slice()).unwrap();
+        assert!(text.starts_with("digraph \"t\" {\n"), "{}", text);
+        assert!(text.contains("  n0 -> n1;\n  "), "{}", text);
+        assert!(text.contains("  n1 -> n2;\n}\n"), "{}", text);
+        assert!(text.contains("merge 4 gradient 10 children 0\\nvalue 14"), "{}", text);
+    }
+}
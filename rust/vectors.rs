@@ -0,0 +1,184 @@
+use board::Line;
+
+/// One golden test vector for the line shift, as stored in
+/// ``testdata/shift_vectors.txt``.
+pub struct ShiftVector {
+    pub input: Line,
+    pub output: Line,
+    pub merged: [bool, ..4]
+}
+
+impl Clone for ShiftVector {
+    fn clone(&self) -> ShiftVector {
+        ShiftVector { input: self.input,
+                      output: self.output,
+                      merged: self.merged }
+    }
+}
+
+fn parse_cells(src: &str) -> Option<Line> {
+    let cells = src.words().map(|word| from_str::<u8>(word))
+        .collect::<Vec<Option<u8>>>();
+    if cells.len() != 4 {
+        return None;
+    }
+    let mut result = [0u8, ..4];
+    let mut i = 0;
+    for cell in cells.iter() {
+        match *cell {
+            Some(value) => result[i] = value,
+            None => return None
+        }
+        i += 1;
+    }
+    Some(result)
+}
+
+fn parse_flags(src: &str) -> Option<[bool, ..4]> {
+    match parse_cells(src) {
+        Some(cells) => {
+            let mut result = [false, ..4];
+            let mut i = 0;
+            for cell in cells.iter() {
+                match *cell {
+                    0 => (),
+                    1 => result[i] = true,
+                    _ => return None
+                }
+                i += 1;
+            }
+            Some(result)
+        }
+        None => None
+    }
+}
+
+/// Parse a shift result of the form ``<cells> | <merge flags>``, as
+/// printed by format_result and by the conformance drivers of the
+/// other engines.
+pub fn parse_result(src: &str) -> Option<(Line, [bool, ..4])> {
+    let parts = src.split('|').collect::<Vec<&str>>();
+    if parts.len() != 2 {
+        return None;
+    }
+    match (parse_cells(*parts.get(0)), parse_flags(*parts.get(1))) {
+        (Some(output), Some(merged)) => Some((output, merged)),
+        _ => None
+    }
+}
+
+/// Format a shift result in the form accepted by parse_result.
+pub fn format_result(output: &Line, merged: &[bool, ..4]) -> String {
+    format!("{} {} {} {} | {} {} {} {}",
+            output[0], output[1], output[2], output[3],
+            merged[0] as u8, merged[1] as u8,
+            merged[2] as u8, merged[3] as u8)
+}
+
+/// Format the input cells of a vector, one line as fed to the
+/// conformance drivers.
+pub fn format_input(input: &Line) -> String {
+    format!("{} {} {} {}", input[0], input[1], input[2], input[3])
+}
+
+/// Parse a golden vector file. Empty lines and lines starting with
+/// ``#`` are skipped. On error, the offending line number (counted
+/// from one) is returned.
+pub fn parse_vectors(src: &str) -> Result<Vec<ShiftVector>, uint> {
+    let mut result = Vec::new();
+    let mut lineno = 0;
+    for line in src.lines() {
+        lineno += 1;
+        let line = line.trim();
+        if line.len() == 0 || line.starts_with("#") {
+            continue;
+        }
+
+        let parts = line.split_str("->").collect::<Vec<&str>>();
+        if parts.len() != 2 {
+            return Err(lineno);
+        }
+        match (parse_cells(*parts.get(0)), parse_result(*parts.get(1))) {
+            (Some(input), Some((output, merged))) => {
+                result.push(ShiftVector { input: input,
+                                          output: output,
+                                          merged: merged });
+            }
+            _ => return Err(lineno)
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use board::{Board, Line, shift_line_tracked};
+    use board::{Up, Down, Left, Right};
+    use super::{parse_vectors, parse_result, format_result};
+
+    static VECTORS: &'static str = include_str!("../testdata/shift_vectors.txt");
+
+    #[test]
+    fn test_shift_line_matches_golden_vectors() {
+        let vectors = parse_vectors(VECTORS).unwrap();
+        assert!(vectors.len() > 0);
+        for vector in vectors.iter() {
+            let mut line = vector.input;
+            let mut merged = [false, ..4];
+            shift_line_tracked(line.as_mut_slice(), merged.as_mut_slice());
+            assert!(line.as_slice() == vector.output.as_slice(),
+                    "{} shifted to {}, expected {}",
+                    vector.input.as_slice(), line.as_slice(),
+                    vector.output.as_slice());
+            assert!(merged.as_slice() == vector.merged.as_slice(),
+                    "{} merged {}, expected {}",
+                    vector.input.as_slice(), merged.as_slice(),
+                    vector.merged.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_shifted_board_matches_golden_vectors() {
+        // every direction has to apply the line shift towards its own
+        // edge
+        let vectors = parse_vectors(VECTORS).unwrap();
+        for vector in vectors.iter() {
+            let reverse = |line: &Line| -> Line {
+                [line[3], line[2], line[1], line[0]]
+            };
+            // the same line four times; arrays are copied, not cloned
+            let lines = |line: Line| vec![line, line, line, line];
+            let reversed = reverse(&vector.input);
+            let expected_reversed = reverse(&vector.output);
+
+            let (board, _) = Board::from_rows(lines(vector.input)).shifted_board(Left);
+            assert!(board == Board::from_rows(lines(vector.output)));
+            let (board, _) = Board::from_cols(lines(vector.input)).shifted_board(Up);
+            assert!(board == Board::from_cols(lines(vector.output)));
+            let (board, _) = Board::from_rows(lines(reversed)).shifted_board(Right);
+            assert!(board == Board::from_rows(lines(expected_reversed)));
+            let (board, _) = Board::from_cols(lines(reversed)).shifted_board(Down);
+            assert!(board == Board::from_cols(lines(expected_reversed)));
+        }
+    }
+
+    #[test]
+    fn test_result_roundtrip() {
+        let output = [3u8, 1, 0, 0];
+        let merged = [true, false, false, false];
+        let formatted = format_result(&output, &merged);
+        assert_eq!(formatted.as_slice(), "3 1 0 0 | 1 0 0 0");
+        let (parsed_output, parsed_merged) = parse_result(formatted.as_slice()).unwrap();
+        assert_eq!(parsed_output.as_slice(), output.as_slice());
+        assert_eq!(parsed_merged.as_slice(), merged.as_slice());
+    }
+
+    #[test]
+    fn test_parse_vectors_rejects_garbage() {
+        assert_eq!(parse_vectors("# comment\n\n1 1 0 0 -> 2 0 0 0 | 1 0 0 0\n").unwrap().len(),
+                   1);
+        assert_eq!(parse_vectors("1 1 0 0 -> 2 0 0 0\n").err(), Some(1));
+        assert_eq!(parse_vectors("\n1 1 0 -> 2 0 0 0 | 1 0 0 0\n").err(), Some(2));
+        assert_eq!(parse_vectors("1 1 0 0 -> 2 0 0 0 | 2 0 0 0\n").err(), Some(1));
+    }
+}